@@ -1,15 +1,23 @@
 #![no_std]
 #![feature(abi_x86_interrupt)]
+#![feature(alloc_error_handler)]
+#![feature(asm)]
 #![cfg_attr(test, no_main)]
 #![feature(custom_test_frameworks)]
 #![feature(const_fn_trait_bound)]
+#![feature(naked_functions)]
 #![test_runner(crate::test_runner)]
 #![reexport_test_harness_main = "test_main"]
 
+extern crate alloc;
+
+use core::fmt;
 use core::panic::PanicInfo;
+use core::sync::atomic::{AtomicBool, Ordering};
 
+use bootloader::BootInfo;
 #[cfg(test)]
-use bootloader::{entry_point, BootInfo};
+use bootloader::entry_point;
 
 pub mod std;
 
@@ -19,13 +27,60 @@ entry_point!(test_kernel_main);
 // Entry point for `cargo xtest`
 // noinspection RsUnresolvedReference
 #[cfg(test)]
-fn test_kernel_main(_boot_info: &'static BootInfo) -> ! {
+fn test_kernel_main(boot_info: &'static BootInfo) -> ! {
+    use x86_64::VirtAddr;
+
+    std::memory::set_physical_memory_offset(VirtAddr::new(boot_info.physical_memory_offset));
     init();
     test_main();
     std::interrupts::htl_loop();
 }
 
+// Set once the first call to `init` has claimed initialization, any later
+// calls will see this set and return without touching the hardware again.
+static INITIALIZED: AtomicBool = AtomicBool::new(false);
+
+/// Enables SSE and initializes the GDT, IDT and PIC controllers before enabling interrupts, followed by the
+/// optional subsystems. Holding left shift during boot selects safe mode, see `init_with_mode`.
+///
+/// Only the first call does any work, all subsequent calls are no-ops. Running
+/// the initialization a second time would re-program the PICs and re-enable
+/// interrupts that the caller may have intentionally disabled. Re-initializing
+/// requires an explicit teardown of the above first, which is not supported.
 pub fn init() {
+    init_once(None);
+}
+
+/// Initializes the kernel in the given mode. In safe mode only the essentials (GDT, IDT, PIC,
+/// serial and VGA) are initialized and every optional subsystem reports that it was skipped.
+pub fn init_with_mode(mode: std::boot::BootMode) {
+    init_once(Some(mode));
+}
+
+// Initializes the kernel on the first call, in the given mode or the one
+// selected by the safe mode key when none is given.
+fn init_once(mode: Option<std::boot::BootMode>) {
+    if INITIALIZED
+        .compare_exchange(false, true, Ordering::SeqCst, Ordering::SeqCst)
+        .is_err()
+    {
+        return;
+    }
+
+    // the key is only checked once, since the check consumes the scan code.
+    let mode = mode.unwrap_or_else(|| {
+        if std::boot::safe_mode_key_held() {
+            std::boot::BootMode::Safe
+        } else {
+            std::boot::BootMode::Normal
+        }
+    });
+
+    // floating point and SIMD instructions fault until SSE has been enabled.
+    std::cpu::enable_sse();
+    std::memory::init_stack_bounds();
+    std::boot::set_mode(mode);
+
     std::gdt::init();
     std::interrupts::init_idt();
 
@@ -33,6 +88,10 @@ pub fn init() {
     // cause unexpected output if the given PIC controllers are
     // misconfigured.
     unsafe { std::interrupts::PICS.lock().initialize() };
+    std::interrupts::unmask_irq(std::interrupts::SERIAL_PORT_ONE_IRQ);
+
+    // the PIT starts at ~18.2Hz, which is too coarse for timing.
+    std::pit::set_pit_frequency(std::pit::DEFAULT_FREQUENCY_HZ);
 
     // Enable interrupts to be processed by the CPU. Meaning that
     // now the CPU does listen ot the interrupt controller. Executing
@@ -42,7 +101,62 @@ pub fn init() {
     // At this point we must configure the basic hardware timer
     // (intel 8253) since its enabled by default otherwise we will
     // start getting double faults.
-    x86_64::instructions::interrupts::enable()
+    x86_64::instructions::interrupts::enable();
+
+    std::boot::init_optional(std::boot::Subsystem::Disk, std::ata::init);
+    std::boot::init_optional(std::boot::Subsystem::Mouse, std::mouse::init);
+}
+
+/// Records the complete physical memory mapping of the boot info and sets up the heap, which is
+/// skipped in safe mode, followed by demand paging. Must only be called once, see
+/// `std::memory::init`.
+///
+/// Returns the physical address the identity mapped VGA text buffer translates to through the
+/// kernel's page table, as a smoke test of the mapper before it is handed to demand paging.
+pub fn init_memory(boot_info: &'static BootInfo) -> Option<x86_64::PhysAddr> {
+    use std::memory::BootInfoFrameAllocator;
+    use x86_64::structures::paging::Translate;
+    use x86_64::VirtAddr;
+
+    let physical_memory_offset = VirtAddr::new(boot_info.physical_memory_offset);
+    std::memory::set_physical_memory_offset(physical_memory_offset);
+
+    let mut mapper = unsafe { std::memory::init(physical_memory_offset) };
+    let vga_buffer = mapper.translate_addr(VirtAddr::new(0xb8000));
+    let mut frame_allocator = unsafe { BootInfoFrameAllocator::init(&boot_info.memory_map) };
+
+    std::boot::init_optional(std::boot::Subsystem::Heap, || {
+        std::allocator::init_heap(&mut mapper, &mut frame_allocator)
+            .expect("heap initialization failed")
+    });
+
+    // page faults in the heap growth range are resolved from now on.
+    std::memory::init_demand_paging(mapper, frame_allocator);
+
+    vga_buffer
+}
+
+/// Returns the console mode the kernel was booted into.
+///
+/// Version 0.9 of the bootloader does not hand over a framebuffer, it always switches to VGA text
+/// mode before jumping to the kernel, so this is currently always `ConsoleMode::Text`. Once the
+/// boot info provides a framebuffer it should be passed to `ConsoleMode::from_framebuffer`.
+pub fn console_mode(_boot_info: &BootInfo) -> std::console::ConsoleMode {
+    std::console::ConsoleMode::from_framebuffer(None)
+}
+
+/// Returns the kernel command line, see `std::cmdline::parse`.
+///
+/// Version 0.9 of the bootloader does not hand over a command line, so it is taken from the
+/// `KERNEL_CMDLINE` environment variable when the kernel is built instead, and is empty if the
+/// variable is not set.
+pub fn cmdline(_boot_info: &BootInfo) -> &'static str {
+    option_env!("KERNEL_CMDLINE").unwrap_or("")
+}
+
+/// Returns true if `init` has already been called.
+pub fn is_initialized() -> bool {
+    INITIALIZED.load(Ordering::SeqCst)
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -53,28 +167,214 @@ pub enum QemuExitCode {
     Failed = 0x11,
 }
 
+/// Returns true if the kernel is running inside of QEMU.
+///
+/// The detection is done in two steps. First the hypervisor bit of CPUID leaf 1 must be set,
+/// which is never set on real hardware and ensures that no ports are touched outside of a
+/// virtual machine. Secondly the QEMU firmware configuration device (fw_cfg) on port 0x510
+/// must return the "QEMU" signature.
+///
+/// Limitations: other hypervisors that emulate the fw_cfg device would be reported as QEMU,
+/// and QEMU started with the hypervisor CPUID bit disabled (`-cpu host,-hypervisor`) would not
+/// be detected.
+pub fn running_under_qemu() -> bool {
+    use core::arch::x86_64::__cpuid;
+    use x86_64::instructions::port::Port;
+
+    const HYPERVISOR_BIT: u32 = 1 << 31;
+    const FW_CFG_SELECTOR: u16 = 0x510;
+    const FW_CFG_DATA: u16 = 0x511;
+    const FW_CFG_SIGNATURE: u16 = 0x0000;
+
+    let features = unsafe { __cpuid(1) };
+    if features.ecx & HYPERVISOR_BIT == 0 {
+        return false;
+    }
+
+    let mut signature = [0u8; 4];
+    unsafe {
+        let mut selector: Port<u16> = Port::new(FW_CFG_SELECTOR);
+        let mut data: Port<u8> = Port::new(FW_CFG_DATA);
+
+        selector.write(FW_CFG_SIGNATURE);
+        for byte in signature.iter_mut() {
+            *byte = data.read();
+        }
+    }
+
+    &signature == b"QEMU"
+}
+
+/// Exits QEMU through the isa-debug-exit device with the given exit code. Does nothing when not
+/// running under QEMU since the port may belong to a real device.
 #[allow(dead_code)]
 pub fn exit_qemu(exit_code: QemuExitCode) {
     use x86_64::instructions::port::Port;
 
+    if !running_under_qemu() {
+        return;
+    }
+
+    // ensure any batched output reaches the screen before the machine exits.
+    std::vga_buffer::flush();
+
     unsafe {
         let mut port = Port::new(0xf4);
         port.write(exit_code as u32);
     }
 }
 
+/// The outcome reported by `shutdown_sequence`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    Success,
+    Failure,
+}
+
+/// Shuts the machine down the same way on every exit. The final statistics are written to
+/// serial and all buffered output is flushed, before exiting QEMU through the isa-debug-exit
+/// device (present when running the tests). Without the device an ACPI shutdown is attempted,
+/// and if the machine is still running it is halted.
+///
+/// # Arguments
+///
+/// `outcome` The outcome reported to QEMU as the exit code.
+///
+/// # Example
+///
+/// ```
+/// operating_system::shutdown_sequence(Outcome::Success);
+/// ```
+pub fn shutdown_sequence(outcome: Outcome) -> ! {
+    use x86_64::instructions::interrupts;
+
+    // no further interrupts, the statistics must not change while being written.
+    interrupts::disable();
+
+    let mut serial = std::panic::lock_for_print(std::serial::serial1());
+    let mut report = ReportWriter {
+        serial: &mut *serial,
+        copy: TestOutput::new(),
+    };
+    write_shutdown_report(&mut report, outcome).expect("Printing to serial failed");
+    let copy = report.copy;
+    drop(serial);
+
+    std::serial::flush();
+    std::vga_buffer::flush();
+
+    let hook = *SHUTDOWN_HOOK.lock();
+    if let Some(hook) = hook {
+        hook(copy.as_str());
+    }
+
+    exit_qemu(match outcome {
+        Outcome::Success => QemuExitCode::Success,
+        Outcome::Failure => QemuExitCode::Failed,
+    });
+
+    // only reached without the isa-debug-exit device.
+    std::power::shutdown();
+}
+
+// A function given the report written to serial by `shutdown_sequence`
+// before QEMU is exited, used by tests to check the report.
+static SHUTDOWN_HOOK: spin::Mutex<Option<fn(&str)>> = spin::Mutex::new(None);
+
+// The size of the copy of the shutdown report kept for the hook, the report
+// written to serial is never cut short.
+const SHUTDOWN_REPORT_COPY_SIZE: usize = 512;
+
+/// Sets a function that is given the report written to serial by `shutdown_sequence` once it has
+/// been flushed and before QEMU is exited, or removes it when given `None`.
+///
+/// # Arguments
+///
+/// `hook` The function, given the report as it was written to serial.
+pub fn set_shutdown_hook(hook: Option<fn(&str)>) {
+    *SHUTDOWN_HOOK.lock() = hook;
+}
+
+// Writes the shutdown report to serial and keeps a copy for the hook.
+struct ReportWriter<'a, W: fmt::Write> {
+    serial: &'a mut W,
+    copy: TestOutput<SHUTDOWN_REPORT_COPY_SIZE>,
+}
+
+impl<W: fmt::Write> fmt::Write for ReportWriter<'_, W> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.serial.write_str(s)?;
+        fmt::Write::write_str(&mut self.copy, s)
+    }
+}
+
+/// Writes the final statistics reported by `shutdown_sequence`.
+pub fn write_shutdown_report(out: &mut impl fmt::Write, outcome: Outcome) -> fmt::Result {
+    let uptime = std::clock::uptime();
+
+    writeln!(out, "shutdown: {:?}", outcome)?;
+    writeln!(out, "  uptime: {}.{:03}s", uptime.as_secs(), uptime.subsec_millis())?;
+    writeln!(out, "  timer interrupts: {}", std::interrupts::ticks())?;
+    writeln!(out, "  breakpoints: {}", std::interrupts::breakpoint_count())?;
+    writeln!(out, "  heap used: {} bytes", std::allocator::used())?;
+    writeln!(out, "  stack headroom: {} bytes", std::memory::stack_headroom())
+}
+
+/// Collects formatted output into a fixed size buffer, so that tests (including the integration
+/// tests, which have no heap) can check what was written. Output that does not fit is dropped.
+pub struct TestOutput<const N: usize> {
+    bytes: [u8; N],
+    len: usize,
+}
+
+impl<const N: usize> TestOutput<N> {
+    pub const fn new() -> TestOutput<N> {
+        TestOutput {
+            bytes: [0; N],
+            len: 0,
+        }
+    }
+
+    /// The bytes written so far.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes[..self.len]
+    }
+
+    /// The output written so far, up to a character cut in half by the end of the buffer.
+    pub fn as_str(&self) -> &str {
+        match core::str::from_utf8(self.as_bytes()) {
+            Ok(output) => output,
+            Err(error) => core::str::from_utf8(&self.bytes[..error.valid_up_to()]).unwrap(),
+        }
+    }
+}
+
+impl<const N: usize> fmt::Write for TestOutput<N> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let end = (self.len + s.len()).min(N);
+        self.bytes[self.len..end].copy_from_slice(&s.as_bytes()[..end - self.len]);
+        self.len = end;
+        Ok(())
+    }
+}
+
 pub trait Testable {
-    fn run(&self) -> ();
+    fn run(&self) -> () {
+        self.run_to(&mut std::shell::SerialOutput);
+    }
+
+    /// Runs the test, writing its name and then the `[ok]` marker once it has passed to `out`.
+    fn run_to(&self, out: &mut dyn fmt::Write);
 }
 
 impl<T> Testable for T
 where
     T: Fn(),
 {
-    fn run(&self) {
-        serial_print!("{}...", core::any::type_name::<T>());
+    fn run_to(&self, out: &mut dyn fmt::Write) {
+        write!(out, "{}...", core::any::type_name::<T>()).expect("Printing the test failed");
         self();
-        serial_println!("[ok]");
+        writeln!(out, "[ok]").expect("Printing the test failed");
     }
 }
 
@@ -87,12 +387,19 @@ pub fn test_runner(tests: &[&dyn Testable]) {
 }
 
 pub fn test_panic_handler(info: &PanicInfo) -> ! {
+    std::panic::begin();
     serial_println!("[failed]\n");
     serial_println!("Error: {}\n", info);
     exit_qemu(QemuExitCode::Failed);
     std::interrupts::htl_loop();
 }
 
+/// Called when a heap allocation fails, there is no way to recover from running out of heap.
+#[alloc_error_handler]
+fn alloc_error_handler(layout: alloc::alloc::Layout) -> ! {
+    panic!("allocation error: {:?}", layout)
+}
+
 // During the testing, we will be exporting all our testing output to the serial port for the
 // virtual machine, using this output to read the results of the tests in the console. And thus
 // if a test panics, we will need the output, this writes to the serial port, not the screen.
@@ -101,3 +408,48 @@ pub fn test_panic_handler(info: &PanicInfo) -> ! {
 fn panic(info: &PanicInfo) -> ! {
     test_panic_handler(info)
 }
+
+// Tests
+
+#[test_case]
+fn test_init_is_idempotent() {
+    use x86_64::instructions::interrupts;
+
+    // the test kernel has already been initialized, so a second call
+    // must not re-enable the interrupts that are disabled here.
+    assert!(is_initialized());
+
+    interrupts::disable();
+    init();
+    let enabled_after_init = std::interrupts::are_enabled();
+    interrupts::enable();
+
+    assert!(!enabled_after_init);
+}
+
+#[test_case]
+fn test_running_under_qemu() {
+    assert!(running_under_qemu());
+}
+
+#[test_case]
+fn test_passing_test_reports_ok() {
+    fn passing() {}
+
+    let mut output: TestOutput<256> = TestOutput::new();
+    passing.run_to(&mut output);
+
+    // the same name and marker `test_runner` writes to serial.
+    assert!(output.as_str().ends_with("::passing...[ok]\n"));
+}
+
+#[test_case]
+fn test_shutdown_report() {
+    let mut output: TestOutput<256> = TestOutput::new();
+    write_shutdown_report(&mut output, Outcome::Success).expect("report failed");
+
+    let report = output.as_str();
+    assert!(report.starts_with("shutdown: Success\n"));
+    assert!(report.contains("uptime: "));
+    assert!(report.contains("timer interrupts: "));
+}
@@ -6,10 +6,9 @@
 #![test_runner(operating_system::test_runner)]
 #![reexport_test_harness_main = "test_main"]
 
-mod std;
-
 use bootloader::{entry_point, BootInfo};
 use core::panic::PanicInfo;
+use operating_system::{print, println, std};
 use x86_64::VirtAddr;
 
 // Defines the entry point function.
@@ -28,26 +27,56 @@ fn kernel_main(boot_info: &'static BootInfo) -> ! {
     println!("Hello World{}", "!");
 
     operating_system::init();
+    // the page table of the x86_64 crate, smoke tested by translating the
+    // identity mapped VGA buffer.
+    let vga_buffer = operating_system::init_memory(boot_info);
+    println!("{:?} -> {:?}", VirtAddr::new(0xb8000), vga_buffer);
 
-    use std::memory::BootInfoFrameAllocator;
-
-    let mut frame_allocator = unsafe {
-        BootInfoFrameAllocator::init(&boot_info.memory_map)
-    };
+    // 80x50 text mode reaches the rows past the first page of the VGA buffer
+    // through the physical memory mapping recorded by `init_memory`.
+    let args = std::cmdline::parse(operating_system::cmdline(boot_info));
+    if let Some(level) = args.get("loglevel").and_then(std::log::Level::from_name) {
+        std::log::set_max_level(level);
+    }
+    if args.get("vga") == Some("80x50") {
+        if let Err(error) = std::vga_buffer::set_80x50_mode() {
+            println!("vga=80x50 ignored: {:?}", error);
+        }
+    }
 
     // as before
     #[cfg(test)]
         test_main();
 
+    // a partially broken kernel is diagnosed over the serial port instead.
+    if std::boot::mode() == std::boot::BootMode::Safe {
+        std::boot::serial_prompt();
+    }
+
     println!("It did not crash!");
-    std::interrupts::htl_loop();
+
+    // run the typed command lines, the keyboard interrupt wakes the loop.
+    loop {
+        use std::shell::{self, ShellError};
+
+        print!("> ");
+        let mut line = [0u8; 80];
+        let len = std::keyboard::read_line(&mut line);
+
+        // only printable ascii characters are stored, the line is always valid utf-8.
+        let line = core::str::from_utf8(&line[..len]).unwrap_or("");
+        match shell::execute(line) {
+            Ok(()) | Err(ShellError::EmptyCommand) => {}
+            Err(error) => println!("{}: {:?}", line, error),
+        }
+    }
 }
 
 /// This function is called on panic.
 #[cfg(not(test))]
 #[panic_handler]
 fn panic(info: &PanicInfo) -> ! {
-    println!("{}", info);
+    std::panic::report(info);
     std::interrupts::htl_loop();
 }
 
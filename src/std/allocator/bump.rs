@@ -0,0 +1,144 @@
+use core::alloc::{GlobalAlloc, Layout};
+use core::ptr;
+
+use super::Locked;
+
+/// An allocator that hands out memory by bumping a pointer through the heap. Memory is only
+/// reclaimed once every allocation has been freed, at which point the whole heap is reused.
+pub struct BumpAllocator {
+    heap_start: usize,
+    heap_end: usize,
+    next: usize,
+    allocations: usize,
+}
+
+impl BumpAllocator {
+    /// Creates a new empty bump allocator, `init` must be called before allocating.
+    pub const fn new() -> Self {
+        BumpAllocator {
+            heap_start: 0,
+            heap_end: 0,
+            next: 0,
+            allocations: 0,
+        }
+    }
+
+    /// Initializes the bump allocator with the given heap bounds.
+    ///
+    /// This function is unsafe because the caller must guarantee that the given memory range is
+    /// unused. Also, this method must be called only once.
+    pub unsafe fn init(&mut self, heap_start: usize, heap_size: usize) {
+        self.heap_start = heap_start;
+        self.heap_end = heap_start + heap_size;
+        self.next = heap_start;
+    }
+
+    /// The number of bytes between the start of the heap and the next allocation, including any
+    /// memory of freed allocations that has not been reclaimed yet.
+    pub fn used(&self) -> usize {
+        self.next - self.heap_start
+    }
+
+    /// The number of allocations that have not been freed.
+    pub fn allocations(&self) -> usize {
+        self.allocations
+    }
+}
+
+unsafe impl GlobalAlloc for Locked<BumpAllocator> {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let mut bump = self.lock();
+
+        let alloc_start = align_up(bump.next, layout.align());
+        let alloc_end = match alloc_start.checked_add(layout.size()) {
+            Some(end) => end,
+            None => return ptr::null_mut(),
+        };
+
+        if alloc_end > bump.heap_end {
+            ptr::null_mut() // out of memory
+        } else {
+            bump.next = alloc_end;
+            bump.allocations += 1;
+            alloc_start as *mut u8
+        }
+    }
+
+    unsafe fn dealloc(&self, _ptr: *mut u8, _layout: Layout) {
+        let mut bump = self.lock();
+
+        bump.allocations -= 1;
+        if bump.allocations == 0 {
+            bump.next = bump.heap_start;
+        }
+    }
+}
+
+/// Aligns the given address upwards to the given alignment, which must be a power of two.
+fn align_up(addr: usize, align: usize) -> usize {
+    (addr + align - 1) & !(align - 1)
+}
+
+// Tests
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const ARENA_SIZE: usize = 4096;
+
+    #[repr(align(4096))]
+    struct Arena([u8; ARENA_SIZE]);
+
+    // Returns a bump allocator over its own arena, separate from the kernel heap.
+    fn arena_allocator() -> Locked<BumpAllocator> {
+        static mut ARENA: Arena = Arena([0; ARENA_SIZE]);
+
+        let allocator = Locked::new(BumpAllocator::new());
+        unsafe { allocator.lock().init(ARENA.0.as_mut_ptr() as usize, ARENA_SIZE) };
+        allocator
+    }
+
+    #[test_case]
+    fn test_bump_resets_after_all_freed() {
+        let allocator = arena_allocator();
+        let layout = Layout::from_size_align(24, 8).unwrap();
+
+        let mut pointers = [ptr::null_mut(); 64];
+        for pointer in pointers.iter_mut() {
+            *pointer = unsafe { allocator.alloc(layout) };
+            assert!(!pointer.is_null());
+        }
+
+        assert_eq!(allocator.lock().allocations(), 64);
+        assert!(allocator.lock().used() >= 64 * 24);
+
+        // memory is only reclaimed once the last allocation is freed.
+        for pointer in pointers[1..].iter() {
+            unsafe { allocator.dealloc(*pointer, layout) };
+        }
+        assert!(allocator.lock().used() >= 64 * 24);
+
+        unsafe { allocator.dealloc(pointers[0], layout) };
+        assert_eq!(allocator.lock().allocations(), 0);
+        assert_eq!(allocator.lock().used(), 0);
+
+        // the next allocation starts at the beginning of the heap again.
+        let pointer = unsafe { allocator.alloc(layout) };
+        assert_eq!(pointer, pointers[0]);
+    }
+
+    #[test_case]
+    fn test_bump_out_of_memory() {
+        let allocator = arena_allocator();
+
+        let too_large = Layout::from_size_align(ARENA_SIZE + 1, 1).unwrap();
+        assert!(unsafe { allocator.alloc(too_large) }.is_null());
+
+        // alignment padding is accounted for against the end of the heap.
+        let half = Layout::from_size_align(ARENA_SIZE / 2, 1).unwrap();
+        let aligned = Layout::from_size_align(ARENA_SIZE / 2, 4096).unwrap();
+        assert!(!unsafe { allocator.alloc(half) }.is_null());
+        assert!(unsafe { allocator.alloc(aligned) }.is_null());
+    }
+}
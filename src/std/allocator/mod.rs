@@ -0,0 +1,141 @@
+use core::alloc::{GlobalAlloc, Layout};
+use core::ops::Deref;
+#[cfg(not(feature = "bump_alloc"))]
+use linked_list_allocator::LockedHeap;
+use x86_64::{
+    structures::paging::{
+        mapper::MapToError, FrameAllocator, Mapper, Page, PageTableFlags, Size4KiB,
+    },
+    VirtAddr,
+};
+
+use crate::std::trace;
+
+pub mod bump;
+
+#[cfg(feature = "bump_alloc")]
+use bump::BumpAllocator;
+
+/// The virtual address the kernel heap starts at, chosen to be easily recognizable in page
+/// faults and far away from anything mapped by the bootloader.
+pub const HEAP_START: usize = 0x_4444_4444_0000;
+
+/// The size of the kernel heap, every page of it is mapped by `init_heap`.
+pub const HEAP_SIZE: usize = 100 * 1024;
+
+/// The virtual range directly above the kernel heap that the heap can grow into. Its pages are
+/// not mapped up front, they are mapped by the page fault handler on first access once demand
+/// paging has been enabled, see `memory::init_demand_paging`.
+pub const HEAP_GROWTH_START: usize = HEAP_START + HEAP_SIZE;
+pub const HEAP_GROWTH_SIZE: usize = 1024 * 1024;
+
+// The linked list allocator is used unless the bump allocator is selected
+// with the `bump_alloc` feature, for comparing the two.
+#[cfg(not(feature = "bump_alloc"))]
+#[global_allocator]
+static ALLOCATOR: Traced<LockedHeap> = Traced::new(LockedHeap::empty());
+
+#[cfg(feature = "bump_alloc")]
+#[global_allocator]
+static ALLOCATOR: Traced<Locked<BumpAllocator>> =
+    Traced::new(Locked::new(BumpAllocator::new()));
+
+/// A spin lock around an allocator, required to implement `GlobalAlloc` for allocators that
+/// need to mutate their state through the shared reference.
+pub struct Locked<A> {
+    inner: spin::Mutex<A>,
+}
+
+impl<A> Locked<A> {
+    pub const fn new(inner: A) -> Self {
+        Locked {
+            inner: spin::Mutex::new(inner),
+        }
+    }
+
+    pub fn lock(&self) -> spin::MutexGuard<A> {
+        self.inner.lock()
+    }
+}
+
+/// A global allocator that records every allocation in the trace buffer before passing it on to
+/// the wrapped allocator, see `trace::ALLOCATION`.
+pub struct Traced<A> {
+    inner: A,
+}
+
+impl<A> Traced<A> {
+    pub const fn new(inner: A) -> Self {
+        Traced { inner }
+    }
+}
+
+impl<A> Deref for Traced<A> {
+    type Target = A;
+
+    fn deref(&self) -> &A {
+        &self.inner
+    }
+}
+
+unsafe impl<A: GlobalAlloc> GlobalAlloc for Traced<A> {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        crate::trace!(trace::ALLOCATION, layout.size());
+        self.inner.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        self.inner.dealloc(ptr, layout)
+    }
+}
+
+/// Maps the pages of the heap region to newly allocated frames and hands the region over to the
+/// global allocator, after which `alloc` types such as `Box` and `Vec` can be used.
+///
+/// # Arguments
+///
+/// `mapper` The page table the heap region is mapped into.
+/// `frame_allocator` Used to allocate the frames backing the heap and any page tables required.
+///
+/// # Example
+///
+/// ```
+/// let mut mapper = unsafe { memory::init(physical_memory_offset) };
+/// let mut frame_allocator = unsafe { BootInfoFrameAllocator::init(&boot_info.memory_map) };
+///
+/// allocator::init_heap(&mut mapper, &mut frame_allocator).expect("heap initialization failed");
+/// ```
+pub fn init_heap(
+    mapper: &mut impl Mapper<Size4KiB>,
+    frame_allocator: &mut impl FrameAllocator<Size4KiB>,
+) -> Result<(), MapToError<Size4KiB>> {
+    let page_range = {
+        let heap_start = VirtAddr::new(HEAP_START as u64);
+        let heap_end = heap_start + HEAP_SIZE - 1u64;
+        let heap_start_page = Page::containing_address(heap_start);
+        let heap_end_page = Page::containing_address(heap_end);
+        Page::range_inclusive(heap_start_page, heap_end_page)
+    };
+
+    for page in page_range {
+        let frame = frame_allocator
+            .allocate_frame()
+            .ok_or(MapToError::FrameAllocationFailed)?;
+        let flags = PageTableFlags::PRESENT | PageTableFlags::WRITABLE;
+
+        unsafe { mapper.map_to(page, frame, flags, frame_allocator)?.flush() };
+    }
+
+    unsafe {
+        ALLOCATOR.lock().init(HEAP_START, HEAP_SIZE);
+    }
+
+    Ok(())
+}
+
+/// Returns the number of heap bytes currently allocated, zero before `init_heap`.
+pub fn used() -> usize {
+    use x86_64::instructions::interrupts;
+
+    interrupts::without_interrupts(|| ALLOCATOR.lock().used())
+}
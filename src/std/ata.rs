@@ -0,0 +1,251 @@
+use core::fmt;
+use spin::Mutex;
+use x86_64::instructions::port::{Port, PortReadOnly, PortWriteOnly};
+
+use crate::std::once::Once;
+
+// The I/O base and control ports of the primary ATA bus.
+// https://wiki.osdev.org/ATA_PIO_Mode#Addressing_Modes
+const PRIMARY_IO_BASE: u16 = 0x1F0;
+const PRIMARY_CONTROL_BASE: u16 = 0x3F6;
+
+const COMMAND_IDENTIFY: u8 = 0xEC;
+const SELECT_MASTER: u8 = 0xA0;
+
+const STATUS_ERR: u8 = 1 << 0;
+const STATUS_DRQ: u8 = 1 << 3;
+const STATUS_BSY: u8 = 1 << 7;
+
+// The number of times the status register will be polled before giving
+// up on the drive. This ensures a missing or stuck drive returns an error
+// instead of hanging the kernel.
+const POLL_LIMIT: usize = 100_000;
+
+/// The errors that can occur when communicating with an ATA drive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AtaError {
+    // No drive is attached to the selected position on the bus.
+    NoDrive,
+    // A drive is attached but it is not an ATA drive (e.g. ATAPI or SATA).
+    NotAta,
+    // The drive did not become ready within the polling limit.
+    Timeout,
+    // The drive set the error bit in the status register.
+    DeviceError,
+}
+
+/// The information extracted from the 256 word response to the IDENTIFY command.
+///
+/// reference: https://wiki.osdev.org/ATA_PIO_Mode#IDENTIFY_command
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DriveInfo {
+    model: [u8; 40],
+    serial: [u8; 20],
+    pub sectors: u64,
+    pub lba48: bool,
+}
+
+impl DriveInfo {
+    /// The model string of the drive with the trailing padding removed.
+    pub fn model(&self) -> &str {
+        trim_ata_string(&self.model)
+    }
+
+    /// The serial number of the drive with the trailing padding removed.
+    pub fn serial(&self) -> &str {
+        trim_ata_string(&self.serial)
+    }
+
+    /// The total addressable size of the drive in bytes, assuming 512 byte sectors.
+    pub fn size_bytes(&self) -> u64 {
+        self.sectors * 512
+    }
+}
+
+impl fmt::Display for DriveInfo {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} (serial {}) {} sectors, {} MiB{}",
+            self.model(),
+            self.serial(),
+            self.sectors,
+            self.size_bytes() / (1024 * 1024),
+            if self.lba48 { ", LBA48" } else { "" }
+        )
+    }
+}
+
+/// A single ATA bus operated in PIO mode.
+pub struct AtaBus {
+    data: Port<u16>,
+    sector_count: PortWriteOnly<u8>,
+    lba_low: Port<u8>,
+    lba_mid: Port<u8>,
+    lba_high: Port<u8>,
+    drive_select: PortWriteOnly<u8>,
+    command: PortWriteOnly<u8>,
+    status: PortReadOnly<u8>,
+    alternate_status: PortReadOnly<u8>,
+}
+
+impl AtaBus {
+    /// Creates a new bus from the given I/O and control base ports.
+    ///
+    /// This function is unsafe since the caller must guarantee that the ports
+    /// belong to an ATA bus and are not being used by anything else.
+    pub const unsafe fn new(io_base: u16, control_base: u16) -> AtaBus {
+        AtaBus {
+            data: Port::new(io_base),
+            sector_count: PortWriteOnly::new(io_base + 2),
+            lba_low: Port::new(io_base + 3),
+            lba_mid: Port::new(io_base + 4),
+            lba_high: Port::new(io_base + 5),
+            drive_select: PortWriteOnly::new(io_base + 6),
+            command: PortWriteOnly::new(io_base + 7),
+            status: PortReadOnly::new(io_base + 7),
+            alternate_status: PortReadOnly::new(control_base),
+        }
+    }
+
+    /// Issues the IDENTIFY command to the master drive of the bus and parses the
+    /// response.
+    pub fn identify(&mut self) -> Result<DriveInfo, AtaError> {
+        let mut words = [0u16; 256];
+
+        unsafe {
+            self.drive_select.write(SELECT_MASTER);
+            self.delay();
+
+            self.sector_count.write(0);
+            self.lba_low.write(0);
+            self.lba_mid.write(0);
+            self.lba_high.write(0);
+            self.command.write(COMMAND_IDENTIFY);
+
+            // a status of zero means that nothing is attached to the bus.
+            if self.status.read() == 0 {
+                return Err(AtaError::NoDrive);
+            }
+
+            self.poll(|status| status & STATUS_BSY == 0)?;
+
+            // ATAPI and SATA devices set the mid and high LBA registers, these
+            // do not support IDENTIFY and must not be waited on any further.
+            if self.lba_mid.read() != 0 || self.lba_high.read() != 0 {
+                return Err(AtaError::NotAta);
+            }
+
+            let status = self.poll(|status| status & (STATUS_DRQ | STATUS_ERR) != 0)?;
+            if status & STATUS_ERR != 0 {
+                return Err(AtaError::DeviceError);
+            }
+
+            for word in words.iter_mut() {
+                *word = self.data.read();
+            }
+        }
+
+        Ok(parse_identify(&words))
+    }
+
+    /// Polls the status register until the given condition holds, returning the
+    /// final status or a timeout once the polling limit has been reached.
+    unsafe fn poll(&mut self, condition: impl Fn(u8) -> bool) -> Result<u8, AtaError> {
+        for _ in 0..POLL_LIMIT {
+            let status = self.status.read();
+            if condition(status) {
+                return Ok(status);
+            }
+        }
+
+        Err(AtaError::Timeout)
+    }
+
+    /// Waits roughly 400ns for the drive to settle after a drive select, by
+    /// reading the alternate status register which takes 100ns each read.
+    unsafe fn delay(&mut self) {
+        for _ in 0..4 {
+            self.alternate_status.read();
+        }
+    }
+}
+
+// The primary ATA bus, wrapped in a Mutex to ensure only a single command
+// is in flight on the bus at a time.
+pub static PRIMARY: Mutex<AtaBus> =
+    Mutex::new(unsafe { AtaBus::new(PRIMARY_IO_BASE, PRIMARY_CONTROL_BASE) });
+
+/// Identifies the master drive on the primary ATA bus.
+pub fn identify() -> Result<DriveInfo, AtaError> {
+    PRIMARY.lock().identify()
+}
+
+// The result of identifying the primary master drive during `init`.
+static DRIVE: Once<Result<DriveInfo, AtaError>> = Once::new();
+
+/// Identifies the primary master drive, keeping the result for `drive`.
+pub fn init() {
+    DRIVE.call_once(identify);
+}
+
+/// Returns the primary master drive found by `init`, or `None` if there is no drive or `init`
+/// has not been run.
+pub fn drive() -> Option<DriveInfo> {
+    DRIVE.r#try().and_then(|result| result.ok())
+}
+
+/// Extracts the drive information from the raw IDENTIFY response.
+fn parse_identify(words: &[u16; 256]) -> DriveInfo {
+    let mut model = [0u8; 40];
+    let mut serial = [0u8; 20];
+
+    copy_ata_string(&words[27..47], &mut model);
+    copy_ata_string(&words[10..20], &mut serial);
+
+    // bit 10 of word 83 reports support for 48 bit addressing, in which case
+    // the sector count is stored in words 100 to 103 instead of 60 and 61.
+    let lba48 = words[83] & (1 << 10) != 0;
+    let sectors = if lba48 {
+        words[100..104]
+            .iter()
+            .enumerate()
+            .fold(0u64, |total, (i, &word)| total | (word as u64) << (16 * i))
+    } else {
+        (words[60] as u64) | (words[61] as u64) << 16
+    };
+
+    DriveInfo {
+        model,
+        serial,
+        sectors,
+        lba48,
+    }
+}
+
+/// Strings in the IDENTIFY response store two characters per word with the
+/// bytes swapped, this copies them back into the correct order.
+fn copy_ata_string(words: &[u16], output: &mut [u8]) {
+    for (i, word) in words.iter().enumerate() {
+        output[i * 2] = (word >> 8) as u8;
+        output[i * 2 + 1] = *word as u8;
+    }
+}
+
+fn trim_ata_string(bytes: &[u8]) -> &str {
+    core::str::from_utf8(bytes)
+        .unwrap_or("")
+        .trim_end_matches(|c| c == ' ' || c == '\0')
+}
+
+// Tests
+
+#[test_case]
+fn test_identify_qemu_disk() {
+    // QEMU boots the kernel from a disk attached as the primary master.
+    let info = identify().expect("identify failed");
+
+    assert!(info.sectors > 0);
+    assert!(!info.model().is_empty());
+    assert!(info.model().starts_with("QEMU"));
+}
@@ -0,0 +1,241 @@
+use core::fmt;
+use core::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+
+use crate::println;
+use crate::std::ps2::{self, Ps2Controller};
+use crate::std::shell::SerialOutput;
+use crate::std::{power, serial};
+
+/// How the kernel is being booted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BootMode {
+    // Every subsystem is initialized.
+    Normal,
+    // Only the essentials (GDT, IDT, PIC, serial and VGA) are initialized, all
+    // optional subsystems are skipped so a partially broken kernel can still
+    // be booted for diagnosis, and the kernel drops to `serial_prompt`.
+    Safe,
+}
+
+/// A subsystem that is skipped when booting in safe mode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum Subsystem {
+    Heap = 1 << 0,
+    Disk = 1 << 1,
+    Mouse = 1 << 2,
+    Shell = 1 << 3,
+}
+
+// Every subsystem, in the order they are listed by the prompt.
+const SUBSYSTEMS: [Subsystem; 4] = [
+    Subsystem::Heap,
+    Subsystem::Disk,
+    Subsystem::Mouse,
+    Subsystem::Shell,
+];
+
+static SAFE_MODE: AtomicBool = AtomicBool::new(false);
+
+// Bit sets of the subsystems whose initialization was attempted or skipped.
+static ATTEMPTED: AtomicU8 = AtomicU8::new(0);
+static SKIPPED: AtomicU8 = AtomicU8::new(0);
+
+// The make code of the left shift key in scan code set 1.
+const LEFT_SHIFT_PRESSED: u8 = 0x2A;
+
+// Bit 0 of the status register is set when a scan code is waiting.
+const STATUS_OUTPUT_FULL: u8 = 1 << 0;
+
+// The longest line the prompt accepts, longer lines are cut off.
+const PROMPT_LINE_LENGTH: usize = 64;
+
+/// Sets the mode that the optional subsystems are initialized in.
+pub fn set_mode(mode: BootMode) {
+    SAFE_MODE.store(mode == BootMode::Safe, Ordering::SeqCst);
+}
+
+/// Returns the mode the kernel was booted in.
+pub fn mode() -> BootMode {
+    if SAFE_MODE.load(Ordering::SeqCst) {
+        BootMode::Safe
+    } else {
+        BootMode::Normal
+    }
+}
+
+/// Returns true if the left shift key is being held down during boot, which selects safe mode.
+/// Must be called before interrupts are enabled, otherwise the keyboard handler consumes the
+/// scan code first. A waiting scan code is consumed, so this is only checked once by `init`.
+pub fn safe_mode_key_held() -> bool {
+    use x86_64::instructions::interrupts;
+
+    interrupts::without_interrupts(|| safe_mode_key_held_with(&mut *ps2::CONTROLLER.lock()))
+}
+
+/// Returns true if the scan code waiting in the given controller is the left shift key being
+/// pressed, see `safe_mode_key_held`.
+pub fn safe_mode_key_held_with(controller: &mut impl Ps2Controller) -> bool {
+    controller.read_status() & STATUS_OUTPUT_FULL != 0
+        && controller.read_data() == LEFT_SHIFT_PRESSED
+}
+
+/// Runs the initialization of an optional subsystem unless booting in safe mode, in which case
+/// it is reported as skipped. Returns true if the initialization was run.
+///
+/// # Arguments
+///
+/// `subsystem` The subsystem being initialized.
+/// `init` The initialization of the subsystem.
+///
+/// # Example
+///
+/// ```
+/// boot::init_optional(Subsystem::Disk, || ata::init());
+/// ```
+pub fn init_optional(subsystem: Subsystem, init: impl FnOnce()) -> bool {
+    if mode() == BootMode::Safe {
+        skip(subsystem);
+        return false;
+    }
+
+    ATTEMPTED.fetch_or(subsystem as u8, Ordering::SeqCst);
+    init();
+    true
+}
+
+/// Returns true if the initialization of the subsystem has been run.
+pub fn was_attempted(subsystem: Subsystem) -> bool {
+    ATTEMPTED.load(Ordering::SeqCst) & subsystem as u8 != 0
+}
+
+/// Returns true if the initialization of the subsystem was skipped by safe mode.
+pub fn was_skipped(subsystem: Subsystem) -> bool {
+    SKIPPED.load(Ordering::SeqCst) & subsystem as u8 != 0
+}
+
+fn skip(subsystem: Subsystem) {
+    SKIPPED.fetch_or(subsystem as u8, Ordering::SeqCst);
+    println!("safe mode: skipped {:?} initialization", subsystem);
+}
+
+/// Runs the minimal prompt of safe mode on the first serial port in place of the shell, which is
+/// reported as skipped. Only commands that don't depend on any optional subsystem are offered:
+///
+/// `status` Lists the boot mode and the skipped subsystems.
+/// `reboot` Reboots the machine.
+/// `shutdown` Powers off the machine.
+pub fn serial_prompt() -> ! {
+    skip(Subsystem::Shell);
+
+    let mut line = [0; PROMPT_LINE_LENGTH];
+    let mut len = 0;
+    crate::serial_print!("safe> ");
+
+    loop {
+        while let Some(byte) = serial::serial_read_byte() {
+            match byte {
+                b'\r' | b'\n' => {
+                    crate::serial_println!();
+                    // only ASCII bytes are added to the line.
+                    let command = core::str::from_utf8(&line[..len]).unwrap_or("");
+                    prompt_command_to(&mut SerialOutput, command).unwrap();
+                    len = 0;
+                    crate::serial_print!("safe> ");
+                }
+                // backspace and delete remove the last character.
+                0x08 | 0x7F if len > 0 => {
+                    len -= 1;
+                    crate::serial_print!("\x08 \x08");
+                }
+                byte if byte.is_ascii_graphic() || byte == b' ' => {
+                    if len < line.len() {
+                        line[len] = byte;
+                        len += 1;
+                        crate::serial_print!("{}", byte as char);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        // the serial interrupt wakes the loop once the next byte arrives.
+        x86_64::instructions::hlt();
+    }
+}
+
+/// Executes a single line of the safe mode prompt, writing its output to the given writer. See
+/// `serial_prompt` for the commands.
+pub fn prompt_command_to(out: &mut impl fmt::Write, line: &str) -> fmt::Result {
+    match line.trim() {
+        "" => Ok(()),
+        "status" => {
+            write!(out, "mode: {:?}\nskipped:", mode())?;
+            for subsystem in SUBSYSTEMS.iter().filter(|&&s| was_skipped(s)) {
+                write!(out, " {:?}", subsystem)?;
+            }
+            writeln!(out)
+        }
+        "reboot" => power::reboot(),
+        "shutdown" => power::shutdown(),
+        _ => writeln!(out, "commands: status, reboot, shutdown"),
+    }
+}
+
+// Tests
+
+// A controller holding at most a single scan code.
+#[cfg(test)]
+struct FakeKeyboard {
+    scan_code: Option<u8>,
+}
+
+#[cfg(test)]
+impl Ps2Controller for FakeKeyboard {
+    fn read_status(&mut self) -> u8 {
+        if self.scan_code.is_some() {
+            STATUS_OUTPUT_FULL
+        } else {
+            0
+        }
+    }
+
+    fn read_data(&mut self) -> u8 {
+        self.scan_code.take().unwrap()
+    }
+
+    fn write_data(&mut self, _value: u8) {}
+
+    fn write_command(&mut self, _value: u8) {}
+}
+
+#[test_case]
+fn test_safe_mode_key_held() {
+    let mut held = FakeKeyboard {
+        scan_code: Some(LEFT_SHIFT_PRESSED),
+    };
+    assert!(safe_mode_key_held_with(&mut held));
+    // the scan code is consumed, a second check no longer sees it.
+    assert!(!safe_mode_key_held_with(&mut held));
+
+    let mut other = FakeKeyboard {
+        scan_code: Some(0x1E),
+    };
+    assert!(!safe_mode_key_held_with(&mut other));
+
+    let mut empty = FakeKeyboard { scan_code: None };
+    assert!(!safe_mode_key_held_with(&mut empty));
+}
+
+#[test_case]
+fn test_prompt_command_lists_skipped_subsystems() {
+    let mut out = crate::TestOutput::<128>::new();
+
+    // the unit tests boot in normal mode, nothing is skipped.
+    prompt_command_to(&mut out, " status ").unwrap();
+    assert_eq!(out.as_str(), "mode: Normal\nskipped:\n");
+
+    let mut out = crate::TestOutput::<128>::new();
+    prompt_command_to(&mut out, "help").unwrap();
+    assert_eq!(out.as_str(), "commands: status, reboot, shutdown\n");
+}
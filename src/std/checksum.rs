@@ -0,0 +1,95 @@
+// The reversed form of the standard CRC-32 polynomial (IEEE 802.3), as used by
+// zlib, gzip and PNG, so that checksums agree with those computed on the host.
+const CRC32_POLYNOMIAL: u32 = 0xEDB8_8320;
+
+// The largest prime smaller than 65536, the modulus of both Adler-32 sums.
+const ADLER32_MODULUS: u32 = 65521;
+
+// The CRC of every possible byte value, built at compile time.
+static CRC32_TABLE: [u32; 256] = build_crc32_table();
+
+const fn build_crc32_table() -> [u32; 256] {
+    let mut table = [0u32; 256];
+    let mut byte = 0;
+
+    while byte < 256 {
+        let mut crc = byte as u32;
+        let mut bit = 0;
+
+        while bit < 8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ CRC32_POLYNOMIAL
+            } else {
+                crc >> 1
+            };
+            bit += 1;
+        }
+
+        table[byte] = crc;
+        byte += 1;
+    }
+
+    table
+}
+
+/// Computes the standard CRC-32 checksum of the data.
+///
+/// # Arguments
+///
+/// `data` The bytes being checksummed.
+///
+/// # Example
+///
+/// ```
+/// assert_eq!(checksum::crc32(b"123456789"), 0xCBF43926);
+/// ```
+pub fn crc32(data: &[u8]) -> u32 {
+    let crc = data.iter().fold(0xFFFF_FFFF, |crc, &byte| {
+        CRC32_TABLE[((crc ^ byte as u32) & 0xFF) as usize] ^ (crc >> 8)
+    });
+
+    !crc
+}
+
+/// Computes the Adler-32 checksum of the data, a faster but weaker alternative to `crc32`.
+///
+/// # Arguments
+///
+/// `data` The bytes being checksummed.
+pub fn adler32(data: &[u8]) -> u32 {
+    let mut a: u32 = 1;
+    let mut b: u32 = 0;
+
+    // the sums are reduced only once per chunk, 5552 being the most bytes that
+    // can be summed before `b` could overflow.
+    for chunk in data.chunks(5552) {
+        for &byte in chunk {
+            a += byte as u32;
+            b += a;
+        }
+
+        a %= ADLER32_MODULUS;
+        b %= ADLER32_MODULUS;
+    }
+
+    (b << 16) | a
+}
+
+// Tests
+
+#[test_case]
+fn test_crc32_known_vectors() {
+    assert_eq!(crc32(b""), 0x0000_0000);
+    assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+    assert_eq!(
+        crc32(b"The quick brown fox jumps over the lazy dog"),
+        0x414F_A339
+    );
+}
+
+#[test_case]
+fn test_adler32_known_vectors() {
+    assert_eq!(adler32(b""), 0x0000_0001);
+    assert_eq!(adler32(b"123456789"), 0x091E_01DE);
+    assert_eq!(adler32(b"Wikipedia"), 0x11E6_0398);
+}
@@ -0,0 +1,143 @@
+use core::time::Duration;
+use spin::Mutex;
+
+#[cfg(test)]
+use core::sync::atomic::{AtomicU64, Ordering};
+
+use crate::std::interrupts::{ms_to_ticks, ticks_to_ms};
+
+/// A source of timer ticks, allowing time dependent code to be driven by a fake clock during
+/// testing instead of waiting on real timer interrupts.
+pub trait Clock: Sync {
+    /// Returns the number of ticks that have passed since the clock started.
+    fn ticks(&self) -> u64;
+}
+
+/// The clock driven by the PIT timer interrupt, see `interrupts::ticks`.
+pub struct PitClock;
+
+impl Clock for PitClock {
+    fn ticks(&self) -> u64 {
+        crate::std::interrupts::ticks()
+    }
+}
+
+/// The clock used unless another one has been set with `set_clock`.
+pub static PIT_CLOCK: PitClock = PitClock;
+
+// The clock read by `ticks`, only locked with interrupts disabled since it is
+// read from interrupt handlers as well.
+static CLOCK: Mutex<&'static dyn Clock> = Mutex::new(&PIT_CLOCK);
+
+/// Replaces the clock read by `ticks`, `uptime`, `Timeout`, `interrupts::sleep_ticks` and the sleep
+/// future.
+///
+/// # Arguments
+///
+/// `clock` The new source of ticks, `&PIT_CLOCK` restores the real clock.
+///
+/// # Example
+///
+/// ```
+/// static FAKE: FakeClock = FakeClock::new();
+///
+/// clock::set_clock(&FAKE);
+/// FAKE.advance(10);
+/// clock::set_clock(&clock::PIT_CLOCK);
+/// ```
+pub fn set_clock(clock: &'static dyn Clock) {
+    use x86_64::instructions::interrupts;
+
+    interrupts::without_interrupts(|| *CLOCK.lock() = clock);
+}
+
+/// Returns the ticks of the current clock.
+pub fn ticks() -> u64 {
+    use x86_64::instructions::interrupts;
+
+    let clock = interrupts::without_interrupts(|| *CLOCK.lock());
+    clock.ticks()
+}
+
+/// Returns the time passed since the current clock started, with the resolution of a tick.
+pub fn uptime() -> Duration {
+    Duration::from_millis(ticks_to_ms(ticks()))
+}
+
+/// A deadline on the current clock, used to give up on operations that take too long.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Timeout {
+    deadline: u64,
+}
+
+impl Timeout {
+    /// Creates a timeout that expires after at least `ms` milliseconds.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let timeout = Timeout::after_ms(100);
+    /// while !device_ready() {
+    ///     if timeout.expired() {
+    ///         return Err(Error::Timeout);
+    ///     }
+    /// }
+    /// ```
+    pub fn after_ms(ms: u64) -> Timeout {
+        Timeout {
+            deadline: ticks() + ms_to_ticks(ms),
+        }
+    }
+
+    /// Returns true once the deadline has been reached.
+    pub fn expired(&self) -> bool {
+        ticks() >= self.deadline
+    }
+}
+
+/// A clock whose ticks only move when advanced manually.
+#[cfg(test)]
+pub struct FakeClock {
+    ticks: AtomicU64,
+}
+
+#[cfg(test)]
+impl FakeClock {
+    pub const fn new() -> FakeClock {
+        FakeClock {
+            ticks: AtomicU64::new(0),
+        }
+    }
+
+    /// Moves the clock forward by the given number of ticks.
+    pub fn advance(&self, ticks: u64) {
+        self.ticks.fetch_add(ticks, Ordering::SeqCst);
+    }
+}
+
+#[cfg(test)]
+impl Clock for FakeClock {
+    fn ticks(&self) -> u64 {
+        self.ticks.load(Ordering::SeqCst)
+    }
+}
+
+// Tests
+
+#[test_case]
+fn test_fake_clock_expires_timeout() {
+    static FAKE: FakeClock = FakeClock::new();
+    set_clock(&FAKE);
+
+    let timeout = Timeout::after_ms(60_000);
+    assert!(!timeout.expired());
+
+    FAKE.advance(ms_to_ticks(60_000) - 1);
+    assert!(!timeout.expired());
+
+    FAKE.advance(1);
+    assert!(timeout.expired());
+    assert!(uptime() >= Duration::from_secs(59));
+
+    set_clock(&PIT_CLOCK);
+}
@@ -0,0 +1,107 @@
+/// A single argument of the kernel command line, borrowed from the command line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Arg<'a> {
+    // A bare word without an equals sign, e.g. `quiet`.
+    Flag(&'a str),
+    // A `key=value` pair, split at the first equals sign.
+    Pair { key: &'a str, value: &'a str },
+}
+
+/// The arguments of the kernel command line. Nothing is copied or allocated, the arguments are
+/// found by walking the command line again on every lookup, which is cheap for the few short
+/// arguments a command line holds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CmdlineArgs<'a> {
+    cmdline: &'a str,
+}
+
+impl<'a> CmdlineArgs<'a> {
+    /// Returns the arguments in the order they were given.
+    pub fn args(&self) -> impl Iterator<Item = Arg<'a>> {
+        self.cmdline
+            .split_whitespace()
+            .map(|arg| match arg.find('=') {
+                Some(index) => Arg::Pair {
+                    key: &arg[..index],
+                    value: &arg[index + 1..],
+                },
+                None => Arg::Flag(arg),
+            })
+    }
+
+    /// Returns the value of the given key, the last one if the key is given more than once so
+    /// that later arguments override earlier ones.
+    ///
+    /// # Arguments
+    ///
+    /// `key` The key before the equals sign.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let level = cmdline::parse("loglevel=debug").get("loglevel");
+    /// assert_eq!(level, Some("debug"));
+    /// ```
+    pub fn get(&self, key: &str) -> Option<&'a str> {
+        self.args()
+            .filter_map(|arg| match arg {
+                Arg::Pair { key: k, value } if k == key => Some(value),
+                _ => None,
+            })
+            .last()
+    }
+
+    /// Returns true if the bare flag was given.
+    ///
+    /// # Arguments
+    ///
+    /// `name` The name of the flag, a `key=value` pair with the same key does not count.
+    pub fn has_flag(&self, name: &str) -> bool {
+        self.args().any(|arg| arg == Arg::Flag(name))
+    }
+}
+
+/// Parses the kernel command line, arguments are separated by whitespace and are either bare
+/// flags or `key=value` pairs.
+///
+/// # Arguments
+///
+/// `cmdline` The command line, which the returned arguments borrow from.
+///
+/// # Example
+///
+/// ```
+/// let args = cmdline::parse("loglevel=debug quiet");
+/// if args.has_flag("quiet") {
+///     log::set_max_level(Level::Error);
+/// }
+/// ```
+pub fn parse(cmdline: &str) -> CmdlineArgs {
+    CmdlineArgs { cmdline }
+}
+
+// Tests
+
+#[test_case]
+fn test_parse_cmdline() {
+    let args = parse("loglevel=debug quiet vga=80x50");
+
+    assert_eq!(args.get("loglevel"), Some("debug"));
+    assert_eq!(args.get("vga"), Some("80x50"));
+    assert_eq!(args.get("quiet"), None);
+    assert!(args.has_flag("quiet"));
+    assert!(!args.has_flag("loglevel"));
+    assert_eq!(args.args().count(), 3);
+}
+
+#[test_case]
+fn test_parse_cmdline_edge_cases() {
+    let args = parse("  a=1\tkey= a=2 x=y=z ");
+
+    // the last value wins, the value may be empty or hold further equals signs.
+    assert_eq!(args.get("a"), Some("2"));
+    assert_eq!(args.get("key"), Some(""));
+    assert_eq!(args.get("x"), Some("y=z"));
+
+    assert_eq!(parse("").args().next(), None);
+}
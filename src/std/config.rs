@@ -0,0 +1,202 @@
+use core::fmt;
+
+use crate::std::interrupts::{self, DoubleFaultAction};
+use crate::std::log::{self, Level};
+use crate::std::vga_buffer;
+
+/// The value of a setting.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Value {
+    Bool(bool),
+    U64(u64),
+    Level(Level),
+}
+
+impl Value {
+    /// Parses the string as a value of the same type as this value, used to change a setting
+    /// from its textual form.
+    pub fn parse_same(&self, s: &str) -> Result<Value, ConfigError> {
+        match self {
+            Value::Bool(_) => match s {
+                "true" | "on" | "1" => Ok(Value::Bool(true)),
+                "false" | "off" | "0" => Ok(Value::Bool(false)),
+                _ => Err(ConfigError::InvalidValue),
+            },
+            Value::U64(_) => s.parse().map(Value::U64).map_err(|_| ConfigError::InvalidValue),
+            Value::Level(_) => {
+                Level::from_name(s).map(Value::Level).ok_or(ConfigError::InvalidValue)
+            }
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Value::Bool(value) => write!(f, "{}", value),
+            Value::U64(value) => write!(f, "{}", value),
+            Value::Level(level) => write!(f, "{}", level.name()),
+        }
+    }
+}
+
+/// The errors that can occur when reading or changing a setting.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfigError {
+    // No setting has the given name.
+    UnknownKey,
+    // The value is of a different type than the setting.
+    TypeMismatch,
+    // The string could not be parsed as a value of the setting's type.
+    InvalidValue,
+}
+
+// A single setting, read and written through the functions of the module
+// that owns the underlying state so that both stay in sync.
+struct Setting {
+    name: &'static str,
+    description: &'static str,
+    get: fn() -> Value,
+    set: fn(Value),
+}
+
+// All settings of the kernel, the setters are only called with a value of the
+// type returned by the getter.
+static SETTINGS: [Setting; 5] = [
+    Setting {
+        name: "timer_dots",
+        description: "print a dot on every timer tick",
+        get: || Value::Bool(interrupts::timer_dots()),
+        set: |value| interrupts::set_timer_dots(value == Value::Bool(true)),
+    },
+    Setting {
+        name: "log_level",
+        description: "the least severe level of log messages that are written",
+        get: || Value::Level(log::max_level()),
+        set: |value| {
+            if let Value::Level(level) = value {
+                log::set_max_level(level);
+            }
+        },
+    },
+    Setting {
+        name: "serial_mirror",
+        description: "mirror the screen output to the serial port",
+        get: || Value::Bool(vga_buffer::serial_mirror()),
+        set: |value| vga_buffer::set_serial_mirror(value == Value::Bool(true)),
+    },
+    Setting {
+        name: "handler_benchmark",
+        description: "measure the timer and keyboard handler overhead",
+        get: || Value::Bool(interrupts::handler_benchmark_enabled()),
+        set: |value| interrupts::set_handler_benchmark(value == Value::Bool(true)),
+    },
+    Setting {
+        name: "double_fault_exits_qemu",
+        description: "exit QEMU with success on a double fault instead of panicking",
+        get: || {
+            Value::Bool(interrupts::double_fault_action() == DoubleFaultAction::ExitQemuSuccess)
+        },
+        set: |value| {
+            interrupts::set_double_fault_action(match value {
+                Value::Bool(true) => DoubleFaultAction::ExitQemuSuccess,
+                _ => DoubleFaultAction::Panic,
+            })
+        },
+    },
+];
+
+fn find(name: &str) -> Result<&'static Setting, ConfigError> {
+    SETTINGS
+        .iter()
+        .find(|setting| setting.name == name)
+        .ok_or(ConfigError::UnknownKey)
+}
+
+/// Returns the current value of the named setting.
+///
+/// # Example
+///
+/// ```
+/// let dots = config::get("timer_dots")?;
+/// ```
+pub fn get(name: &str) -> Result<Value, ConfigError> {
+    Ok((find(name)?.get)())
+}
+
+/// Changes the named setting, the value must be of the same type as the current value.
+///
+/// # Arguments
+///
+/// `name` The name of the setting.
+/// `value` The new value of the setting.
+///
+/// # Example
+///
+/// ```
+/// config::set("timer_dots", Value::Bool(false))?;
+/// ```
+pub fn set(name: &str, value: Value) -> Result<(), ConfigError> {
+    let setting = find(name)?;
+
+    match ((setting.get)(), value) {
+        (Value::Bool(_), Value::Bool(_))
+        | (Value::U64(_), Value::U64(_))
+        | (Value::Level(_), Value::Level(_)) => {
+            (setting.set)(value);
+            Ok(())
+        }
+        _ => Err(ConfigError::TypeMismatch),
+    }
+}
+
+/// Changes the named setting to the value parsed from the string, see `Value::parse_same`.
+pub fn set_from_str(name: &str, value: &str) -> Result<(), ConfigError> {
+    let value = get(name)?.parse_same(value)?;
+    set(name, value)
+}
+
+/// Writes every setting with its current value and description, one per line.
+pub fn write_settings(out: &mut impl fmt::Write) -> fmt::Result {
+    for setting in SETTINGS.iter() {
+        writeln!(out, "{} = {}  ({})", setting.name, (setting.get)(), setting.description)?;
+    }
+
+    Ok(())
+}
+
+// Tests
+
+#[test_case]
+fn test_set_and_get() {
+    assert_eq!(get("timer_dots"), Ok(Value::Bool(cfg!(feature = "timer_dots"))));
+
+    set("timer_dots", Value::Bool(false)).unwrap();
+    assert_eq!(get("timer_dots"), Ok(Value::Bool(false)));
+    assert!(!interrupts::timer_dots());
+
+    set_from_str("timer_dots", "on").unwrap();
+    assert_eq!(get("timer_dots"), Ok(Value::Bool(true)));
+    set("timer_dots", Value::Bool(cfg!(feature = "timer_dots"))).unwrap();
+
+    let level = log::max_level();
+    set_from_str("log_level", "trace").unwrap();
+    assert_eq!(get("log_level"), Ok(Value::Level(Level::Trace)));
+    assert_eq!(log::max_level(), Level::Trace);
+    assert_eq!(set_from_str("log_level", "loud"), Err(ConfigError::InvalidValue));
+    set("log_level", Value::Level(level)).unwrap();
+
+    let mirror = vga_buffer::serial_mirror();
+    set("serial_mirror", Value::Bool(!mirror)).unwrap();
+    assert_eq!(vga_buffer::serial_mirror(), !mirror);
+    set("serial_mirror", Value::Bool(mirror)).unwrap();
+
+    set_from_str("double_fault_exits_qemu", "true").unwrap();
+    assert_eq!(interrupts::double_fault_action(), DoubleFaultAction::ExitQemuSuccess);
+    set("double_fault_exits_qemu", Value::Bool(false)).unwrap();
+    assert_eq!(get("double_fault_exits_qemu"), Ok(Value::Bool(false)));
+
+    assert_eq!(get("missing"), Err(ConfigError::UnknownKey));
+    assert_eq!(set("timer_dots", Value::U64(1)), Err(ConfigError::TypeMismatch));
+    assert_eq!(set_from_str("timer_dots", "maybe"), Err(ConfigError::InvalidValue));
+}
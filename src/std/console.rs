@@ -0,0 +1,342 @@
+use core::fmt;
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+use x86_64::VirtAddr;
+
+use crate::std::framebuffer::{PixelFormat, FRAMEBUFFER_WRITER};
+use crate::std::keyboard::{Key, KeyEvent, KeyState};
+use crate::std::once::Once;
+use crate::std::panic::lock_for_print;
+
+use crate::std::sync::TryMutex;
+use crate::std::vga_buffer::{
+    self, kernel_writer, Buffer, Color, ColorCode, VgaDimensions, Writer,
+};
+
+/// A description of a linear framebuffer handed over by the bootloader, drawn to with
+/// `framebuffer::FrameBuffer`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FramebufferInfo {
+    // The virtual address of the first pixel.
+    pub address: u64,
+    pub width: usize,
+    pub height: usize,
+    // The number of pixels between the start of each row, which may be larger than the width.
+    pub stride: usize,
+    // The number of bytes of a pixel, at least 3.
+    pub bytes_per_pixel: usize,
+    pub format: PixelFormat,
+}
+
+impl FramebufferInfo {
+    /// Returns the number of bytes between the start of two rows.
+    pub fn pitch(&self) -> usize {
+        self.stride * self.bytes_per_pixel
+    }
+
+    /// Returns the size of the framebuffer in bytes.
+    pub fn size(&self) -> usize {
+        self.pitch() * self.height
+    }
+}
+
+/// The kind of output that is available to the kernel after booting.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConsoleMode {
+    // The VGA text buffer at 0xb8000.
+    Text,
+    // A linear framebuffer, text must be rendered into it as pixels.
+    Graphics { info: FramebufferInfo },
+}
+
+impl ConsoleMode {
+    /// Selects the console mode for the framebuffer provided by the bootloader, if any.
+    ///
+    /// # Arguments
+    ///
+    /// `framebuffer` The framebuffer provided by the bootloader, or `None` when booted in
+    /// VGA text mode.
+    pub fn from_framebuffer(framebuffer: Option<FramebufferInfo>) -> ConsoleMode {
+        match framebuffer {
+            Some(info) => ConsoleMode::Graphics { info },
+            None => ConsoleMode::Text,
+        }
+    }
+}
+
+/// A backend that `println!` output can be written to, implemented by every writer so that the
+/// kernel can select the one matching the console mode.
+pub trait Console: fmt::Write {
+    /// The number of text columns and rows available.
+    fn dimensions(&self) -> (usize, usize);
+
+    /// Writes the formatted arguments in the given foreground color, the following output keeps
+    /// the previous color. Backends without the VGA colors write the arguments unchanged.
+    fn write_colored(&mut self, _foreground: Color, args: fmt::Arguments) -> fmt::Result {
+        self.write_fmt(args)
+    }
+}
+
+impl Console for Writer {
+    fn dimensions(&self) -> (usize, usize) {
+        (self.width(), self.height())
+    }
+
+    fn write_colored(&mut self, foreground: Color, args: fmt::Arguments) -> fmt::Result {
+        let previous = self.color_code;
+
+        self.color_code = previous.with_foreground(foreground);
+        let result = self.write_fmt(args);
+        self.color_code = previous;
+
+        result
+    }
+}
+
+// Set while `ConsoleMode::Graphics` is selected, `println!` then writes to the
+// framebuffer writer instead of the kernel console.
+static GRAPHICS: AtomicBool = AtomicBool::new(false);
+
+/// Selects the backend that `println!` writes to, the kernel console of the VGA text buffer in
+/// `ConsoleMode::Text` and the writer set up by `framebuffer::init` in `ConsoleMode::Graphics`.
+pub fn select(mode: ConsoleMode) {
+    GRAPHICS.store(
+        matches!(mode, ConsoleMode::Graphics { .. }),
+        Ordering::SeqCst,
+    );
+}
+
+/// Runs the closure with the backend selected by `select`, which stays locked while the closure
+/// runs. Falls back to the kernel console while no framebuffer writer has been set up. Must be
+/// called with interrupts disabled, since interrupt handlers print as well.
+pub fn with_selected<R>(f: impl FnOnce(&mut dyn Console) -> R) -> R {
+    if GRAPHICS.load(Ordering::SeqCst) {
+        if let Some(writer) = lock_for_print(&FRAMEBUFFER_WRITER).as_mut() {
+            return f(writer);
+        }
+    }
+
+    f(&mut *lock_for_print(kernel_writer()))
+}
+
+/// The number of virtual consoles, console 0 is the kernel output of `println!` and the others
+/// are written to with `write_to`. Alt+F1 to Alt+F4 switch between them.
+pub const CONSOLE_COUNT: usize = 4;
+
+/// The errors that can occur when using a virtual console.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConsoleError {
+    // The console number is not below `CONSOLE_COUNT`.
+    NoSuchConsole,
+}
+
+// The consoles after the first, each a writer that holds its own screen and
+// cursor in its back buffer while hidden. Only ever locked with interrupts
+// disabled, since a switch locks the kernel console along with them, which
+// interrupt handlers print to and would dead lock on.
+static CONSOLES: Once<[TryMutex<Writer>; CONSOLE_COUNT - 1]> = Once::new();
+
+// The console currently shown on the screen.
+static ACTIVE: AtomicUsize = AtomicUsize::new(0);
+
+fn new_console() -> Writer {
+    // all consoles share the VGA buffer, only the shown console writes to it.
+    Writer::new_hidden(
+        ColorCode::new(Color::LightGray, Color::Black),
+        unsafe { &mut *(0xb8000 as *mut Buffer) },
+    )
+}
+
+fn consoles() -> &'static [TryMutex<Writer>; CONSOLE_COUNT - 1] {
+    CONSOLES.call_once(|| {
+        [
+            TryMutex::new("console 1", new_console()),
+            TryMutex::new("console 2", new_console()),
+            TryMutex::new("console 3", new_console()),
+        ]
+    })
+}
+
+fn console(n: usize) -> Result<&'static TryMutex<Writer>, ConsoleError> {
+    match n {
+        0 => Ok(kernel_writer()),
+        n if n < CONSOLE_COUNT => Ok(&consoles()[n - 1]),
+        _ => Err(ConsoleError::NoSuchConsole),
+    }
+}
+
+/// Returns the number of the console currently shown on the screen.
+pub fn active() -> usize {
+    ACTIVE.load(Ordering::SeqCst)
+}
+
+/// Shows the given console on the screen, the previously shown console keeps its content and
+/// cursor and continues to receive output in the background.
+///
+/// # Arguments
+///
+/// `n` The number of the console, below `CONSOLE_COUNT`.
+///
+/// # Example
+///
+/// ```
+/// console::switch_to(1)?;
+/// ```
+pub fn switch_to(n: usize) -> Result<(), ConsoleError> {
+    use x86_64::instructions::interrupts;
+
+    let next = console(n)?;
+
+    interrupts::without_interrupts(|| {
+        let current = active();
+        if current == n {
+            return Ok(());
+        }
+
+        // hide before showing, so the screen is saved before being replaced.
+        console(current)?.lock().set_hidden(true);
+        next.lock().set_hidden(false);
+
+        ACTIVE.store(n, Ordering::SeqCst);
+        Ok(())
+    })
+}
+
+/// Writes the formatted arguments to the given console, whether or not it is shown.
+///
+/// # Example
+///
+/// ```
+/// console::write_to(1, format_args!("breakpoint at {:#x}\n", address))?;
+/// ```
+pub fn write_to(n: usize, args: fmt::Arguments) -> Result<(), ConsoleError> {
+    use core::fmt::Write;
+    use x86_64::instructions::interrupts;
+
+    let console = console(n)?;
+
+    interrupts::without_interrupts(|| console.lock().write_fmt(args).unwrap());
+    Ok(())
+}
+
+/// Changes the dimensions of every console after switching text modes, see
+/// `Writer::set_dimensions`.
+pub fn set_dimensions(dimensions: VgaDimensions) {
+    use x86_64::instructions::interrupts;
+
+    interrupts::without_interrupts(|| {
+        for n in 0..CONSOLE_COUNT {
+            // every number below the count is a valid console.
+            console(n).unwrap().lock().set_dimensions(dimensions);
+        }
+    })
+}
+
+/// Makes every console write to the VGA buffer through the complete physical memory mapping, see
+/// `vga_buffer::mapped_buffer`.
+///
+/// This function is unsafe because the caller must guarantee that the complete physical memory
+/// is mapped at the passed `physical_memory_offset`.
+pub unsafe fn use_mapped_buffer(physical_memory_offset: VirtAddr) {
+    use x86_64::instructions::interrupts;
+
+    interrupts::without_interrupts(|| {
+        for n in 0..CONSOLE_COUNT {
+            let buffer = vga_buffer::mapped_buffer(physical_memory_offset);
+            // every number below the count is a valid console.
+            console(n).unwrap().lock().set_buffer(buffer);
+        }
+    })
+}
+
+/// Switches consoles on Alt+F1 to Alt+F4 and scrolls the shown console back and forward by half
+/// a screen on Shift+PageUp and Shift+PageDown, called from the keyboard path. Returns true if the
+/// event was a console hotkey, in which case it should not be passed on.
+pub fn handle_hotkey(event: &KeyEvent) -> bool {
+    use x86_64::instructions::interrupts;
+
+    if event.modifiers.shift && matches!(event.key, Key::PageUp | Key::PageDown) {
+        if event.state == KeyState::Down {
+            interrupts::without_interrupts(|| {
+                // the active console always exists.
+                let mut writer = console(active()).unwrap().lock();
+                let lines = writer.height() / 2;
+
+                match event.key {
+                    Key::PageUp => writer.scroll_up(lines),
+                    _ => writer.scroll_down(lines),
+                }
+            });
+        }
+
+        return true;
+    }
+
+    if !event.modifiers.alt {
+        return false;
+    }
+
+    let n = match event.key {
+        Key::F1 => 0,
+        Key::F2 => 1,
+        Key::F3 => 2,
+        Key::F4 => 3,
+        _ => return false,
+    };
+
+    if event.state == KeyState::Down {
+        // the number is always a valid console.
+        let _ = switch_to(n);
+    }
+
+    true
+}
+
+// Tests
+
+#[test_case]
+fn test_console_mode_from_framebuffer() {
+    assert_eq!(ConsoleMode::from_framebuffer(None), ConsoleMode::Text);
+
+    let info = FramebufferInfo {
+        address: 0xfd00_0000,
+        width: 1024,
+        height: 768,
+        stride: 1024,
+        bytes_per_pixel: 4,
+        format: PixelFormat::Bgr,
+    };
+
+    assert_eq!(
+        ConsoleMode::from_framebuffer(Some(info)),
+        ConsoleMode::Graphics { info }
+    );
+}
+
+#[test_case]
+fn test_switch_between_consoles() {
+    use crate::std::vga_buffer::screen_contains;
+
+    write_to(1, format_args!("console one")).unwrap();
+    write_to(2, format_args!("console two")).unwrap();
+
+    switch_to(1).unwrap();
+    assert_eq!(active(), 1);
+    assert!(screen_contains("console one"));
+
+    switch_to(2).unwrap();
+    assert_eq!(active(), 2);
+    assert!(screen_contains("console two"));
+
+    // writing to a console in the background leaves the screen unchanged.
+    write_to(1, format_args!(" again")).unwrap();
+    assert!(screen_contains("console two"));
+    assert!(!screen_contains("again"));
+
+    switch_to(1).unwrap();
+    assert!(screen_contains("console one again"));
+
+    switch_to(0).unwrap();
+    assert_eq!(active(), 0);
+    assert_eq!(switch_to(CONSOLE_COUNT), Err(ConsoleError::NoSuchConsole));
+}
@@ -0,0 +1,69 @@
+use x86_64::registers::control::{Cr0, Cr0Flags, Cr4, Cr4Flags};
+
+use crate::std::cpuid::{self, CpuFeature};
+
+/// Enables the FPU and SSE so that floating point and SIMD instructions can be executed, returning
+/// false if the CPU does not support SSE (never the case for x86_64, but checked regardless).
+///
+/// Long mode still requires the operating system to opt in, without this the first SSE
+/// instruction raises an invalid opcode (#UD) or device not available (#NM) exception:
+///
+/// CR0.EM cleared, the FPU is not emulated.
+/// CR0.MP set, wait instructions respect the task switched flag.
+/// CR4.OSFXSR set, fxsave/fxrstor and the SSE instructions are enabled.
+/// CR4.OSXMMEXCPT set, unmasked SIMD floating point errors raise #XM.
+pub fn enable_sse() -> bool {
+    if !cpuid::has_feature(CpuFeature::Fpu) || !cpuid::has_feature(CpuFeature::Sse) {
+        return false;
+    }
+
+    unsafe {
+        Cr0::update(|flags| {
+            flags.remove(Cr0Flags::EMULATE_COPROCESSOR);
+            flags.insert(Cr0Flags::MONITOR_COPROCESSOR);
+        });
+
+        Cr4::update(|flags| {
+            flags.insert(Cr4Flags::OSFXSR | Cr4Flags::OSXMMEXCPT_ENABLE);
+        });
+    }
+
+    true
+}
+
+/// Returns true if the FPU and SSE have been enabled by `enable_sse`.
+pub fn sse_enabled() -> bool {
+    !Cr0::read().contains(Cr0Flags::EMULATE_COPROCESSOR)
+        && Cr4::read().contains(Cr4Flags::OSFXSR | Cr4Flags::OSXMMEXCPT_ENABLE)
+}
+
+// Tests
+
+#[test_case]
+fn test_sse_instruction_after_enable_sse() {
+    assert!(enable_sse());
+    assert!(sse_enabled());
+
+    // the kernel is built with soft-float, so floating point code never uses
+    // SSE and the instruction has to be executed directly. xmm0 is saved and
+    // restored by hand, the target does not allow naming it as a clobber.
+    let mut saved = [0u8; 16];
+    let mut sum = 1.5f64.to_bits();
+    let addend = 2.25f64.to_bits();
+
+    unsafe {
+        asm!(
+            "movdqu [{saved}], xmm0",
+            "movq xmm0, {sum}",
+            "addsd xmm0, qword ptr [{addend}]",
+            "movq {sum}, xmm0",
+            "movdqu xmm0, [{saved}]",
+            saved = in(reg) saved.as_mut_ptr(),
+            sum = inout(reg) sum,
+            addend = in(reg) &addend as *const u64,
+            options(nostack, preserves_flags),
+        );
+    }
+
+    assert_eq!(f64::from_bits(sum), 3.75);
+}
@@ -0,0 +1,99 @@
+use core::arch::x86_64::__cpuid;
+
+// The leaf returning the vendor string, and the leaf returning the feature bits.
+const LEAF_VENDOR: u32 = 0;
+const LEAF_FEATURES: u32 = 1;
+
+/// A CPU feature reported by CPUID leaf 1.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CpuFeature {
+    // The local APIC is present.
+    Apic,
+    // The rdrand instruction is supported.
+    Rdrand,
+    // The SSE instructions are supported.
+    Sse,
+    // The x87 floating point unit is present.
+    Fpu,
+    // The time stamp counter and the rdtsc instruction are supported.
+    Tsc,
+    // The local APIC supports x2APIC mode.
+    X2apic,
+}
+
+// The register of CPUID leaf 1 holding a feature bit.
+enum Register {
+    Ecx,
+    Edx,
+}
+
+impl CpuFeature {
+    // The register and bit reporting the feature.
+    // https://wiki.osdev.org/CPUID#Using_CPUID
+    fn bit(self) -> (Register, u32) {
+        match self {
+            CpuFeature::Fpu => (Register::Edx, 0),
+            CpuFeature::Tsc => (Register::Edx, 4),
+            CpuFeature::Apic => (Register::Edx, 9),
+            CpuFeature::Sse => (Register::Edx, 25),
+            CpuFeature::X2apic => (Register::Ecx, 21),
+            CpuFeature::Rdrand => (Register::Ecx, 30),
+        }
+    }
+}
+
+/// Returns true if the CPU supports the given feature.
+///
+/// # Arguments
+///
+/// `feature` The feature being checked.
+///
+/// # Example
+///
+/// ```
+/// if cpuid::has_feature(CpuFeature::Rdrand) {
+///     println!("hardware random numbers are available");
+/// }
+/// ```
+pub fn has_feature(feature: CpuFeature) -> bool {
+    let features = unsafe { __cpuid(LEAF_FEATURES) };
+
+    let (register, bit) = feature.bit();
+    let value = match register {
+        Register::Ecx => features.ecx,
+        Register::Edx => features.edx,
+    };
+
+    value & (1 << bit) != 0
+}
+
+/// Returns the vendor string of the CPU, e.g. "GenuineIntel", "AuthenticAMD" or "TCGTCGTCGTCG"
+/// when running under QEMU without hardware acceleration.
+pub fn vendor_string() -> [u8; 12] {
+    let vendor = unsafe { __cpuid(LEAF_VENDOR) };
+    let mut string = [0u8; 12];
+
+    // the string is stored in ebx, edx and ecx, in that order.
+    string[0..4].copy_from_slice(&vendor.ebx.to_le_bytes());
+    string[4..8].copy_from_slice(&vendor.edx.to_le_bytes());
+    string[8..12].copy_from_slice(&vendor.ecx.to_le_bytes());
+
+    string
+}
+
+// Tests
+
+#[test_case]
+fn test_has_feature() {
+    // every x86_64 CPU, including the default QEMU one, has these.
+    assert!(has_feature(CpuFeature::Fpu));
+    assert!(has_feature(CpuFeature::Sse));
+    assert!(has_feature(CpuFeature::Tsc));
+}
+
+#[test_case]
+fn test_vendor_string() {
+    let vendor = vendor_string();
+
+    assert!(vendor.iter().all(|byte| byte.is_ascii_graphic()));
+}
@@ -0,0 +1,74 @@
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use crate::std::interrupts::are_enabled;
+use x86_64::instructions::interrupts;
+
+// The number of currently open critical sections. Only the outermost enter and
+// exit pair will actually touch the interrupt flag, all nested pairs only move
+// the counter up and down.
+static NESTING: AtomicUsize = AtomicUsize::new(0);
+
+// The state of the interrupt flag before the outermost critical section was
+// entered, this is the state that will be restored once the count reaches zero.
+static WERE_ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Enters a critical section, disabling interrupts if this is the outermost call.
+///
+/// Interrupts are disabled before the nesting count is updated, ensuring that no
+/// interrupt handler can observe a count that does not match the interrupt flag.
+/// Every call to `enter` must be paired with a call to `exit`.
+///
+/// # Example
+///
+/// ```
+/// critical_section::enter();
+/// // ... work that must not be interrupted ...
+/// critical_section::exit();
+/// ```
+pub fn enter() {
+    let enabled = are_enabled();
+    interrupts::disable();
+
+    if NESTING.fetch_add(1, Ordering::SeqCst) == 0 {
+        WERE_ENABLED.store(enabled, Ordering::SeqCst);
+    }
+}
+
+/// Exits a critical section, restoring the interrupt flag to the state it was in
+/// before the outermost `enter` once the nesting count returns to zero. Inner
+/// exits never re-enable interrupts.
+pub fn exit() {
+    let previous = NESTING.fetch_sub(1, Ordering::SeqCst);
+    assert!(previous > 0, "critical_section::exit called without a matching enter");
+
+    if previous == 1 && WERE_ENABLED.load(Ordering::SeqCst) {
+        interrupts::enable();
+    }
+}
+
+/// Returns the number of critical sections that are currently open.
+pub fn depth() -> usize {
+    NESTING.load(Ordering::SeqCst)
+}
+
+// Tests
+
+#[test_case]
+fn test_nested_critical_sections_restore_on_outermost_exit() {
+    assert!(are_enabled());
+
+    enter();
+    enter();
+    enter();
+    assert_eq!(depth(), 3);
+    assert!(!are_enabled());
+
+    exit();
+    assert!(!are_enabled());
+
+    exit();
+    assert!(!are_enabled());
+
+    exit();
+    assert_eq!(depth(), 0);
+    assert!(are_enabled());
+}
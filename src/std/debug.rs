@@ -0,0 +1,65 @@
+use core::fmt;
+use x86_64::registers::control::{Cr0, Cr2, Cr3, Cr4};
+use x86_64::registers::rflags;
+
+use crate::println;
+
+/// Writes the instruction pointer, stack pointer, flags and control registers of the caller.
+///
+/// # Arguments
+///
+/// `out` The output the registers are written to.
+///
+/// # Example
+///
+/// ```
+/// RIP: 0x20345a RSP: 0x10000201a70
+/// RFLAGS: IOPL_LOW | INTERRUPT_FLAG | SIGN_FLAG | 0x2
+/// CR0: PROTECTED_MODE_ENABLE | MONITOR_COPROCESSOR | EXTENSION_TYPE | WRITE_PROTECT | PAGING
+/// CR2: 0x0
+/// CR3: 0x1000 (flags: (empty))
+/// CR4: PHYSICAL_ADDRESS_EXTENSION | OSFXSR | OSXMMEXCPT_ENABLE
+/// ```
+pub fn write_registers(out: &mut impl fmt::Write) -> fmt::Result {
+    let rip = x86_64::instructions::read_rip();
+    let rsp: u64;
+    unsafe { asm!("mov {}, rsp", out(reg) rsp, options(nomem, nostack, preserves_flags)) };
+
+    let (level_4_table, cr3_flags) = Cr3::read();
+
+    writeln!(out, "RIP: {:#x} RSP: {:#x}", rip.as_u64(), rsp)?;
+    writeln!(out, "RFLAGS: {:?}", rflags::read())?;
+    writeln!(out, "CR0: {:?}", Cr0::read())?;
+    // the address of the last page fault.
+    writeln!(out, "CR2: {:#x}", Cr2::read().as_u64())?;
+    writeln!(
+        out,
+        "CR3: {:#x} (flags: {:?})",
+        level_4_table.start_address().as_u64(),
+        cr3_flags
+    )?;
+    writeln!(out, "CR4: {:?}", Cr4::read())
+}
+
+/// Prints the instruction pointer, stack pointer, flags and control registers to the screen,
+/// used by the panic handler to help debugging faults. See `write_registers`.
+pub fn dump_registers() {
+    struct Screen;
+
+    impl fmt::Write for Screen {
+        fn write_str(&mut self, s: &str) -> fmt::Result {
+            crate::print!("{}", s);
+            Ok(())
+        }
+    }
+
+    println!("REGISTERS:");
+    let _ = write_registers(&mut Screen);
+}
+
+// Tests
+
+#[test_case]
+fn test_dump_registers() {
+    dump_registers();
+}
@@ -0,0 +1,646 @@
+use core::fmt;
+
+use crate::std::console::{self, Console, ConsoleMode, FramebufferInfo};
+use crate::std::panic::lock_for_print;
+use crate::std::sync::TryMutex;
+
+/// The width and height of a character drawn by `FrameBuffer::draw_char`, in pixels.
+pub const CHAR_SIZE: usize = 8;
+
+/// The height of a character drawn by `FrameBuffer::draw_char_8x16` and `FramebufferWriter`, in
+/// pixels. The characters are `CHAR_SIZE` pixels wide.
+pub const CHAR_HEIGHT: usize = 16;
+
+// The first and last character of the font, the printable ASCII characters.
+const FONT_FIRST: u8 = 0x20;
+const FONT_LAST: u8 = 0x7E;
+const FONT_LEN: usize = (FONT_LAST - FONT_FIRST + 1) as usize;
+
+// The character drawn in place of characters missing from the font.
+const FONT_REPLACEMENT: u8 = b'?';
+
+// An 8x8 font of the printable ASCII characters, one byte per row from the
+// top, with the least significant bit being the leftmost pixel.
+// https://github.com/dhepper/font8x8 (public domain)
+const FONT: [[u8; CHAR_SIZE]; FONT_LEN] = [
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00], // ' '
+    [0x18, 0x3C, 0x3C, 0x18, 0x18, 0x00, 0x18, 0x00], // '!'
+    [0x36, 0x36, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00], // '"'
+    [0x36, 0x36, 0x7F, 0x36, 0x7F, 0x36, 0x36, 0x00], // '#'
+    [0x0C, 0x3E, 0x03, 0x1E, 0x30, 0x1F, 0x0C, 0x00], // '$'
+    [0x00, 0x63, 0x33, 0x18, 0x0C, 0x66, 0x63, 0x00], // '%'
+    [0x1C, 0x36, 0x1C, 0x6E, 0x3B, 0x33, 0x6E, 0x00], // '&'
+    [0x06, 0x06, 0x03, 0x00, 0x00, 0x00, 0x00, 0x00], // '\''
+    [0x18, 0x0C, 0x06, 0x06, 0x06, 0x0C, 0x18, 0x00], // '('
+    [0x06, 0x0C, 0x18, 0x18, 0x18, 0x0C, 0x06, 0x00], // ')'
+    [0x00, 0x66, 0x3C, 0xFF, 0x3C, 0x66, 0x00, 0x00], // '*'
+    [0x00, 0x0C, 0x0C, 0x3F, 0x0C, 0x0C, 0x00, 0x00], // '+'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x0C, 0x0C, 0x06], // ','
+    [0x00, 0x00, 0x00, 0x3F, 0x00, 0x00, 0x00, 0x00], // '-'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x0C, 0x0C, 0x00], // '.'
+    [0x60, 0x30, 0x18, 0x0C, 0x06, 0x03, 0x01, 0x00], // '/'
+    [0x3E, 0x63, 0x73, 0x7B, 0x6F, 0x67, 0x3E, 0x00], // '0'
+    [0x0C, 0x0E, 0x0C, 0x0C, 0x0C, 0x0C, 0x3F, 0x00], // '1'
+    [0x1E, 0x33, 0x30, 0x1C, 0x06, 0x33, 0x3F, 0x00], // '2'
+    [0x1E, 0x33, 0x30, 0x1C, 0x30, 0x33, 0x1E, 0x00], // '3'
+    [0x38, 0x3C, 0x36, 0x33, 0x7F, 0x30, 0x78, 0x00], // '4'
+    [0x3F, 0x03, 0x1F, 0x30, 0x30, 0x33, 0x1E, 0x00], // '5'
+    [0x1C, 0x06, 0x03, 0x1F, 0x33, 0x33, 0x1E, 0x00], // '6'
+    [0x3F, 0x33, 0x30, 0x18, 0x0C, 0x0C, 0x0C, 0x00], // '7'
+    [0x1E, 0x33, 0x33, 0x1E, 0x33, 0x33, 0x1E, 0x00], // '8'
+    [0x1E, 0x33, 0x33, 0x3E, 0x30, 0x18, 0x0E, 0x00], // '9'
+    [0x00, 0x0C, 0x0C, 0x00, 0x00, 0x0C, 0x0C, 0x00], // ':'
+    [0x00, 0x0C, 0x0C, 0x00, 0x00, 0x0C, 0x0C, 0x06], // ';'
+    [0x18, 0x0C, 0x06, 0x03, 0x06, 0x0C, 0x18, 0x00], // '<'
+    [0x00, 0x00, 0x3F, 0x00, 0x00, 0x3F, 0x00, 0x00], // '='
+    [0x06, 0x0C, 0x18, 0x30, 0x18, 0x0C, 0x06, 0x00], // '>'
+    [0x1E, 0x33, 0x30, 0x18, 0x0C, 0x00, 0x0C, 0x00], // '?'
+    [0x3E, 0x63, 0x7B, 0x7B, 0x7B, 0x03, 0x1E, 0x00], // '@'
+    [0x0C, 0x1E, 0x33, 0x33, 0x3F, 0x33, 0x33, 0x00], // 'A'
+    [0x3F, 0x66, 0x66, 0x3E, 0x66, 0x66, 0x3F, 0x00], // 'B'
+    [0x3C, 0x66, 0x03, 0x03, 0x03, 0x66, 0x3C, 0x00], // 'C'
+    [0x1F, 0x36, 0x66, 0x66, 0x66, 0x36, 0x1F, 0x00], // 'D'
+    [0x7F, 0x46, 0x16, 0x1E, 0x16, 0x46, 0x7F, 0x00], // 'E'
+    [0x7F, 0x46, 0x16, 0x1E, 0x16, 0x06, 0x0F, 0x00], // 'F'
+    [0x3C, 0x66, 0x03, 0x03, 0x73, 0x66, 0x7C, 0x00], // 'G'
+    [0x33, 0x33, 0x33, 0x3F, 0x33, 0x33, 0x33, 0x00], // 'H'
+    [0x1E, 0x0C, 0x0C, 0x0C, 0x0C, 0x0C, 0x1E, 0x00], // 'I'
+    [0x78, 0x30, 0x30, 0x30, 0x33, 0x33, 0x1E, 0x00], // 'J'
+    [0x67, 0x66, 0x36, 0x1E, 0x36, 0x66, 0x67, 0x00], // 'K'
+    [0x0F, 0x06, 0x06, 0x06, 0x46, 0x66, 0x7F, 0x00], // 'L'
+    [0x63, 0x77, 0x7F, 0x7F, 0x6B, 0x63, 0x63, 0x00], // 'M'
+    [0x63, 0x67, 0x6F, 0x7B, 0x73, 0x63, 0x63, 0x00], // 'N'
+    [0x1C, 0x36, 0x63, 0x63, 0x63, 0x36, 0x1C, 0x00], // 'O'
+    [0x3F, 0x66, 0x66, 0x3E, 0x06, 0x06, 0x0F, 0x00], // 'P'
+    [0x1E, 0x33, 0x33, 0x33, 0x3B, 0x1E, 0x38, 0x00], // 'Q'
+    [0x3F, 0x66, 0x66, 0x3E, 0x36, 0x66, 0x67, 0x00], // 'R'
+    [0x1E, 0x33, 0x07, 0x0E, 0x38, 0x33, 0x1E, 0x00], // 'S'
+    [0x3F, 0x2D, 0x0C, 0x0C, 0x0C, 0x0C, 0x1E, 0x00], // 'T'
+    [0x33, 0x33, 0x33, 0x33, 0x33, 0x33, 0x3F, 0x00], // 'U'
+    [0x33, 0x33, 0x33, 0x33, 0x33, 0x1E, 0x0C, 0x00], // 'V'
+    [0x63, 0x63, 0x63, 0x6B, 0x7F, 0x77, 0x63, 0x00], // 'W'
+    [0x63, 0x63, 0x36, 0x1C, 0x1C, 0x36, 0x63, 0x00], // 'X'
+    [0x33, 0x33, 0x33, 0x1E, 0x0C, 0x0C, 0x1E, 0x00], // 'Y'
+    [0x7F, 0x63, 0x31, 0x18, 0x4C, 0x66, 0x7F, 0x00], // 'Z'
+    [0x1E, 0x06, 0x06, 0x06, 0x06, 0x06, 0x1E, 0x00], // '['
+    [0x03, 0x06, 0x0C, 0x18, 0x30, 0x60, 0x40, 0x00], // '\\'
+    [0x1E, 0x18, 0x18, 0x18, 0x18, 0x18, 0x1E, 0x00], // ']'
+    [0x08, 0x1C, 0x36, 0x63, 0x00, 0x00, 0x00, 0x00], // '^'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xFF], // '_'
+    [0x0C, 0x0C, 0x18, 0x00, 0x00, 0x00, 0x00, 0x00], // '`'
+    [0x00, 0x00, 0x1E, 0x30, 0x3E, 0x33, 0x6E, 0x00], // 'a'
+    [0x07, 0x06, 0x06, 0x3E, 0x66, 0x66, 0x3B, 0x00], // 'b'
+    [0x00, 0x00, 0x1E, 0x33, 0x03, 0x33, 0x1E, 0x00], // 'c'
+    [0x38, 0x30, 0x30, 0x3E, 0x33, 0x33, 0x6E, 0x00], // 'd'
+    [0x00, 0x00, 0x1E, 0x33, 0x3F, 0x03, 0x1E, 0x00], // 'e'
+    [0x1C, 0x36, 0x06, 0x0F, 0x06, 0x06, 0x0F, 0x00], // 'f'
+    [0x00, 0x00, 0x6E, 0x33, 0x33, 0x3E, 0x30, 0x1F], // 'g'
+    [0x07, 0x06, 0x36, 0x6E, 0x66, 0x66, 0x67, 0x00], // 'h'
+    [0x0C, 0x00, 0x0E, 0x0C, 0x0C, 0x0C, 0x1E, 0x00], // 'i'
+    [0x30, 0x00, 0x30, 0x30, 0x30, 0x33, 0x33, 0x1E], // 'j'
+    [0x07, 0x06, 0x66, 0x36, 0x1E, 0x36, 0x67, 0x00], // 'k'
+    [0x0E, 0x0C, 0x0C, 0x0C, 0x0C, 0x0C, 0x1E, 0x00], // 'l'
+    [0x00, 0x00, 0x33, 0x7F, 0x7F, 0x6B, 0x63, 0x00], // 'm'
+    [0x00, 0x00, 0x1F, 0x33, 0x33, 0x33, 0x33, 0x00], // 'n'
+    [0x00, 0x00, 0x1E, 0x33, 0x33, 0x33, 0x1E, 0x00], // 'o'
+    [0x00, 0x00, 0x3B, 0x66, 0x66, 0x3E, 0x06, 0x0F], // 'p'
+    [0x00, 0x00, 0x6E, 0x33, 0x33, 0x3E, 0x30, 0x78], // 'q'
+    [0x00, 0x00, 0x3B, 0x6E, 0x66, 0x06, 0x0F, 0x00], // 'r'
+    [0x00, 0x00, 0x3E, 0x03, 0x1E, 0x30, 0x1F, 0x00], // 's'
+    [0x08, 0x0C, 0x3E, 0x0C, 0x0C, 0x2C, 0x18, 0x00], // 't'
+    [0x00, 0x00, 0x33, 0x33, 0x33, 0x33, 0x6E, 0x00], // 'u'
+    [0x00, 0x00, 0x33, 0x33, 0x33, 0x1E, 0x0C, 0x00], // 'v'
+    [0x00, 0x00, 0x63, 0x6B, 0x7F, 0x7F, 0x36, 0x00], // 'w'
+    [0x00, 0x00, 0x63, 0x36, 0x1C, 0x36, 0x63, 0x00], // 'x'
+    [0x00, 0x00, 0x33, 0x33, 0x33, 0x3E, 0x30, 0x1F], // 'y'
+    [0x00, 0x00, 0x3F, 0x19, 0x0C, 0x26, 0x3F, 0x00], // 'z'
+    [0x38, 0x0C, 0x0C, 0x07, 0x0C, 0x0C, 0x38, 0x00], // '{'
+    [0x18, 0x18, 0x18, 0x00, 0x18, 0x18, 0x18, 0x00], // '|'
+    [0x07, 0x0C, 0x0C, 0x38, 0x0C, 0x0C, 0x07, 0x00], // '}'
+    [0x6E, 0x3B, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00], // '~'
+];
+
+// The 8x16 font of the same characters, each row of the 8x8 font drawn twice.
+const FONT_8X16: [[u8; CHAR_HEIGHT]; FONT_LEN] = double_rows();
+
+const fn double_rows() -> [[u8; CHAR_HEIGHT]; FONT_LEN] {
+    let mut font = [[0; CHAR_HEIGHT]; FONT_LEN];
+
+    let mut character = 0;
+    while character < FONT_LEN {
+        let mut row = 0;
+        while row < CHAR_HEIGHT {
+            font[character][row] = FONT[character][row / 2];
+            row += 1;
+        }
+
+        character += 1;
+    }
+
+    font
+}
+
+/// The order of the color components of a pixel in memory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PixelFormat {
+    /// Red first, followed by green and blue.
+    Rgb,
+    /// Blue first, followed by green and red.
+    Bgr,
+}
+
+/// A color of a pixel of the framebuffer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rgb {
+    pub red: u8,
+    pub green: u8,
+    pub blue: u8,
+}
+
+impl Rgb {
+    pub const BLACK: Rgb = Rgb::new(0, 0, 0);
+    pub const WHITE: Rgb = Rgb::new(0xFF, 0xFF, 0xFF);
+
+    pub const fn new(red: u8, green: u8, blue: u8) -> Rgb {
+        Rgb { red, green, blue }
+    }
+}
+
+/// A linear framebuffer that pixels and characters can be drawn to, laid out as described by
+/// the same `FramebufferInfo` that selects `ConsoleMode::Graphics`.
+pub struct FrameBuffer {
+    info: FramebufferInfo,
+    buffer: &'static mut [u8],
+}
+
+impl FrameBuffer {
+    /// Returns a framebuffer drawing to the memory described by the info.
+    ///
+    /// This function is unsafe since the caller must guarantee that the memory at the address of
+    /// the info is mapped, `info.size()` bytes long and not used by anything else.
+    pub unsafe fn new(info: FramebufferInfo) -> FrameBuffer {
+        assert!(
+            info.bytes_per_pixel >= 3,
+            "framebuffer pixels must have 3 components"
+        );
+        assert!(info.stride >= info.width);
+
+        let buffer = core::slice::from_raw_parts_mut(info.address as *mut u8, info.size());
+        FrameBuffer { info, buffer }
+    }
+
+    pub fn info(&self) -> &FramebufferInfo {
+        &self.info
+    }
+
+    /// Sets the pixel at the given position to the color, does nothing outside of the screen.
+    ///
+    /// # Arguments
+    ///
+    /// `x` The column of the pixel, from the left.
+    /// `y` The row of the pixel, from the top.
+    /// `color` The color of the pixel.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// framebuffer.put_pixel(10, 20, Rgb::new(0xFF, 0x00, 0x00));
+    /// ```
+    pub fn put_pixel(&mut self, x: usize, y: usize, color: Rgb) {
+        let offset = match self.offset(x, y) {
+            Some(offset) => offset,
+            None => return,
+        };
+
+        let bytes = match self.info.format {
+            PixelFormat::Rgb => [color.red, color.green, color.blue],
+            PixelFormat::Bgr => [color.blue, color.green, color.red],
+        };
+
+        for (i, &byte) in bytes.iter().enumerate() {
+            unsafe { core::ptr::write_volatile(&mut self.buffer[offset + i], byte) };
+        }
+    }
+
+    /// Returns the color of the pixel at the given position, or `None` outside of the screen.
+    pub fn get_pixel(&self, x: usize, y: usize) -> Option<Rgb> {
+        let offset = self.offset(x, y)?;
+
+        let mut bytes = [0; 3];
+        for (i, byte) in bytes.iter_mut().enumerate() {
+            *byte = unsafe { core::ptr::read_volatile(&self.buffer[offset + i]) };
+        }
+
+        Some(match self.info.format {
+            PixelFormat::Rgb => Rgb::new(bytes[0], bytes[1], bytes[2]),
+            PixelFormat::Bgr => Rgb::new(bytes[2], bytes[1], bytes[0]),
+        })
+    }
+
+    /// Fills the whole framebuffer with the color.
+    pub fn clear(&mut self, color: Rgb) {
+        for y in 0..self.info.height {
+            for x in 0..self.info.width {
+                self.put_pixel(x, y, color);
+            }
+        }
+    }
+
+    /// Draws an 8x8 character with its top left corner at the given position. Characters
+    /// outside of printable ASCII are drawn as `?`, pixels outside of the screen are clipped.
+    ///
+    /// # Arguments
+    ///
+    /// `x` The column of the top left pixel, from the left.
+    /// `y` The row of the top left pixel, from the top.
+    /// `character` The character to draw.
+    /// `foreground` The color of the character.
+    /// `background` The color of the rest of the 8x8 cell.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// for (i, character) in "Hello".chars().enumerate() {
+    ///     framebuffer.draw_char(i * CHAR_SIZE, 0, character, Rgb::WHITE, Rgb::BLACK);
+    /// }
+    /// ```
+    pub fn draw_char(
+        &mut self,
+        x: usize,
+        y: usize,
+        character: char,
+        foreground: Rgb,
+        background: Rgb,
+    ) {
+        self.draw_glyph(x, y, glyph(character), foreground, background);
+    }
+
+    /// Draws an 8x16 character with its top left corner at the given position, see `draw_char`.
+    pub fn draw_char_8x16(
+        &mut self,
+        x: usize,
+        y: usize,
+        character: char,
+        foreground: Rgb,
+        background: Rgb,
+    ) {
+        self.draw_glyph(x, y, glyph_8x16(character), foreground, background);
+    }
+
+    // Draws the rows of a glyph, the least significant bit being the leftmost
+    // pixel of a row.
+    fn draw_glyph(&mut self, x: usize, y: usize, rows: &[u8], foreground: Rgb, background: Rgb) {
+        for (row, bits) in rows.iter().enumerate() {
+            for column in 0..CHAR_SIZE {
+                let color = if bits & (1 << column) != 0 {
+                    foreground
+                } else {
+                    background
+                };
+                self.put_pixel(x + column, y + row, color);
+            }
+        }
+    }
+
+    /// Moves the contents of the framebuffer up by the number of pixel rows, filling the rows
+    /// uncovered at the bottom with the background color.
+    ///
+    /// # Arguments
+    ///
+    /// `rows` The number of pixel rows to scroll by.
+    /// `background` The color of the uncovered rows.
+    pub fn scroll_up(&mut self, rows: usize, background: Rgb) {
+        let rows = rows.min(self.info.height);
+        let pitch = self.info.pitch();
+
+        self.buffer.copy_within(rows * pitch.., 0);
+
+        for y in self.info.height - rows..self.info.height {
+            for x in 0..self.info.width {
+                self.put_pixel(x, y, background);
+            }
+        }
+    }
+
+    // The byte offset of the pixel, or None outside of the screen.
+    fn offset(&self, x: usize, y: usize) -> Option<usize> {
+        if x >= self.info.width || y >= self.info.height {
+            return None;
+        }
+
+        Some(y * self.info.pitch() + x * self.info.bytes_per_pixel)
+    }
+}
+
+/// Writes text to a `FrameBuffer`, mirroring the `Writer` of the VGA text buffer. Characters
+/// are drawn in 8x16 cells from the top left, wrapping at the right edge and scrolling the
+/// framebuffer up once the bottom is reached.
+pub struct FramebufferWriter {
+    framebuffer: FrameBuffer,
+    // The position of the top left pixel of the next character.
+    x: usize,
+    y: usize,
+    foreground: Rgb,
+    background: Rgb,
+}
+
+impl FramebufferWriter {
+    /// Returns a writer drawing white on black text, starting at the top left of the cleared
+    /// framebuffer.
+    pub fn new(mut framebuffer: FrameBuffer) -> FramebufferWriter {
+        framebuffer.clear(Rgb::BLACK);
+
+        FramebufferWriter {
+            framebuffer,
+            x: 0,
+            y: 0,
+            foreground: Rgb::WHITE,
+            background: Rgb::BLACK,
+        }
+    }
+
+    /// Sets the colors of the text written from now on.
+    pub fn set_color(&mut self, foreground: Rgb, background: Rgb) {
+        self.foreground = foreground;
+        self.background = background;
+    }
+
+    /// Returns the number of characters that fit on a line.
+    pub fn width(&self) -> usize {
+        self.framebuffer.info().width / CHAR_SIZE
+    }
+
+    /// Returns the number of lines that fit on the screen.
+    pub fn height(&self) -> usize {
+        self.framebuffer.info().height / CHAR_HEIGHT
+    }
+
+    /// Returns the position of the top left pixel of the next character.
+    pub fn cursor(&self) -> (usize, usize) {
+        (self.x, self.y)
+    }
+
+    pub fn framebuffer(&self) -> &FrameBuffer {
+        &self.framebuffer
+    }
+
+    /// Writes the string to the framebuffer, characters outside of printable ASCII are drawn as
+    /// `?`.
+    ///
+    /// # Arguments
+    ///
+    /// `input_string` The string that will be written.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// writer.write_string("Hello, World\n");
+    /// ```
+    pub fn write_string(&mut self, input_string: &str) {
+        for character in input_string.chars() {
+            self.write_char(character);
+        }
+    }
+
+    /// Writes a single character, a new line moves to the start of the next line.
+    pub fn write_char(&mut self, character: char) {
+        match character {
+            '\n' => self.new_line(),
+            '\r' => self.x = 0,
+            character => {
+                if self.x + CHAR_SIZE > self.framebuffer.info().width {
+                    self.new_line();
+                }
+
+                self.framebuffer.draw_char_8x16(
+                    self.x,
+                    self.y,
+                    character,
+                    self.foreground,
+                    self.background,
+                );
+                self.x += CHAR_SIZE;
+            }
+        }
+    }
+
+    /// Replaces every pixel with the background color and moves the cursor to the top left.
+    pub fn clear_screen(&mut self) {
+        self.framebuffer.clear(self.background);
+        self.x = 0;
+        self.y = 0;
+    }
+
+    /// Moves the cursor to the start of the next line, scrolling the framebuffer up by a line
+    /// when the next line would not fit on the screen.
+    pub fn new_line(&mut self) {
+        self.x = 0;
+        self.y += CHAR_HEIGHT;
+
+        let height = self.framebuffer.info().height;
+        if self.y + CHAR_HEIGHT > height {
+            let overflow = self.y + CHAR_HEIGHT - height;
+            self.framebuffer.scroll_up(overflow, self.background);
+            self.y -= overflow;
+        }
+    }
+}
+
+impl fmt::Write for FramebufferWriter {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.write_string(s);
+        Ok(())
+    }
+}
+
+impl Console for FramebufferWriter {
+    fn dimensions(&self) -> (usize, usize) {
+        (self.width(), self.height())
+    }
+}
+
+/// Returns the rows of the character in the 8x8 font.
+pub(crate) fn glyph(character: char) -> &'static [u8; CHAR_SIZE] {
+    &FONT[font_index(character)]
+}
+
+/// Returns the rows of the character in the 8x16 font.
+fn glyph_8x16(character: char) -> &'static [u8; CHAR_HEIGHT] {
+    &FONT_8X16[font_index(character)]
+}
+
+// The index of the character in the fonts, characters missing from the fonts
+// are replaced.
+fn font_index(character: char) -> usize {
+    let code = character as u32;
+    let byte = if (FONT_FIRST as u32..=FONT_LAST as u32).contains(&code) {
+        code as u8
+    } else {
+        FONT_REPLACEMENT
+    };
+
+    (byte - FONT_FIRST) as usize
+}
+
+/// The writer of the framebuffer handed over at boot, `None` when booted into the VGA text mode.
+pub static FRAMEBUFFER_WRITER: TryMutex<Option<FramebufferWriter>> =
+    TryMutex::new("FRAMEBUFFER_WRITER", None);
+
+/// Sets the framebuffer handed over at boot and selects it as the console `println!` writes to,
+/// see `console::select`. The bootloader currently in use always boots into the VGA text mode, so
+/// this has to be called with the info of a framebuffer set up by other means.
+///
+/// This function is unsafe for the same reasons as `FrameBuffer::new`.
+pub unsafe fn init(info: FramebufferInfo) {
+    use x86_64::instructions::interrupts;
+
+    let writer = FramebufferWriter::new(FrameBuffer::new(info));
+    interrupts::without_interrupts(|| *lock_for_print(&FRAMEBUFFER_WRITER) = Some(writer));
+    console::select(ConsoleMode::Graphics { info });
+}
+
+// Tests
+
+#[cfg(test)]
+const TEST_WIDTH: usize = 16;
+#[cfg(test)]
+const TEST_HEIGHT: usize = CHAR_HEIGHT;
+#[cfg(test)]
+const TEST_STRIDE: usize = TEST_WIDTH + 2;
+#[cfg(test)]
+const TEST_PITCH: usize = TEST_STRIDE * 4;
+
+// A framebuffer in memory with padding at the end of each row, so that tests
+// don't depend on the bootloader handing one over.
+#[cfg(test)]
+static mut TEST_BUFFER: [u8; TEST_PITCH * TEST_HEIGHT] = [0; TEST_PITCH * TEST_HEIGHT];
+
+#[cfg(test)]
+fn test_framebuffer(format: PixelFormat) -> FrameBuffer {
+    let info = FramebufferInfo {
+        address: unsafe { TEST_BUFFER.as_ptr() } as u64,
+        width: TEST_WIDTH,
+        height: TEST_HEIGHT,
+        stride: TEST_STRIDE,
+        bytes_per_pixel: 4,
+        format,
+    };
+
+    unsafe { FrameBuffer::new(info) }
+}
+
+#[test_case]
+fn test_put_pixel_reads_back() {
+    let orange = Rgb::new(0xFF, 0x80, 0x00);
+
+    let mut framebuffer = test_framebuffer(PixelFormat::Rgb);
+    framebuffer.clear(Rgb::BLACK);
+    framebuffer.put_pixel(3, 2, orange);
+    framebuffer.put_pixel(TEST_WIDTH, 0, orange);
+
+    assert_eq!(framebuffer.get_pixel(3, 2), Some(orange));
+    assert_eq!(framebuffer.get_pixel(4, 2), Some(Rgb::BLACK));
+    assert_eq!(framebuffer.get_pixel(TEST_WIDTH, 0), None);
+
+    let offset = 2 * TEST_PITCH + 3 * 4;
+    assert_eq!(
+        unsafe { &TEST_BUFFER[offset..offset + 3] },
+        &[0xFF, 0x80, 0x00]
+    );
+
+    let mut framebuffer = test_framebuffer(PixelFormat::Bgr);
+    framebuffer.put_pixel(3, 2, orange);
+
+    assert_eq!(framebuffer.get_pixel(3, 2), Some(orange));
+    assert_eq!(
+        unsafe { &TEST_BUFFER[offset..offset + 3] },
+        &[0x00, 0x80, 0xFF]
+    );
+}
+
+#[test_case]
+fn test_draw_char() {
+    let mut framebuffer = test_framebuffer(PixelFormat::Rgb);
+    framebuffer.draw_char(0, 0, '|', Rgb::WHITE, Rgb::BLACK);
+
+    // the bar of '|' covers columns 3 and 4, with a gap in row 3.
+    assert_eq!(framebuffer.get_pixel(3, 0), Some(Rgb::WHITE));
+    assert_eq!(framebuffer.get_pixel(4, 0), Some(Rgb::WHITE));
+    assert_eq!(framebuffer.get_pixel(2, 0), Some(Rgb::BLACK));
+    assert_eq!(framebuffer.get_pixel(3, 3), Some(Rgb::BLACK));
+
+    // characters outside of the font are drawn as '?'.
+    framebuffer.draw_char(CHAR_SIZE, 0, 'é', Rgb::WHITE, Rgb::BLACK);
+    framebuffer.draw_char(0, 0, '?', Rgb::WHITE, Rgb::BLACK);
+    for y in 0..CHAR_SIZE {
+        for x in 0..CHAR_SIZE {
+            assert_eq!(
+                framebuffer.get_pixel(x, y),
+                framebuffer.get_pixel(x + CHAR_SIZE, y)
+            );
+        }
+    }
+}
+
+#[test_case]
+fn test_font_8x16_doubles_rows() {
+    for character in ['A', '|', 'é'].iter().copied() {
+        for row in 0..CHAR_HEIGHT {
+            assert_eq!(glyph_8x16(character)[row], glyph(character)[row / 2]);
+        }
+    }
+}
+
+#[test_case]
+fn test_framebuffer_writer() {
+    use core::fmt::Write;
+
+    let mut writer = FramebufferWriter::new(test_framebuffer(PixelFormat::Rgb));
+    write!(writer, "Hi").unwrap();
+
+    assert_eq!(writer.cursor(), (2 * CHAR_SIZE, 0));
+
+    // both glyph cells have some pixels that are not the background.
+    for cell in 0..2 {
+        let drawn = (0..CHAR_SIZE * CHAR_HEIGHT).any(|i| {
+            let (x, y) = (cell * CHAR_SIZE + i % CHAR_SIZE, i / CHAR_SIZE);
+            writer.framebuffer().get_pixel(x, y) != Some(Rgb::BLACK)
+        });
+        assert!(drawn);
+    }
+
+    // the test framebuffer is a single line high, the next line scrolls "Hi"
+    // off the screen.
+    writeln!(writer).unwrap();
+    assert_eq!(writer.cursor(), (0, 0));
+    for x in 0..TEST_WIDTH {
+        for y in 0..TEST_HEIGHT {
+            assert_eq!(writer.framebuffer().get_pixel(x, y), Some(Rgb::BLACK));
+        }
+    }
+}
+
+#[test_case]
+fn test_print_through_selected_console() {
+    use x86_64::instructions::interrupts;
+
+    let info = *test_framebuffer(PixelFormat::Rgb).info();
+
+    // a timer dot printed while the framebuffer is selected would move the
+    // cursor, so nothing else prints until the text console is back.
+    interrupts::without_interrupts(|| {
+        unsafe { init(info) };
+        crate::print!("Hi");
+
+        let cursor = FRAMEBUFFER_WRITER
+            .lock()
+            .as_ref()
+            .map(|writer| writer.cursor());
+
+        // the kernel console is used again by the following tests.
+        console::select(ConsoleMode::Text);
+        *FRAMEBUFFER_WRITER.lock() = None;
+
+        assert_eq!(cursor, Some((2 * CHAR_SIZE, 0)));
+    });
+}
@@ -1,13 +1,20 @@
 use x86_64::VirtAddr;
 use x86_64::structures::gdt::{GlobalDescriptorTable, Descriptor, SegmentSelector};
 use x86_64::structures::tss::TaskStateSegment;
-use lazy_static::lazy_static;
+
+use crate::std::once::Once;
 
 pub const DOUBLE_FAULT_IST_INDEX: u16 = 0;
 
+// The stack the CPU switches to when an interrupt or system call moves from
+// user mode (ring 3) into the kernel (ring 0).
+const KERNEL_PRIVILEGE_STACK_INDEX: usize = 0;
+
+static TSS: Once<TaskStateSegment> = Once::new();
+static GDT: Once<(GlobalDescriptorTable, Selectors)> = Once::new();
 
-lazy_static! {
-    static ref TSS: TaskStateSegment = {
+fn tss() -> &'static TaskStateSegment {
+    TSS.call_once(|| {
         let mut tss = TaskStateSegment::new();
 
         tss.interrupt_stack_table[DOUBLE_FAULT_IST_INDEX as usize] = {
@@ -19,33 +26,80 @@ lazy_static! {
             stack_end
         };
 
-    tss
-    };
+        tss.privilege_stack_table[KERNEL_PRIVILEGE_STACK_INDEX] = {
+            const STACK_SIZE: usize = 4096 * 5;
+            static mut STACK: [u8; STACK_SIZE] = [0; STACK_SIZE];
+
+            let stack_start = VirtAddr::from_ptr(unsafe { &STACK });
+            stack_start + STACK_SIZE
+        };
+
+        tss
+    })
 }
 
-lazy_static! {
-    static ref GDT: (GlobalDescriptorTable, Selectors) = {
+fn gdt() -> &'static (GlobalDescriptorTable, Selectors) {
+    GDT.call_once(|| {
         let mut gdt = GlobalDescriptorTable::new();
-        let code_selector = gdt.add_entry(Descriptor::kernel_code_segment());
-        let tss_selector = gdt.add_entry(Descriptor::tss_segment(&TSS));
-        (gdt, Selectors { code_selector, tss_selector})
-    };
+
+        // the user data segment comes before the user code segment, the order
+        // sysret expects them in.
+        let kernel_code = gdt.add_entry(Descriptor::kernel_code_segment());
+        let kernel_data = gdt.add_entry(Descriptor::kernel_data_segment());
+        let user_data = gdt.add_entry(Descriptor::user_data_segment());
+        let user_code = gdt.add_entry(Descriptor::user_code_segment());
+        let tss = gdt.add_entry(Descriptor::tss_segment(tss()));
+
+        (gdt, Selectors { kernel_code, kernel_data, user_code, user_data, tss })
+    })
+}
+
+/// The selectors of the segments in the GDT. The user segments have a requested privilege level
+/// of 3, so they can be loaded directly when switching to user mode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Selectors {
+    pub kernel_code: SegmentSelector,
+    pub kernel_data: SegmentSelector,
+    pub user_code: SegmentSelector,
+    pub user_data: SegmentSelector,
+    pub tss: SegmentSelector,
 }
 
-struct Selectors {
-    code_selector: SegmentSelector,
-    tss_selector: SegmentSelector,
+/// Returns the selectors of the segments in the GDT loaded by `init`.
+pub fn selectors() -> &'static Selectors {
+    &gdt().1
 }
 
 #[allow(dead_code)]
 pub fn init() {
-    use x86_64::instructions::segmentation::set_cs;
+    use x86_64::instructions::segmentation::{load_ds, load_ss, set_cs};
     use x86_64::instructions::tables::load_tss;
 
-    GDT.0.load();
+    let (gdt, selectors) = gdt();
+    gdt.load();
 
     unsafe {
-        set_cs(GDT.1.code_selector);
-        load_tss(GDT.1.tss_selector);
+        set_cs(selectors.kernel_code);
+        load_ss(selectors.kernel_data);
+        load_ds(selectors.kernel_data);
+        load_tss(selectors.tss);
     }
 }
+
+// Tests
+
+#[test_case]
+fn test_selector_privilege_levels() {
+    use x86_64::PrivilegeLevel;
+
+    let selectors = selectors();
+
+    assert_eq!(selectors.kernel_code.rpl(), PrivilegeLevel::Ring0);
+    assert_eq!(selectors.kernel_data.rpl(), PrivilegeLevel::Ring0);
+    assert_eq!(selectors.user_code.rpl(), PrivilegeLevel::Ring3);
+    assert_eq!(selectors.user_data.rpl(), PrivilegeLevel::Ring3);
+
+    // the code segment the kernel is running in.
+    let cs = x86_64::instructions::segmentation::cs();
+    assert_eq!(cs, selectors.kernel_code);
+}
@@ -0,0 +1,126 @@
+use core::sync::atomic::{AtomicU64, Ordering};
+use x86_64::{
+    structures::paging::{
+        mapper::MapToError, FrameAllocator, Mapper, OffsetPageTable, PageTableFlags, PhysFrame,
+        Size4KiB,
+    },
+    PhysAddr,
+};
+
+/// The address the HPET is placed at by QEMU and most chipsets. Until the ACPI tables are parsed
+/// this is the address that should be passed to `init`.
+pub const DEFAULT_HPET_ADDRESS: u64 = 0xFED0_0000;
+
+// Offsets of the HPET registers from the base address.
+// https://wiki.osdev.org/HPET
+const CAPABILITIES_REGISTER: u64 = 0x000;
+const CONFIGURATION_REGISTER: u64 = 0x010;
+const MAIN_COUNTER_REGISTER: u64 = 0x0F0;
+
+const ENABLE_CNF: u64 = 1 << 0;
+
+// The specification limits the counter period to at most 100ns, any larger
+// value means that there is no HPET at the given address.
+const MAX_PERIOD_FEMTOSECONDS: u64 = 100_000_000;
+const FEMTOSECONDS_PER_NANOSECOND: u128 = 1_000_000;
+
+// The virtual address of the registers and the counter period in femtoseconds,
+// both zero until `init` has found a HPET.
+static BASE: AtomicU64 = AtomicU64::new(0);
+static PERIOD: AtomicU64 = AtomicU64::new(0);
+
+/// The errors that can occur when initializing the HPET.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HpetError {
+    // The registers could not be mapped into the address space.
+    MappingFailed,
+    // The capabilities register did not report a valid counter period.
+    NotPresent,
+}
+
+/// Maps the HPET registers at the given physical address, checks that a HPET is present and
+/// enables its main counter.
+///
+/// The registers are identity mapped as uncached memory, if the page is already mapped the
+/// existing mapping is used as is.
+///
+/// # Arguments
+///
+/// `base` The physical address of the HPET registers, see `DEFAULT_HPET_ADDRESS`.
+/// `mapper` The page table the registers will be mapped into.
+/// `frame_allocator` Used to allocate any page tables required for the mapping.
+pub fn init(
+    base: PhysAddr,
+    mapper: &mut OffsetPageTable,
+    frame_allocator: &mut impl FrameAllocator<Size4KiB>,
+) -> Result<(), HpetError> {
+    let frame = PhysFrame::<Size4KiB>::containing_address(base);
+    let flags = PageTableFlags::PRESENT | PageTableFlags::WRITABLE | PageTableFlags::NO_CACHE;
+
+    match unsafe { mapper.identity_map(frame, flags, frame_allocator) } {
+        Ok(flush) => flush.flush(),
+        Err(MapToError::PageAlreadyMapped(_)) => {}
+        Err(_) => return Err(HpetError::MappingFailed),
+    }
+
+    BASE.store(base.as_u64(), Ordering::SeqCst);
+
+    // the upper 32 bits of the capabilities register hold the period of the
+    // main counter in femtoseconds.
+    let period = unsafe { read_register(CAPABILITIES_REGISTER) } >> 32;
+    if period == 0 || period > MAX_PERIOD_FEMTOSECONDS {
+        BASE.store(0, Ordering::SeqCst);
+        return Err(HpetError::NotPresent);
+    }
+
+    unsafe {
+        let configuration = read_register(CONFIGURATION_REGISTER);
+        write_register(CONFIGURATION_REGISTER, configuration | ENABLE_CNF);
+    }
+
+    PERIOD.store(period, Ordering::SeqCst);
+    Ok(())
+}
+
+/// Returns true if `init` found and enabled a HPET.
+pub fn is_present() -> bool {
+    PERIOD.load(Ordering::SeqCst) != 0
+}
+
+/// Returns the period of the main counter in femtoseconds, or `None` if there is no HPET.
+pub fn period_fs() -> Option<u64> {
+    match PERIOD.load(Ordering::SeqCst) {
+        0 => None,
+        period => Some(period),
+    }
+}
+
+/// Returns the raw value of the main counter, or zero if there is no HPET.
+pub fn counter() -> u64 {
+    if !is_present() {
+        return 0;
+    }
+
+    unsafe { read_register(MAIN_COUNTER_REGISTER) }
+}
+
+/// Returns the number of nanoseconds since the main counter was enabled, or zero if there is
+/// no HPET.
+pub fn now_ns() -> u64 {
+    let period = PERIOD.load(Ordering::SeqCst) as u128;
+    (counter() as u128 * period / FEMTOSECONDS_PER_NANOSECOND) as u64
+}
+
+/// This function is unsafe because the caller must guarantee that the registers have been
+/// mapped by `init`.
+unsafe fn read_register(offset: u64) -> u64 {
+    let register = (BASE.load(Ordering::SeqCst) + offset) as *const u64;
+    core::ptr::read_volatile(register)
+}
+
+/// This function is unsafe because the caller must guarantee that the registers have been
+/// mapped by `init`.
+unsafe fn write_register(offset: u64, value: u64) {
+    let register = (BASE.load(Ordering::SeqCst) + offset) as *mut u64;
+    core::ptr::write_volatile(register, value)
+}
@@ -1,10 +1,16 @@
-use crate::std::gdt;
-use lazy_static::lazy_static;
+use crate::std::once::Once;
+use crate::std::{gdt, pit, syscall};
+use core::fmt;
+use core::sync::atomic::{AtomicBool, AtomicU64, AtomicU8, AtomicUsize, Ordering};
 use pic8259::ChainedPics;
 use spin;
-use x86_64::structures::idt::{InterruptDescriptorTable, InterruptStackFrame, PageFaultErrorCode};
+use x86_64::structures::idt::{
+    HandlerFunc, InterruptDescriptorTable, InterruptStackFrame, PageFaultErrorCode,
+};
+use x86_64::PrivilegeLevel;
 
-use crate::{print, println};
+use crate::std::trace as events;
+use crate::{exit_qemu, print, println, serial_println, trace, QemuExitCode};
 
 /// Halt loop that will allow the CPU to go into idle and only continue
 /// executing once the next interrupt arrives.
@@ -16,6 +22,14 @@ pub fn htl_loop() -> ! {
     }
 }
 
+/// Returns true if the CPU is currently accepting maskable interrupts, read
+/// from the interrupt flag (IF) of the RFLAGS register.
+pub fn are_enabled() -> bool {
+    use x86_64::registers::rflags::{self, RFlags};
+
+    rflags::read().contains(RFlags::INTERRUPT_FLAG)
+}
+
 // The index values in which will be used in the interrupt
 // descriptor table to allow the CPU to know which handler
 // to be called for external interrupts.
@@ -27,11 +41,20 @@ pub enum InterruptIndex {
     Timer = PIC_1_OFFSET,
     // The offset in which the keyboard interrupt is triggered.
     Keyboard = PIC_1_OFFSET + 1,
-    SerialPortOne = PIC_1_OFFSET + 3,
-    SerialPortTwo = PIC_1_OFFSET + 4,
+    // COM2 is on IRQ 3 and COM1 on IRQ 4.
+    SerialPortTwo = PIC_1_OFFSET + 3,
+    SerialPortOne = PIC_1_OFFSET + 4,
     ParallelPortTwoAndThree = PIC_1_OFFSET + 5,
     FloppyDisk = PIC_1_OFFSET + 6,
+    // Also raised for spurious interrupts of the master PIC.
     ParallelPortOne = PIC_1_OFFSET + 7,
+    // The real time clock on IRQ 8, the first line of the slave PIC.
+    Rtc = PIC_2_OFFSET,
+    // The PS/2 mouse on IRQ 12, the fifth line of the slave PIC.
+    Mouse = PIC_2_OFFSET + 4,
+    // The secondary ATA channel on IRQ 15, the last line of the slave PIC,
+    // also raised for spurious interrupts of the slave PIC.
+    SecondaryAta = PIC_2_OFFSET + 7,
 }
 
 impl InterruptIndex {
@@ -57,12 +80,440 @@ pub const PIC_2_OFFSET: u8 = PIC_1_OFFSET + 8;
 pub static PICS: spin::Mutex<ChainedPics> =
     spin::Mutex::new(unsafe { ChainedPics::new(PIC_1_OFFSET, PIC_2_OFFSET) });
 
-lazy_static! {
-    static ref IDT: InterruptDescriptorTable = {
+// The data ports of the two PICs, writing to these while the PIC is not
+// being initialized sets the interrupt mask register.
+// https://wiki.osdev.org/8259_PIC#Masking
+const PIC_1_DATA_PORT: u16 = 0x21;
+const PIC_2_DATA_PORT: u16 = 0xA1;
+
+/// Masking of single IRQ lines through the interrupt mask registers of the chained PICs, which
+/// the `pic8259` crate does not provide.
+pub trait PicMask {
+    /// Masks the given IRQ line (0 to 15), the interrupt will not be delivered to the CPU until
+    /// it is unmasked again. IRQs 8 to 15 are masked on the slave PIC.
+    fn mask(&mut self, irq: u8);
+
+    /// Unmasks the given IRQ line (0 to 15).
+    fn unmask(&mut self, irq: u8);
+
+    /// Returns the interrupt mask registers of the master and the slave PIC, a set bit means the
+    /// IRQ line is masked.
+    fn read_masks(&self) -> (u8, u8);
+}
+
+impl PicMask for ChainedPics {
+    fn mask(&mut self, irq: u8) {
+        update_irq_mask(irq, |mask, bit| mask | bit);
+    }
+
+    fn unmask(&mut self, irq: u8) {
+        update_irq_mask(irq, |mask, bit| mask & !bit);
+    }
+
+    fn read_masks(&self) -> (u8, u8) {
+        use x86_64::instructions::port::Port;
+
+        let mut master: Port<u8> = Port::new(PIC_1_DATA_PORT);
+        let mut slave: Port<u8> = Port::new(PIC_2_DATA_PORT);
+
+        unsafe { (master.read(), slave.read()) }
+    }
+}
+
+// Reads, updates and writes back the mask register of the PIC the IRQ line
+// belongs to. Only called through `PicMask`, so the PICS lock is held and the
+// mask is not changed while the PICs are being initialized or notified.
+fn update_irq_mask(irq: u8, update: impl Fn(u8, u8) -> u8) {
+    use x86_64::instructions::port::Port;
+
+    assert!(irq < 16, "the PICs only have 16 IRQ lines");
+
+    let (port, line) = if irq < 8 {
+        (PIC_1_DATA_PORT, irq)
+    } else {
+        (PIC_2_DATA_PORT, irq - 8)
+    };
+
+    let mut data: Port<u8> = Port::new(port);
+
+    unsafe {
+        let mask = data.read();
+        data.write(update(mask, 1 << line));
+    }
+}
+
+/// Masks the given IRQ line (0 to 15) on the PICs, see `PicMask::mask`.
+pub fn mask_irq(irq: u8) {
+    use x86_64::instructions::interrupts;
+
+    interrupts::without_interrupts(|| PICS.lock().mask(irq));
+}
+
+/// Unmasks the given IRQ line (0 to 15) on the PICs, see `PicMask::unmask`.
+pub fn unmask_irq(irq: u8) {
+    use x86_64::instructions::interrupts;
+
+    interrupts::without_interrupts(|| PICS.lock().unmask(irq));
+}
+
+/// Returns the interrupt mask registers of the PICs, see `PicMask::read_masks`.
+pub fn irq_masks() -> (u8, u8) {
+    use x86_64::instructions::interrupts;
+
+    interrupts::without_interrupts(|| PICS.lock().read_masks())
+}
+
+// The command ports of the two PICs, the OCW3 command selecting the
+// in-service register for the next read of the command port, and the end of
+// interrupt command.
+// https://wiki.osdev.org/8259_PIC#Spurious_IRQs
+const PIC_1_COMMAND_PORT: u16 = 0x20;
+const PIC_2_COMMAND_PORT: u16 = 0xA0;
+const PIC_READ_ISR: u8 = 0x0B;
+const PIC_END_OF_INTERRUPT: u8 = 0x20;
+
+/// The IRQ lines the master and the slave PIC raise spurious interrupts on, the lowest priority
+/// line of each PIC.
+pub const SPURIOUS_MASTER_IRQ: u8 = 7;
+pub const SPURIOUS_SLAVE_IRQ: u8 = 15;
+
+/// Access to the in-service registers of the chained PICs, needed to tell spurious interrupts
+/// apart from genuine ones, which the `pic8259` crate does not provide.
+pub trait PicIsr {
+    /// Returns the in-service registers of the master and the slave PIC, a set bit means the
+    /// interrupt of the IRQ line is currently being handled.
+    fn read_isr(&mut self) -> (u8, u8);
+
+    /// Signals the end of the interrupt with the given vector, to both PICs for IRQs 8 to 15.
+    fn end_of_interrupt(&mut self, vector: u8);
+
+    /// Signals the end of an interrupt to the master PIC only.
+    fn end_of_master_interrupt(&mut self);
+}
+
+impl PicIsr for ChainedPics {
+    fn read_isr(&mut self) -> (u8, u8) {
+        use x86_64::instructions::port::Port;
+
+        let mut master: Port<u8> = Port::new(PIC_1_COMMAND_PORT);
+        let mut slave: Port<u8> = Port::new(PIC_2_COMMAND_PORT);
+
+        unsafe {
+            master.write(PIC_READ_ISR);
+            slave.write(PIC_READ_ISR);
+            (master.read(), slave.read())
+        }
+    }
+
+    fn end_of_interrupt(&mut self, vector: u8) {
+        unsafe { self.notify_end_of_interrupt(vector) }
+    }
+
+    fn end_of_master_interrupt(&mut self) {
+        use x86_64::instructions::port::Port;
+
+        let mut master: Port<u8> = Port::new(PIC_1_COMMAND_PORT);
+        unsafe { master.write(PIC_END_OF_INTERRUPT) }
+    }
+}
+
+// The number of spurious interrupts of either PIC since boot.
+static SPURIOUS_INTERRUPTS: AtomicU64 = AtomicU64::new(0);
+
+/// Returns the number of spurious PIC interrupts since boot.
+pub fn spurious_interrupts() -> u64 {
+    SPURIOUS_INTERRUPTS.load(Ordering::Relaxed)
+}
+
+/// Handles an interrupt on the IRQ line a PIC raises spurious interrupts on, returning true if
+/// the interrupt is genuine.
+///
+/// A PIC raises a spurious interrupt when the interrupt request disappears before the CPU
+/// acknowledges it, in which case the line is not set in the in-service register. A genuine
+/// interrupt is ended as usual. A spurious interrupt must not be ended on the PIC that raised it,
+/// since that would end the interrupt that is actually being handled. A spurious interrupt of the
+/// slave still has to be ended on the master, which saw a genuine interrupt on the cascade line.
+///
+/// # Arguments
+///
+/// `pics` The PICs the interrupt came from.
+/// `irq` The IRQ line of the interrupt, `SPURIOUS_MASTER_IRQ` or `SPURIOUS_SLAVE_IRQ`.
+pub fn handle_spurious_irq(pics: &mut impl PicIsr, irq: u8) -> bool {
+    let (master, slave) = pics.read_isr();
+    let genuine = if irq < 8 {
+        master & (1 << irq) != 0
+    } else {
+        slave & (1 << (irq - 8)) != 0
+    };
+
+    if genuine {
+        pics.end_of_interrupt(PIC_1_OFFSET + irq);
+    } else {
+        SPURIOUS_INTERRUPTS.fetch_add(1, Ordering::Relaxed);
+
+        if irq >= 8 {
+            pics.end_of_master_interrupt();
+        }
+    }
+
+    genuine
+}
+
+// The IRQ line of the PIT timer.
+const TIMER_IRQ: u8 = 0;
+
+/// The IRQ line of the master PIC the slave PIC is chained to, which must be unmasked for any
+/// of the interrupts of the slave (IRQ 8 to 15) to be delivered.
+pub const CASCADE_IRQ: u8 = 2;
+
+/// The IRQ line of the first serial port (COM1).
+pub const SERIAL_PORT_ONE_IRQ: u8 = 4;
+
+// The number of timer interrupts handled since interrupts were enabled. Only
+// ever incremented, relaxed ordering is enough for a monotonic counter.
+static TICKS: AtomicU64 = AtomicU64::new(0);
+
+// If the timer handler prints a dot on every tick, a debugging aid that is
+// off by default so the screen and test output are not spammed. Enabled by
+// the `timer_dots` feature, or at runtime through the config store.
+static TIMER_DOTS: AtomicBool = AtomicBool::new(cfg!(feature = "timer_dots"));
+
+/// Enables or disables the dot printed by the timer handler on every tick.
+pub fn set_timer_dots(enabled: bool) {
+    TIMER_DOTS.store(enabled, Ordering::SeqCst);
+}
+
+/// Returns true if the timer handler prints a dot on every tick.
+pub fn timer_dots() -> bool {
+    TIMER_DOTS.load(Ordering::SeqCst)
+}
+
+// The number of outstanding `pause_timer` calls.
+static TIMER_PAUSE_DEPTH: AtomicUsize = AtomicUsize::new(0);
+
+/// Returns the number of timer interrupts handled since interrupts were enabled.
+pub fn ticks() -> u64 {
+    TICKS.load(Ordering::Relaxed)
+}
+
+/// Halts until at least `n` timer ticks have passed, a coarse blocking delay until a scheduler
+/// exists. Interrupts keep being handled while waiting.
+///
+/// The resolution is a single tick, which depends on the PIT frequency (10ms at the default
+/// 100Hz set by `init`, see `pit::set_pit_frequency`). The delay may be up to a tick shorter than requested
+/// since the first tick can arrive at any time after the call.
+///
+/// # Arguments
+///
+/// `n` The number of ticks to wait for.
+///
+/// # Example
+///
+/// ```
+/// interrupts::sleep_ticks(interrupts::ms_to_ticks(500));
+/// ```
+pub fn sleep_ticks(n: u64) {
+    // halting with interrupts disabled would never wake up again.
+    assert!(are_enabled(), "sleep_ticks called with interrupts disabled");
+
+    sleep_ticks_with(n, x86_64::instructions::hlt);
+}
+
+// Waits until `n` ticks of the current clock have passed, calling `wait`
+// between reads of the clock so that tests can advance a fake clock.
+fn sleep_ticks_with(n: u64, mut wait: impl FnMut()) {
+    use crate::std::clock;
+
+    let start = clock::ticks();
+    while clock::ticks() - start < n {
+        wait();
+    }
+}
+
+/// Converts milliseconds into the number of timer ticks covering at least that long at the
+/// current PIT frequency, rounding up so that a non zero duration is always at least a single
+/// tick.
+pub fn ms_to_ticks(ms: u64) -> u64 {
+    let divisor = pit::reload() as u64 * 1000;
+    (ms * pit::BASE_FREQUENCY_HZ as u64 + divisor - 1) / divisor
+}
+
+/// Converts a number of timer ticks into whole milliseconds at the current PIT frequency,
+/// rounding down.
+pub fn ticks_to_ms(ticks: u64) -> u64 {
+    (ticks as u128 * pit::reload() as u128 * 1000 / pit::BASE_FREQUENCY_HZ as u128) as u64
+}
+
+/// Stops timer interrupts from being delivered by masking the timer IRQ, leaving all other
+/// interrupts enabled. Calls nest, the timer only resumes once every pause has been matched by
+/// a call to `resume_timer`.
+///
+/// Ticks are not counted while the timer is paused and are not caught up on resume, the tick
+/// count simply continues from where it stopped.
+pub fn pause_timer() {
+    if TIMER_PAUSE_DEPTH.fetch_add(1, Ordering::SeqCst) == 0 {
+        mask_irq(TIMER_IRQ);
+    }
+}
+
+/// Resumes timer interrupts once the outermost `pause_timer` has been matched.
+pub fn resume_timer() {
+    let previous = TIMER_PAUSE_DEPTH.fetch_sub(1, Ordering::SeqCst);
+    assert!(previous > 0, "resume_timer called without a matching pause_timer");
+
+    if previous == 1 {
+        unmask_irq(TIMER_IRQ);
+    }
+}
+
+/// The interrupt handlers whose overhead can be measured, see `set_handler_benchmark`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BenchmarkedHandler {
+    Timer,
+    Keyboard,
+}
+
+/// The cycles spent from handler entry to the end of interrupt notification, over all samples
+/// taken since the benchmark was last reset.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HandlerCycles {
+    pub samples: u64,
+    pub min: u64,
+    pub avg: u64,
+    pub max: u64,
+}
+
+// The cycle counts of a single handler, min starts at u64::MAX so that the
+// first sample always replaces it.
+struct CycleCounter {
+    samples: AtomicU64,
+    total: AtomicU64,
+    min: AtomicU64,
+    max: AtomicU64,
+}
+
+impl CycleCounter {
+    const fn new() -> CycleCounter {
+        CycleCounter {
+            samples: AtomicU64::new(0),
+            total: AtomicU64::new(0),
+            min: AtomicU64::new(u64::MAX),
+            max: AtomicU64::new(0),
+        }
+    }
+
+    fn record(&self, cycles: u64) {
+        self.samples.fetch_add(1, Ordering::SeqCst);
+        self.total.fetch_add(cycles, Ordering::SeqCst);
+        self.min.fetch_min(cycles, Ordering::SeqCst);
+        self.max.fetch_max(cycles, Ordering::SeqCst);
+    }
+
+    fn reset(&self) {
+        self.samples.store(0, Ordering::SeqCst);
+        self.total.store(0, Ordering::SeqCst);
+        self.min.store(u64::MAX, Ordering::SeqCst);
+        self.max.store(0, Ordering::SeqCst);
+    }
+
+    fn read(&self) -> Option<HandlerCycles> {
+        let samples = self.samples.load(Ordering::SeqCst);
+        if samples == 0 {
+            return None;
+        }
+
+        Some(HandlerCycles {
+            samples,
+            min: self.min.load(Ordering::SeqCst),
+            avg: self.total.load(Ordering::SeqCst) / samples,
+            max: self.max.load(Ordering::SeqCst),
+        })
+    }
+}
+
+// If the timer and keyboard handlers measure their own overhead, the cost
+// when disabled is a single atomic load per interrupt.
+static HANDLER_BENCHMARK: AtomicBool = AtomicBool::new(false);
+static TIMER_CYCLES: CycleCounter = CycleCounter::new();
+static KEYBOARD_CYCLES: CycleCounter = CycleCounter::new();
+
+/// Enables or disables measuring the overhead of the timer and keyboard handlers at runtime.
+/// Enabling resets all previously taken samples.
+///
+/// # Example
+///
+/// ```
+/// interrupts::set_handler_benchmark(true);
+/// // ... let some interrupts happen ...
+/// interrupts::set_handler_benchmark(false);
+/// interrupts::print_handler_benchmark();
+/// ```
+pub fn set_handler_benchmark(enabled: bool) {
+    if enabled {
+        TIMER_CYCLES.reset();
+        KEYBOARD_CYCLES.reset();
+    }
+
+    HANDLER_BENCHMARK.store(enabled, Ordering::SeqCst);
+}
+
+/// Returns true if the handler overhead is currently being measured.
+pub fn handler_benchmark_enabled() -> bool {
+    HANDLER_BENCHMARK.load(Ordering::SeqCst)
+}
+
+/// Returns the measured overhead of the given handler, or `None` if no samples have been taken.
+pub fn handler_benchmark(handler: BenchmarkedHandler) -> Option<HandlerCycles> {
+    match handler {
+        BenchmarkedHandler::Timer => TIMER_CYCLES.read(),
+        BenchmarkedHandler::Keyboard => KEYBOARD_CYCLES.read(),
+    }
+}
+
+/// Prints the measured overhead of every handler with samples to serial.
+pub fn print_handler_benchmark() {
+    for &handler in &[BenchmarkedHandler::Timer, BenchmarkedHandler::Keyboard] {
+        if let Some(cycles) = handler_benchmark(handler) {
+            serial_println!(
+                "{:?} handler: {} samples, min {} avg {} max {} cycles",
+                handler, cycles.samples, cycles.min, cycles.avg, cycles.max
+            );
+        }
+    }
+}
+
+/// Returns the time stamp counter at handler entry, or zero when the benchmark is disabled.
+fn benchmark_start() -> u64 {
+    if HANDLER_BENCHMARK.load(Ordering::SeqCst) {
+        unsafe { core::arch::x86_64::_rdtsc() }
+    } else {
+        0
+    }
+}
+
+/// Records the cycles since `start` unless the benchmark was disabled at handler entry.
+fn benchmark_end(counter: &CycleCounter, start: u64) {
+    if start != 0 {
+        counter.record(unsafe { core::arch::x86_64::_rdtsc() } - start);
+    }
+}
+
+static IDT: Once<InterruptDescriptorTable> = Once::new();
+
+fn idt() -> &'static InterruptDescriptorTable {
+    IDT.call_once(|| {
         let mut idt = InterruptDescriptorTable::new();
 
         idt.breakpoint.set_handler_fn(breakpoint_handler);
         idt.page_fault.set_handler_fn(page_fault_handler);
+        idt.divide_error.set_handler_fn(divide_error_handler);
+        idt.invalid_opcode.set_handler_fn(invalid_opcode_handler);
+        idt.non_maskable_interrupt.set_handler_fn(nmi_handler);
+        idt.overflow.set_handler_fn(overflow_handler);
+        idt.bound_range_exceeded.set_handler_fn(bound_range_exceeded_handler);
+        idt.x87_floating_point.set_handler_fn(x87_floating_point_handler);
+        idt.segment_not_present.set_handler_fn(segment_not_present_handler);
+        idt.stack_segment_fault.set_handler_fn(stack_segment_fault_handler);
 
         // configure the double fault handler with the
         // alternative stack to ensure double faults
@@ -80,14 +531,409 @@ lazy_static! {
         idt[InterruptIndex::Keyboard.as_usize()]
         .set_handler_fn(ps2_keyboard_interrupt_handler);
 
+        idt[InterruptIndex::SerialPortOne.as_usize()]
+            .set_handler_fn(serial_port_one_interrupt_handler);
+
+        idt[InterruptIndex::Rtc.as_usize()]
+            .set_handler_fn(rtc_interrupt_handler);
+
+        idt[InterruptIndex::Mouse.as_usize()]
+            .set_handler_fn(ps2_mouse_interrupt_handler);
+
+        idt[InterruptIndex::ParallelPortOne.as_usize()]
+            .set_handler_fn(spurious_master_interrupt_handler);
+
+        idt[InterruptIndex::SecondaryAta.as_usize()]
+            .set_handler_fn(spurious_slave_interrupt_handler);
+
+        // the syscall entry may be issued from user mode. It is naked so that it
+        // can return its result in rax, and is only given the type of a handler.
+        let syscall_entry: HandlerFunc =
+            unsafe { core::mem::transmute(syscall::syscall_entry as unsafe extern "C" fn()) };
+        idt[syscall::SYSCALL_VECTOR]
+            .set_handler_fn(syscall_entry)
+            .set_privilege_level(PrivilegeLevel::Ring3);
 
         idt
-    };
+    })
+}
+
+/// The action taken by the double fault handler once a double fault has occurred.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum DoubleFaultAction {
+    // Panic with the stack frame, the default for the kernel.
+    Panic = 0,
+    // Report success on the serial port and exit QEMU, used by tests
+    // in which a double fault is the expected outcome.
+    ExitQemuSuccess = 1,
+}
+
+// The currently configured double fault action, stored as the u8
+// representation of `DoubleFaultAction` so it can be read from within
+// the handler without taking a lock.
+static DOUBLE_FAULT_ACTION: AtomicU8 = AtomicU8::new(DoubleFaultAction::Panic as u8);
+
+/// Sets the action taken by the double fault handler.
+///
+/// # Arguments
+///
+/// `action` The action that will be taken on the next double fault.
+pub fn set_double_fault_action(action: DoubleFaultAction) {
+    DOUBLE_FAULT_ACTION.store(action as u8, Ordering::SeqCst);
+}
+
+/// Returns the action currently taken by the double fault handler.
+pub fn double_fault_action() -> DoubleFaultAction {
+    match DOUBLE_FAULT_ACTION.load(Ordering::SeqCst) {
+        1 => DoubleFaultAction::ExitQemuSuccess,
+        _ => DoubleFaultAction::Panic,
+    }
+}
+
+// The number of breakpoint exceptions handled since boot.
+static BREAKPOINT_COUNT: AtomicU64 = AtomicU64::new(0);
+
+/// Returns the number of breakpoint exceptions that have been handled since boot.
+pub fn breakpoint_count() -> u64 {
+    BREAKPOINT_COUNT.load(Ordering::SeqCst)
+}
+
+// The number of breakpoint handlers currently running, and the most that
+// have ever been running at once.
+static BREAKPOINT_DEPTH: AtomicUsize = AtomicUsize::new(0);
+static BREAKPOINT_MAX_DEPTH: AtomicUsize = AtomicUsize::new(0);
+
+// A function run at the end of every breakpoint, used for debugging.
+static BREAKPOINT_HOOK: spin::Mutex<Option<fn()>> = spin::Mutex::new(None);
+
+/// Returns the deepest nesting of breakpoint handlers seen since boot.
+pub fn breakpoint_max_depth() -> usize {
+    BREAKPOINT_MAX_DEPTH.load(Ordering::SeqCst)
+}
+
+/// Sets a function that is run from within the breakpoint handler after the breakpoint has been
+/// reported, or removes it when given `None`. The hook may itself hit a breakpoint.
+pub fn set_breakpoint_hook(hook: Option<fn()>) {
+    *BREAKPOINT_HOOK.lock() = hook;
+}
+
+/// The exceptions after which the kernel halts, as passed to the exception hook.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Exception {
+    InvalidOpcode,
+    Overflow,
+    BoundRangeExceeded,
+    X87FloatingPoint,
+    NonMaskableInterrupt,
+    // The segment related exceptions, with the decoded error code.
+    SegmentNotPresent(SelectorErrorCode),
+    StackSegmentFault(SelectorErrorCode),
+}
+
+// A function run by the halting exception handlers before the CPU is halted,
+// used by tests to check which handler of the kernel's IDT was entered.
+static EXCEPTION_HOOK: spin::Mutex<Option<fn(Exception)>> = spin::Mutex::new(None);
+
+/// Sets a function that is run from within the handlers of the exceptions after which the kernel
+/// halts, see `Exception`, after the exception has been reported and before the CPU is halted, or
+/// removes it when given `None`.
+///
+/// # Arguments
+///
+/// `hook` The function, given the exception that was raised.
+///
+/// # Example
+///
+/// ```
+/// interrupts::set_exception_hook(Some(|exception| {
+///     serial_println!("halting after {:?}", exception);
+/// }));
+/// ```
+pub fn set_exception_hook(hook: Option<fn(Exception)>) {
+    *EXCEPTION_HOOK.lock() = hook;
+}
+
+// Runs the exception hook if one is set. The lock is only tried, the
+// exception may have interrupted code setting the hook.
+fn run_exception_hook(exception: Exception) {
+    let hook = EXCEPTION_HOOK.try_lock().and_then(|hook| *hook);
+    if let Some(hook) = hook {
+        hook(exception);
+    }
+}
+
+// The system control ports, holding the status of the sources of a
+// non-maskable interrupt.
+// https://wiki.osdev.org/Non_Maskable_Interrupt
+const SYSTEM_CONTROL_PORT_A: u16 = 0x92;
+const SYSTEM_CONTROL_PORT_B: u16 = 0x61;
+
+const CONTROL_A_WATCHDOG_TIMEOUT: u8 = 1 << 4;
+const CONTROL_B_IO_CHANNEL_CHECK: u8 = 1 << 6;
+const CONTROL_B_MEMORY_PARITY: u8 = 1 << 7;
+
+/// The causes of a non-maskable interrupt reported by the system control ports. None of them
+/// being set means the NMI was raised by something else, e.g. a software `int 2`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct NmiStatus {
+    pub memory_parity_error: bool,
+    pub io_channel_check: bool,
+    pub watchdog_timeout: bool,
+}
+
+impl fmt::Display for NmiStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let causes = [
+            (self.memory_parity_error, "memory parity error"),
+            (self.io_channel_check, "I/O channel check"),
+            (self.watchdog_timeout, "watchdog timeout"),
+        ];
+
+        let mut any = false;
+        for &(_, cause) in causes.iter().filter(|(set, _)| *set) {
+            if any {
+                write!(f, ", ")?;
+            }
+
+            write!(f, "{}", cause)?;
+            any = true;
+        }
+
+        if !any {
+            write!(f, "unknown cause")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Reads the causes of a non-maskable interrupt from the system control ports.
+pub fn nmi_status() -> NmiStatus {
+    use x86_64::instructions::port::PortReadOnly;
+
+    let mut control_a: PortReadOnly<u8> = PortReadOnly::new(SYSTEM_CONTROL_PORT_A);
+    let mut control_b: PortReadOnly<u8> = PortReadOnly::new(SYSTEM_CONTROL_PORT_B);
+    let (control_a, control_b) = unsafe { (control_a.read(), control_b.read()) };
+
+    NmiStatus {
+        memory_parity_error: control_b & CONTROL_B_MEMORY_PARITY != 0,
+        io_channel_check: control_b & CONTROL_B_IO_CHANNEL_CHECK != 0,
+        watchdog_timeout: control_a & CONTROL_A_WATCHDOG_TIMEOUT != 0,
+    }
+}
+
+/// The descriptor table a selector error code refers to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SelectorTable {
+    Gdt,
+    Idt,
+    Ldt,
+}
+
+/// The error code of the segment related exceptions, identifying the selector that caused the
+/// fault.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SelectorErrorCode {
+    /// True if the fault happened while delivering an external event, e.g. a hardware interrupt.
+    pub external: bool,
+    pub table: SelectorTable,
+    /// The index of the descriptor in the table.
+    pub index: u16,
+}
+
+impl SelectorErrorCode {
+    /// Decodes the error code pushed by the CPU.
+    ///
+    /// # Arguments
+    ///
+    /// `error_code` The error code passed to the exception handler.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// // the selector 0x10, the third entry of the GDT.
+    /// let selector = SelectorErrorCode::new(0x10);
+    /// assert_eq!(selector.index, 2);
+    /// ```
+    pub fn new(error_code: u64) -> SelectorErrorCode {
+        // bit 1 selects the IDT, otherwise bit 2 selects the LDT over the GDT.
+        let table = match (error_code >> 1) & 0b11 {
+            0b00 => SelectorTable::Gdt,
+            0b10 => SelectorTable::Ldt,
+            _ => SelectorTable::Idt,
+        };
+
+        SelectorErrorCode {
+            external: error_code & 1 != 0,
+            table,
+            index: ((error_code >> 3) & 0x1FFF) as u16,
+        }
+    }
+}
+
+impl fmt::Display for SelectorErrorCode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let table = match self.table {
+            SelectorTable::Gdt => "GDT",
+            SelectorTable::Idt => "IDT",
+            SelectorTable::Ldt => "LDT",
+        };
+
+        write!(f, "{} index {}", table, self.index)?;
+        if self.external {
+            write!(f, " (external)")?;
+        }
+
+        Ok(())
+    }
 }
 
 #[allow(dead_code)]
 pub fn init_idt() {
-    IDT.load();
+    idt().load();
+}
+
+/// The maximum number of timer callbacks that can be registered at once.
+pub const MAX_TIMER_CALLBACKS: usize = 16;
+
+/// The errors that can occur when registering a timer callback.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimerCallbackError {
+    // All of the callback slots are in use.
+    TableFull,
+    // A callback cannot run every zero ticks.
+    ZeroPeriod,
+}
+
+/// A description of a registered timer callback, as returned by `list_timer_callbacks`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimerCallbackInfo {
+    pub id: usize,
+    pub name: &'static str,
+    pub period_ticks: u64,
+    pub fire_count: u64,
+}
+
+#[derive(Clone, Copy)]
+struct TimerCallback {
+    info: TimerCallbackInfo,
+    callback: fn(),
+    // The number of ticks left until the callback next runs.
+    remaining_ticks: u64,
+}
+
+// The table of registered callbacks, the index of the slot is the id of the
+// callback. Only ever locked with interrupts disabled, since the timer handler
+// locks it as well.
+static TIMER_CALLBACKS: spin::Mutex<[Option<TimerCallback>; MAX_TIMER_CALLBACKS]> =
+    spin::Mutex::new([None; MAX_TIMER_CALLBACKS]);
+
+/// Registers a callback that is run from the timer interrupt handler every `period_ticks`
+/// timer ticks, returning the id of the callback. Callbacks run with interrupts disabled and
+/// must be short.
+///
+/// # Arguments
+///
+/// `name` A name describing the callback, shown when listing the callbacks.
+/// `period_ticks` The number of ticks between each run of the callback.
+/// `callback` The function being run.
+///
+/// # Example
+///
+/// ```
+/// interrupts::register_timer_callback("cursor blink", 9, blink_cursor)?;
+/// ```
+pub fn register_timer_callback(
+    name: &'static str,
+    period_ticks: u64,
+    callback: fn(),
+) -> Result<usize, TimerCallbackError> {
+    use x86_64::instructions::interrupts;
+
+    if period_ticks == 0 {
+        return Err(TimerCallbackError::ZeroPeriod);
+    }
+
+    interrupts::without_interrupts(|| -> Result<usize, TimerCallbackError> {
+        let mut callbacks = TIMER_CALLBACKS.lock();
+        let (id, slot) = callbacks
+            .iter_mut()
+            .enumerate()
+            .find(|(_, slot)| slot.is_none())
+            .ok_or(TimerCallbackError::TableFull)?;
+
+        *slot = Some(TimerCallback {
+            info: TimerCallbackInfo {
+                id,
+                name,
+                period_ticks,
+                fire_count: 0,
+            },
+            callback,
+            remaining_ticks: period_ticks,
+        });
+
+        Ok(id)
+    })
+}
+
+/// Removes the timer callback with the given id, returning false if no such callback exists.
+pub fn unregister_timer_callback(id: usize) -> bool {
+    use x86_64::instructions::interrupts;
+
+    interrupts::without_interrupts(|| match TIMER_CALLBACKS.lock().get_mut(id) {
+        Some(slot) => slot.take().is_some(),
+        None => false,
+    })
+}
+
+/// Returns a snapshot of all registered timer callbacks, with their periods and the number of
+/// times that each has run.
+pub fn list_timer_callbacks() -> [Option<TimerCallbackInfo>; MAX_TIMER_CALLBACKS] {
+    use x86_64::instructions::interrupts;
+
+    let mut list = [None; MAX_TIMER_CALLBACKS];
+
+    interrupts::without_interrupts(|| {
+        for (entry, slot) in list.iter_mut().zip(TIMER_CALLBACKS.lock().iter()) {
+            *entry = slot.map(|callback| callback.info);
+        }
+    });
+
+    list
+}
+
+/// Prints all registered timer callbacks.
+pub fn print_timer_callbacks() {
+    for info in list_timer_callbacks().iter().flatten() {
+        println!(
+            "timer {}: {} every {} ticks, fired {} times",
+            info.id, info.name, info.period_ticks, info.fire_count
+        );
+    }
+}
+
+/// Counts down every registered callback and runs those that are due. The due callbacks are
+/// collected before being run so that the table is not locked while they execute.
+fn run_timer_callbacks() {
+    let mut due: [Option<fn()>; MAX_TIMER_CALLBACKS] = [None; MAX_TIMER_CALLBACKS];
+
+    for (due, slot) in due.iter_mut().zip(TIMER_CALLBACKS.lock().iter_mut()) {
+        if let Some(callback) = slot {
+            callback.remaining_ticks -= 1;
+
+            if callback.remaining_ticks == 0 {
+                callback.remaining_ticks = callback.info.period_ticks;
+                callback.info.fire_count += 1;
+                *due = Some(callback.callback);
+            }
+        }
+    }
+
+    for callback in due.iter().flatten() {
+        callback();
+    }
 }
 
 /// Exception Type
@@ -105,8 +951,33 @@ pub fn init_idt() {
 ///
 /// `stack_frame` The stack frame at the point of which the breakpoint was hit.
 ///
+/// The handler is re-entrant, a breakpoint hit while handling another breakpoint (e.g. from
+/// within the print path) is reported with its nesting depth. The output is written with
+/// `try_print` so that a breakpoint hit while the writer is locked falls back to the serial
+/// port instead of dead locking, and is dropped if both are locked.
 extern "x86-interrupt" fn breakpoint_handler(stack_frame: InterruptStackFrame) {
-    println!("EXCEPTION: BREAKPOINT\n{:#?}", stack_frame)
+    use crate::std::{serial, vga_buffer};
+
+    let depth = BREAKPOINT_DEPTH.fetch_add(1, Ordering::SeqCst) + 1;
+    BREAKPOINT_MAX_DEPTH.fetch_max(depth, Ordering::SeqCst);
+    BREAKPOINT_COUNT.fetch_add(1, Ordering::SeqCst);
+
+    let report = |print: fn(core::fmt::Arguments) -> bool| {
+        print(format_args!("EXCEPTION: BREAKPOINT (depth {})\n{:#?}\n", depth, stack_frame))
+    };
+
+    if !report(vga_buffer::try_print) {
+        report(serial::try_print);
+    }
+
+    // copy the hook out before running it, the hook may hit a breakpoint
+    // which would otherwise dead lock on the still held lock.
+    let hook = *BREAKPOINT_HOOK.lock();
+    if let Some(hook) = hook {
+        hook();
+    }
+
+    BREAKPOINT_DEPTH.fetch_sub(1, Ordering::SeqCst);
 }
 
 /// Handler for processing double fault exceptions.
@@ -141,16 +1012,40 @@ extern "x86-interrupt" fn breakpoint_handler(stack_frame: InterruptStackFrame) {
 /// Segment Not Present,
 /// Stack-Segment Fault,
 /// General Protection Fault
+///
+/// What happens after a double fault is decided by `DOUBLE_FAULT_ACTION`, see
+/// `set_double_fault_action`.
 extern "x86-interrupt" fn double_fault_handler(
     stack_frame: InterruptStackFrame,
     _error_code: u64,
 ) -> ! {
-    panic!("EXCEPTION: DOUBLE FAULT\n{:#?}", stack_frame)
+    match double_fault_action() {
+        DoubleFaultAction::Panic => panic!("EXCEPTION: DOUBLE FAULT\n{:#?}", stack_frame),
+        DoubleFaultAction::ExitQemuSuccess => {
+            serial_println!("[ok]");
+            exit_qemu(QemuExitCode::Success);
+            htl_loop();
+        }
+    }
 }
 
 /// Handler for processing timer interrupts.
 extern "x86-interrupt" fn timer_interrupt_handler(_stack_frame: InterruptStackFrame) {
-    print!(".");
+    let start = benchmark_start();
+
+    TICKS.fetch_add(1, Ordering::Relaxed);
+    trace!(events::TIMER_INTERRUPT, 0);
+
+    if timer_dots() {
+        print!(".");
+    }
+
+    // write any batched output to the screen, bounding the refresh rate of
+    // batched output to the frequency of the timer.
+    crate::std::vga_buffer::flush();
+
+    run_timer_callbacks();
+    crate::std::task::timer::wake_due(crate::std::clock::ticks());
 
     // Let the PICS know that the interrupt has been handled via
     // EOI (end of interrupt). If not done, the PIC will assume
@@ -159,13 +1054,15 @@ extern "x86-interrupt" fn timer_interrupt_handler(_stack_frame: InterruptStackFr
         PICS.lock()
             .notify_end_of_interrupt(InterruptIndex::Timer.as_u8())
     }
+
+    benchmark_end(&TIMER_CYCLES, start);
 }
 
 // Handler for processing interrupts triggered via a PS2 keyboard input.
 extern "x86-interrupt" fn ps2_keyboard_interrupt_handler(_stack_frame: InterruptStackFrame) {
-    use pc_keyboard::{layouts, DecodedKey, HandleControl, Keyboard, ScancodeSet1};
     use x86_64::instructions::port::Port;
-    use spin::Mutex;
+
+    let start = benchmark_start();
 
     // we need to read from the PS2 controller which is on the I/O port of x60.
     // https://wiki.osdev.org/I/O_Ports#The_list
@@ -174,28 +1071,13 @@ extern "x86-interrupt" fn ps2_keyboard_interrupt_handler(_stack_frame: Interrupt
     // notification to end correctly, and thus allowing another key press.
     //
     // PS2 Only, USB keyboards don't use interrupts to generate a input.
-    lazy_static! {
-        static ref KEYBOARD: Mutex<Keyboard<layouts::Us104Key, ScancodeSet1>> =
-            Mutex::new(Keyboard::new(layouts::Us104Key, ScancodeSet1,
-                HandleControl::Ignore)
-            );
-    }
-
-    let mut keyboard = KEYBOARD.lock();
     let mut port = Port::new(0x60);
 
     let scan_code: u8 = unsafe { port.read() };
+    trace!(events::KEYBOARD_INTERRUPT, scan_code);
 
-    if let Ok(Some(key_event)) = keyboard.add_byte(scan_code) {
-        if let Some(key) = keyboard.process_keyevent(key_event) {
-            match key {
-                DecodedKey::Unicode(character) => print!("{}", character),
-                DecodedKey::RawKey(key) => print!("{:?}", key),
-            }
-        }
-
-
-    }
+    // decoding happens outside of the interrupt, see `keyboard::poll_keyboard`.
+    crate::std::keyboard::push_scan_code(scan_code);
 
     // Let the PICS know that the interrupt has been handled via
     // EOI (end of interrupt). If not done, the PIC will assume
@@ -204,11 +1086,178 @@ extern "x86-interrupt" fn ps2_keyboard_interrupt_handler(_stack_frame: Interrupt
         PICS.lock()
             .notify_end_of_interrupt(InterruptIndex::Keyboard.as_u8())
     }
+
+    benchmark_end(&KEYBOARD_CYCLES, start);
+}
+
+// Handler for the periodic interrupt of the real time clock, see
+// `rtc::enable_periodic_interrupt`.
+extern "x86-interrupt" fn rtc_interrupt_handler(_stack_frame: InterruptStackFrame) {
+    crate::std::rtc::handle_interrupt();
+
+    // the EOI is sent to both PICs since the interrupt came from the slave.
+    unsafe {
+        PICS.lock()
+            .notify_end_of_interrupt(InterruptIndex::Rtc.as_u8())
+    }
+}
+
+// Handlers for the lowest priority line of each PIC, which nothing drives
+// yet, so every interrupt on them is expected to be spurious. See
+// `handle_spurious_irq`.
+extern "x86-interrupt" fn spurious_master_interrupt_handler(_stack_frame: InterruptStackFrame) {
+    handle_spurious_irq(&mut *PICS.lock(), SPURIOUS_MASTER_IRQ);
+}
+
+extern "x86-interrupt" fn spurious_slave_interrupt_handler(_stack_frame: InterruptStackFrame) {
+    handle_spurious_irq(&mut *PICS.lock(), SPURIOUS_SLAVE_IRQ);
+}
+
+// Handler for processing interrupts triggered by the PS/2 mouse, one for
+// every byte of a packet. Like the keyboard the bytes are queued and
+// assembled outside of the interrupt, see `mouse::poll_mouse`.
+extern "x86-interrupt" fn ps2_mouse_interrupt_handler(_stack_frame: InterruptStackFrame) {
+    use x86_64::instructions::port::Port;
+
+    let mut port = Port::new(0x60);
+    let byte: u8 = unsafe { port.read() };
+    crate::std::mouse::push_packet_byte(byte);
+
+    // the EOI is sent to both PICs since the interrupt came from the slave.
+    unsafe {
+        PICS.lock()
+            .notify_end_of_interrupt(InterruptIndex::Mouse.as_u8())
+    }
+}
+
+// Handler for processing interrupts raised by the first serial port once
+// received data is available.
+extern "x86-interrupt" fn serial_port_one_interrupt_handler(_stack_frame: InterruptStackFrame) {
+    // reading the received bytes clears the interrupt on the UART.
+    crate::std::serial::receive_pending();
+
+    unsafe {
+        PICS.lock()
+            .notify_end_of_interrupt(InterruptIndex::SerialPortOne.as_u8())
+    }
+}
+
+// Handler for the divide error exception (#DE), raised by `div` and `idiv`
+// when dividing by zero or when the quotient does not fit the destination.
+extern "x86-interrupt" fn divide_error_handler(stack_frame: InterruptStackFrame) {
+    println!("EXCEPTION: DIVIDE ERROR");
+    println!("{:#?}", stack_frame);
+
+    htl_loop();
+}
+
+// Handler for the invalid opcode exception (#UD), raised when executing an
+// instruction that does not exist or is not supported by the CPU. The
+// instruction pointer of the stack frame points at the offending opcode.
+extern "x86-interrupt" fn invalid_opcode_handler(stack_frame: InterruptStackFrame) {
+    println!("EXCEPTION: INVALID OPCODE");
+    println!("{:#?}", stack_frame);
+    run_exception_hook(Exception::InvalidOpcode);
+
+    htl_loop();
+}
+
+// Handler for the overflow exception (#OF), raised by `into` when the
+// overflow flag is set, or by a software `int 4`.
+extern "x86-interrupt" fn overflow_handler(stack_frame: InterruptStackFrame) {
+    println!("EXCEPTION: OVERFLOW");
+    println!("{:#?}", stack_frame);
+    run_exception_hook(Exception::Overflow);
+
+    htl_loop();
+}
+
+// Handler for the bound range exceeded exception (#BR), raised by `bound`
+// when an index is outside of the given bounds.
+extern "x86-interrupt" fn bound_range_exceeded_handler(stack_frame: InterruptStackFrame) {
+    println!("EXCEPTION: BOUND RANGE EXCEEDED");
+    println!("{:#?}", stack_frame);
+    run_exception_hook(Exception::BoundRangeExceeded);
+
+    htl_loop();
+}
+
+// Handler for the x87 floating point exception (#MF), raised by the next
+// x87 instruction after an unmasked floating point error.
+extern "x86-interrupt" fn x87_floating_point_handler(stack_frame: InterruptStackFrame) {
+    println!("EXCEPTION: x87 FLOATING POINT");
+    println!("{:#?}", stack_frame);
+    run_exception_hook(Exception::X87FloatingPoint);
+
+    htl_loop();
+}
+
+// Handler for the segment not present exception (#NP), raised when loading
+// a segment or gate whose descriptor is marked as not present.
+extern "x86-interrupt" fn segment_not_present_handler(
+    stack_frame: InterruptStackFrame,
+    error_code: u64,
+) {
+    let selector = SelectorErrorCode::new(error_code);
+
+    println!("EXCEPTION: SEGMENT NOT PRESENT");
+    println!("Selector: {}", selector);
+    println!("{:#?}", stack_frame);
+    run_exception_hook(Exception::SegmentNotPresent(selector));
+
+    htl_loop();
+}
+
+// Handler for the stack segment fault (#SS), raised when loading a stack
+// segment that is not present, or on a non-canonical stack address in which
+// case the error code is zero.
+extern "x86-interrupt" fn stack_segment_fault_handler(
+    stack_frame: InterruptStackFrame,
+    error_code: u64,
+) {
+    let selector = SelectorErrorCode::new(error_code);
+
+    println!("EXCEPTION: STACK SEGMENT FAULT");
+    println!("Selector: {}", selector);
+    println!("{:#?}", stack_frame);
+    run_exception_hook(Exception::StackSegmentFault(selector));
+
+    htl_loop();
+}
+
+// Handler for non-maskable interrupts, raised by hardware errors such as
+// memory parity errors or a watchdog. The machine can not be trusted to
+// continue afterwards, so the cause is reported and the CPU halted. The
+// report falls back to the serial port since the NMI may have interrupted
+// code holding the writer lock.
+extern "x86-interrupt" fn nmi_handler(stack_frame: InterruptStackFrame) {
+    use crate::std::{serial, vga_buffer};
+
+    let status = nmi_status();
+    let report = |print: fn(core::fmt::Arguments) -> bool| {
+        print(format_args!(
+            "EXCEPTION: NON-MASKABLE INTERRUPT: {}\n{:#?}\n",
+            status, stack_frame
+        ))
+    };
+
+    if !report(vga_buffer::try_print) {
+        report(serial::try_print);
+    }
+    run_exception_hook(Exception::NonMaskableInterrupt);
+
+    htl_loop();
 }
 
 extern "x86-interrupt" fn page_fault_handler(stack_frame: InterruptStackFrame, error_code: PageFaultErrorCode) {
     use x86_64::registers::control::Cr2;
 
+    // a fault in the heap growth range is resolved by mapping the page, after
+    // which returning retries the faulting instruction.
+    if crate::std::memory::handle_demand_fault(Cr2::read(), error_code) {
+        return;
+    }
+
     println!("EXCEPTION: PAGE FAULT");
     println!("Accessed Address: {:?}", Cr2::read());
     println!("Error Code: {:?}", error_code);
@@ -225,3 +1274,274 @@ fn test_breakpoint_exception() {
     // then we have passed since it should not fault.
     x86_64::instructions::interrupts::int3();
 }
+
+#[test_case]
+fn test_are_enabled_tracks_interrupt_flag() {
+    use x86_64::instructions::interrupts;
+
+    assert!(are_enabled());
+    interrupts::without_interrupts(|| assert!(!are_enabled()));
+    assert!(are_enabled());
+}
+
+#[test_case]
+fn test_list_timer_callbacks() {
+    fn fast() {}
+    fn slow() {}
+
+    let fast_id = register_timer_callback("fast", 2, fast).expect("register failed");
+    let slow_id = register_timer_callback("slow", 50, slow).expect("register failed");
+
+    let list = list_timer_callbacks();
+    let fast_info = list[fast_id].expect("fast callback is listed");
+    let slow_info = list[slow_id].expect("slow callback is listed");
+
+    assert_eq!((fast_info.name, fast_info.period_ticks), ("fast", 2));
+    assert_eq!((slow_info.name, slow_info.period_ticks), ("slow", 50));
+
+    assert!(unregister_timer_callback(fast_id));
+    assert!(unregister_timer_callback(slow_id));
+    assert!(list_timer_callbacks()[fast_id].is_none());
+}
+
+#[test_case]
+fn test_nested_breakpoint_is_reported() {
+    static NESTED: AtomicBool = AtomicBool::new(false);
+
+    // fire a second breakpoint from within the first, only once.
+    fn nested_breakpoint() {
+        if !NESTED.swap(true, Ordering::SeqCst) {
+            x86_64::instructions::interrupts::int3();
+        }
+    }
+
+    let before = breakpoint_count();
+
+    set_breakpoint_hook(Some(nested_breakpoint));
+    x86_64::instructions::interrupts::int3();
+    set_breakpoint_hook(None);
+
+    assert_eq!(breakpoint_count(), before + 2);
+    assert!(breakpoint_max_depth() >= 2);
+}
+
+#[test_case]
+fn test_breakpoint_while_writer_locked() {
+    use crate::std::vga_buffer::kernel_writer;
+
+    let before = breakpoint_count();
+
+    // hold the writer as if the breakpoint was hit inside of the print path,
+    // with interrupts disabled so the timer handler does not wait on it.
+    x86_64::instructions::interrupts::without_interrupts(|| {
+        let _writer = kernel_writer().lock();
+        x86_64::instructions::interrupts::int3();
+    });
+
+    assert_eq!(breakpoint_count(), before + 1);
+}
+
+#[test_case]
+fn test_pause_and_resume_timer() {
+    let busy_wait = |cycles: u64| {
+        let deadline = unsafe { core::arch::x86_64::_rdtsc() } + cycles;
+        while unsafe { core::arch::x86_64::_rdtsc() } < deadline {}
+    };
+
+    pause_timer();
+    pause_timer();
+
+    let paused_at = ticks();
+    busy_wait(200_000_000);
+    assert_eq!(ticks(), paused_at);
+
+    // the inner resume must not restart the timer.
+    resume_timer();
+    busy_wait(200_000_000);
+    assert_eq!(ticks(), paused_at);
+
+    resume_timer();
+    while ticks() == paused_at {
+        x86_64::instructions::hlt();
+    }
+
+    assert!(ticks() > paused_at);
+}
+
+#[test_case]
+fn test_timer_handler_benchmark() {
+    const SAMPLES: u64 = 5;
+
+    set_handler_benchmark(true);
+
+    let start = ticks();
+    while ticks() < start + SAMPLES + 1 {
+        x86_64::instructions::hlt();
+    }
+
+    set_handler_benchmark(false);
+    print_handler_benchmark();
+
+    let cycles = handler_benchmark(BenchmarkedHandler::Timer).expect("no timer samples");
+    assert!(cycles.samples >= SAMPLES);
+    assert!(cycles.min <= cycles.avg && cycles.avg <= cycles.max);
+
+    // a handler that takes no time or more than a whole tick has been measured wrongly.
+    assert!(cycles.avg > 10);
+    assert!(cycles.avg < 100_000_000);
+}
+
+#[test_case]
+fn test_pics_cover_both_offsets() {
+    use x86_64::instructions::interrupts;
+
+    // the secondary PIC must handle its own eight vectors (IRQs 8-15), directly
+    // following the eight of the primary PIC.
+    interrupts::without_interrupts(|| {
+        let pics = PICS.lock();
+
+        assert!(pics.handles_interrupt(PIC_1_OFFSET));
+        assert!(pics.handles_interrupt(PIC_1_OFFSET + 7));
+        assert!(pics.handles_interrupt(PIC_2_OFFSET));
+        assert!(pics.handles_interrupt(PIC_2_OFFSET + 7));
+        assert!(!pics.handles_interrupt(PIC_2_OFFSET + 8));
+        assert!(!pics.handles_interrupt(PIC_1_OFFSET - 1));
+    });
+}
+
+#[test_case]
+fn test_chained_pics_handles_interrupt() {
+    // constructing the PICs does not touch the hardware.
+    let pics = unsafe { ChainedPics::new(32, 40) };
+
+    assert!(pics.handles_interrupt(33));
+    assert!(pics.handles_interrupt(41));
+    assert!(!pics.handles_interrupt(5));
+}
+
+#[test_case]
+fn test_ticks_increase() {
+    let start = ticks();
+
+    // busy wait rather than halt, the counter must move on its own.
+    while ticks() == start {
+        core::hint::spin_loop();
+    }
+
+    assert!(ticks() > start);
+}
+
+#[test_case]
+fn test_sleep_ticks() {
+    let start = ticks();
+    sleep_ticks(3);
+
+    assert!(ticks() - start >= 3);
+}
+
+#[test_case]
+fn test_sleep_ticks_reads_the_clock() {
+    use crate::std::clock::{self, FakeClock};
+
+    static FAKE: FakeClock = FakeClock::new();
+
+    // the fake clock is only advanced by the wait, no timer interrupt is needed.
+    x86_64::instructions::interrupts::without_interrupts(|| {
+        clock::set_clock(&FAKE);
+
+        let mut waits = 0;
+        sleep_ticks_with(5, || {
+            waits += 1;
+            FAKE.advance(1);
+        });
+
+        clock::set_clock(&clock::PIT_CLOCK);
+        assert_eq!(waits, 5);
+    });
+}
+
+#[test_case]
+fn test_selector_error_code() {
+    let selector = SelectorErrorCode::new(0x10);
+    assert_eq!(selector.table, SelectorTable::Gdt);
+    assert_eq!(selector.index, 2);
+    assert!(!selector.external);
+
+    // the IDT entry of the timer interrupt, while delivering it.
+    let selector = SelectorErrorCode::new((32 << 3) | 0b011);
+    assert_eq!(selector.table, SelectorTable::Idt);
+    assert_eq!(selector.index, 32);
+    assert!(selector.external);
+
+    let selector = SelectorErrorCode::new((5 << 3) | 0b100);
+    assert_eq!(selector.table, SelectorTable::Ldt);
+}
+
+#[test_case]
+fn test_handle_spurious_irq() {
+    // PICs with a fixed in-service register, recording the end of interrupt
+    // commands sent to them.
+    struct FakePics {
+        isr: (u8, u8),
+        ended: Option<u8>,
+        master_ended: bool,
+    }
+
+    impl PicIsr for FakePics {
+        fn read_isr(&mut self) -> (u8, u8) {
+            self.isr
+        }
+
+        fn end_of_interrupt(&mut self, vector: u8) {
+            self.ended = Some(vector);
+        }
+
+        fn end_of_master_interrupt(&mut self) {
+            self.master_ended = true;
+        }
+    }
+
+    let fake = |isr| FakePics {
+        isr,
+        ended: None,
+        master_ended: false,
+    };
+    let spurious = spurious_interrupts();
+
+    let mut pics = fake((1 << 7, 0));
+    assert!(handle_spurious_irq(&mut pics, SPURIOUS_MASTER_IRQ));
+    assert_eq!(pics.ended, Some(PIC_1_OFFSET + 7));
+
+    let mut pics = fake((0, 1 << 7));
+    assert!(handle_spurious_irq(&mut pics, SPURIOUS_SLAVE_IRQ));
+    assert_eq!(pics.ended, Some(PIC_2_OFFSET + 7));
+
+    // a spurious interrupt is not ended, except on the master for the slave.
+    let mut pics = fake((0, 0));
+    assert!(!handle_spurious_irq(&mut pics, SPURIOUS_MASTER_IRQ));
+    assert_eq!((pics.ended, pics.master_ended), (None, false));
+
+    let mut pics = fake((1 << CASCADE_IRQ, 0));
+    assert!(!handle_spurious_irq(&mut pics, SPURIOUS_SLAVE_IRQ));
+    assert_eq!((pics.ended, pics.master_ended), (None, true));
+
+    assert_eq!(spurious_interrupts(), spurious + 2);
+}
+
+#[test_case]
+fn test_pic_mask() {
+    use x86_64::instructions::interrupts;
+
+    // the keyboard IRQ, masked only for the duration of the test.
+    const KEYBOARD_IRQ: u8 = 1;
+
+    interrupts::without_interrupts(|| {
+        let mut pics = PICS.lock();
+
+        pics.mask(KEYBOARD_IRQ);
+        assert_ne!(pics.read_masks().0 & (1 << KEYBOARD_IRQ), 0);
+
+        pics.unmask(KEYBOARD_IRQ);
+        assert_eq!(pics.read_masks().0 & (1 << KEYBOARD_IRQ), 0);
+    });
+}
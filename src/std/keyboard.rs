@@ -0,0 +1,543 @@
+use pc_keyboard::{layouts, DecodedKey, Error, HandleControl, KeyCode, Keyboard, ScancodeSet1};
+use spin::Mutex;
+
+use crate::std::once::Once;
+use crate::std::queue::ByteQueue;
+
+/// A physical key on the keyboard, independent of the active layout and of whether the key
+/// produces a character.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Key {
+    A, B, C, D, E, F, G, H, I, J, K, L, M,
+    N, O, P, Q, R, S, T, U, V, W, X, Y, Z,
+    Digit0, Digit1, Digit2, Digit3, Digit4,
+    Digit5, Digit6, Digit7, Digit8, Digit9,
+    F1, F2, F3, F4, F5, F6, F7, F8, F9, F10, F11, F12,
+    ArrowUp, ArrowDown, ArrowLeft, ArrowRight,
+    ShiftLeft, ShiftRight, ControlLeft, ControlRight, AltLeft, AltRight, CapsLock,
+    Escape, Enter, Backspace, Tab, Space,
+    Insert, Delete, Home, End, PageUp, PageDown,
+    // Any key that does not have a variant of its own.
+    Other,
+}
+
+/// If the key was pressed or released.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyState {
+    Down,
+    Up,
+}
+
+/// The modifier keys that were held at the time of a key event, and whether caps lock was on.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Modifiers {
+    pub shift: bool,
+    pub control: bool,
+    pub alt: bool,
+    // Toggled by every press of the caps lock key instead of following it.
+    pub caps_lock: bool,
+}
+
+/// A single decoded key press or release.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyEvent {
+    pub key: Key,
+    pub state: KeyState,
+    pub modifiers: Modifiers,
+    // The character or raw key produced by the active layout, only set for
+    // key presses that produce one.
+    pub decoded: Option<DecodedKey>,
+}
+
+/// The keyboard layouts scan codes can be decoded with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyboardLayout {
+    // The US 104 key layout, used unless another layout is set.
+    Us104,
+    // The UK 105 key layout.
+    Uk105,
+    // The French AZERTY layout.
+    Azerty,
+    // The Dvorak layout on a 104 key keyboard.
+    Dvorak,
+}
+
+// Every layout is a distinct type in pc_keyboard, so the keyboard state
+// machine is wrapped in an enum with a variant per supported layout.
+enum LayoutKeyboard {
+    Us104(Keyboard<layouts::Us104Key, ScancodeSet1>),
+    Uk105(Keyboard<layouts::Uk105Key, ScancodeSet1>),
+    Azerty(Keyboard<layouts::Azerty, ScancodeSet1>),
+    Dvorak(Keyboard<layouts::Dvorak104Key, ScancodeSet1>),
+}
+
+impl LayoutKeyboard {
+    fn new(layout: KeyboardLayout) -> LayoutKeyboard {
+        let control = HandleControl::Ignore;
+
+        match layout {
+            KeyboardLayout::Us104 => {
+                LayoutKeyboard::Us104(Keyboard::new(layouts::Us104Key, ScancodeSet1, control))
+            }
+            KeyboardLayout::Uk105 => {
+                LayoutKeyboard::Uk105(Keyboard::new(layouts::Uk105Key, ScancodeSet1, control))
+            }
+            KeyboardLayout::Azerty => {
+                LayoutKeyboard::Azerty(Keyboard::new(layouts::Azerty, ScancodeSet1, control))
+            }
+            KeyboardLayout::Dvorak => {
+                LayoutKeyboard::Dvorak(Keyboard::new(layouts::Dvorak104Key, ScancodeSet1, control))
+            }
+        }
+    }
+
+    fn add_byte(&mut self, scan_code: u8) -> Result<Option<pc_keyboard::KeyEvent>, Error> {
+        match self {
+            LayoutKeyboard::Us104(keyboard) => keyboard.add_byte(scan_code),
+            LayoutKeyboard::Uk105(keyboard) => keyboard.add_byte(scan_code),
+            LayoutKeyboard::Azerty(keyboard) => keyboard.add_byte(scan_code),
+            LayoutKeyboard::Dvorak(keyboard) => keyboard.add_byte(scan_code),
+        }
+    }
+
+    fn process_keyevent(&mut self, event: pc_keyboard::KeyEvent) -> Option<DecodedKey> {
+        match self {
+            LayoutKeyboard::Us104(keyboard) => keyboard.process_keyevent(event),
+            LayoutKeyboard::Uk105(keyboard) => keyboard.process_keyevent(event),
+            LayoutKeyboard::Azerty(keyboard) => keyboard.process_keyevent(event),
+            LayoutKeyboard::Dvorak(keyboard) => keyboard.process_keyevent(event),
+        }
+    }
+}
+
+/// Decodes raw scan codes into key events, keeping track of the held modifier keys.
+pub struct KeyEventDecoder {
+    keyboard: LayoutKeyboard,
+    modifiers: Modifiers,
+}
+
+impl KeyEventDecoder {
+    pub fn new() -> KeyEventDecoder {
+        KeyEventDecoder::with_layout(KeyboardLayout::Us104)
+    }
+
+    /// Creates a decoder that decodes scan codes with the given layout.
+    pub fn with_layout(layout: KeyboardLayout) -> KeyEventDecoder {
+        KeyEventDecoder {
+            keyboard: LayoutKeyboard::new(layout),
+            modifiers: Modifiers::default(),
+        }
+    }
+
+    /// Adds a single scan code byte, returning a key event once a complete scan code sequence
+    /// has been received. Extended keys (such as the arrows) are made up of multiple bytes.
+    ///
+    /// # Arguments
+    ///
+    /// `scan_code` The byte read from the PS/2 data port.
+    pub fn add_byte(&mut self, scan_code: u8) -> Option<KeyEvent> {
+        let event = match self.keyboard.add_byte(scan_code) {
+            Ok(Some(event)) => event,
+            _ => return None,
+        };
+
+        let key = Key::from(event.code);
+        let decoded = self.keyboard.process_keyevent(event.clone());
+        let state = match event.state {
+            pc_keyboard::KeyState::Down => KeyState::Down,
+            pc_keyboard::KeyState::Up => KeyState::Up,
+        };
+
+        let held = state == KeyState::Down;
+        match key {
+            Key::ShiftLeft | Key::ShiftRight => self.modifiers.shift = held,
+            Key::ControlLeft | Key::ControlRight => self.modifiers.control = held,
+            Key::AltLeft | Key::AltRight => self.modifiers.alt = held,
+            Key::CapsLock if held => self.modifiers.caps_lock = !self.modifiers.caps_lock,
+            _ => {}
+        }
+
+        Some(KeyEvent {
+            key,
+            state,
+            modifiers: self.modifiers,
+            decoded,
+        })
+    }
+}
+
+impl From<KeyCode> for Key {
+    fn from(code: KeyCode) -> Key {
+        match code {
+            KeyCode::A => Key::A,
+            KeyCode::B => Key::B,
+            KeyCode::C => Key::C,
+            KeyCode::D => Key::D,
+            KeyCode::E => Key::E,
+            KeyCode::F => Key::F,
+            KeyCode::G => Key::G,
+            KeyCode::H => Key::H,
+            KeyCode::I => Key::I,
+            KeyCode::J => Key::J,
+            KeyCode::K => Key::K,
+            KeyCode::L => Key::L,
+            KeyCode::M => Key::M,
+            KeyCode::N => Key::N,
+            KeyCode::O => Key::O,
+            KeyCode::P => Key::P,
+            KeyCode::Q => Key::Q,
+            KeyCode::R => Key::R,
+            KeyCode::S => Key::S,
+            KeyCode::T => Key::T,
+            KeyCode::U => Key::U,
+            KeyCode::V => Key::V,
+            KeyCode::W => Key::W,
+            KeyCode::X => Key::X,
+            KeyCode::Y => Key::Y,
+            KeyCode::Z => Key::Z,
+            KeyCode::Key0 => Key::Digit0,
+            KeyCode::Key1 => Key::Digit1,
+            KeyCode::Key2 => Key::Digit2,
+            KeyCode::Key3 => Key::Digit3,
+            KeyCode::Key4 => Key::Digit4,
+            KeyCode::Key5 => Key::Digit5,
+            KeyCode::Key6 => Key::Digit6,
+            KeyCode::Key7 => Key::Digit7,
+            KeyCode::Key8 => Key::Digit8,
+            KeyCode::Key9 => Key::Digit9,
+            KeyCode::F1 => Key::F1,
+            KeyCode::F2 => Key::F2,
+            KeyCode::F3 => Key::F3,
+            KeyCode::F4 => Key::F4,
+            KeyCode::F5 => Key::F5,
+            KeyCode::F6 => Key::F6,
+            KeyCode::F7 => Key::F7,
+            KeyCode::F8 => Key::F8,
+            KeyCode::F9 => Key::F9,
+            KeyCode::F10 => Key::F10,
+            KeyCode::F11 => Key::F11,
+            KeyCode::F12 => Key::F12,
+            KeyCode::ArrowUp => Key::ArrowUp,
+            KeyCode::ArrowDown => Key::ArrowDown,
+            KeyCode::ArrowLeft => Key::ArrowLeft,
+            KeyCode::ArrowRight => Key::ArrowRight,
+            KeyCode::ShiftLeft => Key::ShiftLeft,
+            KeyCode::ShiftRight => Key::ShiftRight,
+            KeyCode::ControlLeft => Key::ControlLeft,
+            KeyCode::ControlRight => Key::ControlRight,
+            KeyCode::AltLeft => Key::AltLeft,
+            KeyCode::AltRight => Key::AltRight,
+            KeyCode::CapsLock => Key::CapsLock,
+            KeyCode::Escape => Key::Escape,
+            KeyCode::Enter => Key::Enter,
+            KeyCode::Backspace => Key::Backspace,
+            KeyCode::Tab => Key::Tab,
+            KeyCode::Spacebar => Key::Space,
+            KeyCode::Insert => Key::Insert,
+            KeyCode::Delete => Key::Delete,
+            KeyCode::Home => Key::Home,
+            KeyCode::End => Key::End,
+            KeyCode::PageUp => Key::PageUp,
+            KeyCode::PageDown => Key::PageDown,
+            _ => Key::Other,
+        }
+    }
+}
+
+/// The number of key events that can be waiting before new events are dropped.
+const EVENT_QUEUE_SIZE: usize = 32;
+
+/// A fixed size first in first out queue of key events.
+struct EventQueue {
+    events: [Option<KeyEvent>; EVENT_QUEUE_SIZE],
+    head: usize,
+    len: usize,
+}
+
+impl EventQueue {
+    const fn new() -> EventQueue {
+        EventQueue {
+            events: [None; EVENT_QUEUE_SIZE],
+            head: 0,
+            len: 0,
+        }
+    }
+
+    /// Adds the event to the back of the queue, returning false if the queue is full.
+    fn push(&mut self, event: KeyEvent) -> bool {
+        if self.len == EVENT_QUEUE_SIZE {
+            return false;
+        }
+
+        self.events[(self.head + self.len) % EVENT_QUEUE_SIZE] = Some(event);
+        self.len += 1;
+        true
+    }
+
+    fn pop(&mut self) -> Option<KeyEvent> {
+        if self.len == 0 {
+            return None;
+        }
+
+        let event = self.events[self.head].take();
+        self.head = (self.head + 1) % EVENT_QUEUE_SIZE;
+        self.len -= 1;
+        event
+    }
+}
+
+/// The number of raw scan codes that can be waiting to be decoded before new ones are dropped.
+const SCAN_CODE_QUEUE_SIZE: usize = 64;
+
+// Raw scan codes pushed by the keyboard interrupt handler, popped by the
+// decoder while it holds the `DECODER` lock.
+static SCAN_CODES: ByteQueue<SCAN_CODE_QUEUE_SIZE> = ByteQueue::new();
+
+static DECODER: Once<Mutex<KeyEventDecoder>> = Once::new();
+
+static EVENTS: Mutex<EventQueue> = Mutex::new(EventQueue::new());
+
+/// Queues a raw scan code to be decoded outside of the interrupt, called from the keyboard
+/// interrupt handler. Scan codes are dropped when the queue is full.
+pub fn push_scan_code(scan_code: u8) {
+    if SCAN_CODES.push(scan_code) {
+        crate::std::task::keyboard::wake();
+    }
+}
+
+/// Returns the oldest queued scan code without decoding it, used by `task::keyboard` to decode
+/// scan codes in async code instead.
+pub fn pop_scan_code() -> Option<u8> {
+    // the decoder lock makes this the only consumer of the queue.
+    let _decoder = decoder().lock();
+    SCAN_CODES.pop()
+}
+
+fn decoder() -> &'static Mutex<KeyEventDecoder> {
+    DECODER.call_once(|| Mutex::new(KeyEventDecoder::new()))
+}
+
+/// Decodes every queued scan code and queues the resulting key events. Events are dropped when
+/// the queue is full, console hotkeys are handled here and never queued.
+fn decode_pending() {
+    let mut decoder = decoder().lock();
+
+    while let Some(scan_code) = SCAN_CODES.pop() {
+        if let Some(event) = decoder.add_byte(scan_code) {
+            if crate::std::console::handle_hotkey(&event) {
+                continue;
+            }
+
+            EVENTS.lock().push(event);
+        }
+    }
+}
+
+/// Changes the layout used to decode scan codes. Scan codes that are still queued are decoded
+/// with the new layout, and modifier keys held at the time of the switch are released.
+///
+/// # Arguments
+///
+/// `layout` The layout of the attached keyboard.
+///
+/// # Example
+///
+/// ```
+/// keyboard::set_layout(KeyboardLayout::Azerty);
+/// ```
+pub fn set_layout(layout: KeyboardLayout) {
+    *decoder().lock() = KeyEventDecoder::with_layout(layout);
+}
+
+/// Returns the modifier keys that are currently held and whether caps lock is on, taking every
+/// scan code received so far into account.
+///
+/// # Example
+///
+/// ```
+/// if keyboard::modifiers().control {
+///     println!("control is held");
+/// }
+/// ```
+pub fn modifiers() -> Modifiers {
+    decode_pending();
+    decoder().lock().modifiers
+}
+
+/// Returns the oldest key event that has not been read yet.
+pub fn next_event() -> Option<KeyEvent> {
+    decode_pending();
+    EVENTS.lock().pop()
+}
+
+/// Returns the next character (or raw key without a character) typed on the keyboard, skipping
+/// key releases and presses of keys that produce neither, such as the modifiers. Reads the same
+/// events as `next_event`.
+///
+/// # Example
+///
+/// ```
+/// while let Some(key) = keyboard::poll_keyboard() {
+///     match key {
+///         DecodedKey::Unicode(character) => print!("{}", character),
+///         DecodedKey::RawKey(key) => print!("{:?}", key),
+///     }
+/// }
+/// ```
+pub fn poll_keyboard() -> Option<DecodedKey> {
+    while let Some(event) = next_event() {
+        if event.decoded.is_some() {
+            return event.decoded;
+        }
+    }
+
+    None
+}
+
+/// Reads a line typed on the keyboard into the buffer, halting until Enter is pressed, and
+/// returns the number of bytes read. Printable ascii characters are echoed to the screen and
+/// stored while the buffer has room, backspace removes the last stored character, and any
+/// other key is ignored. The line ending is not stored.
+///
+/// # Arguments
+///
+/// `buf` The buffer the line is read into, characters beyond its length are dropped.
+///
+/// # Example
+///
+/// ```
+/// let mut line = [0u8; 80];
+/// let len = keyboard::read_line(&mut line);
+/// shell::execute(core::str::from_utf8(&line[..len]).unwrap());
+/// ```
+pub fn read_line(buf: &mut [u8]) -> usize {
+    let mut len = 0;
+
+    loop {
+        let character = match poll_keyboard() {
+            Some(DecodedKey::Unicode(character)) => character,
+            Some(DecodedKey::RawKey(_)) => continue,
+            // wait for the next key press, the keyboard interrupt wakes the CPU.
+            None => {
+                x86_64::instructions::hlt();
+                continue;
+            }
+        };
+
+        match character {
+            '\n' => break,
+            '\u{8}' if len > 0 => {
+                len -= 1;
+                crate::print!("\u{8}");
+            }
+            ' '..='~' if len < buf.len() => {
+                buf[len] = character as u8;
+                len += 1;
+                crate::print!("{}", character);
+            }
+            _ => {}
+        }
+    }
+
+    crate::println!();
+    len
+}
+
+// Tests
+
+#[test_case]
+fn test_decode_arrow_and_function_keys() {
+    let mut decoder = KeyEventDecoder::new();
+
+    // arrow up is an extended key, the first byte does not produce an event.
+    assert_eq!(decoder.add_byte(0xE0), None);
+    let event = decoder.add_byte(0x48).expect("arrow up pressed");
+    assert_eq!((event.key, event.state), (Key::ArrowUp, KeyState::Down));
+
+    assert_eq!(decoder.add_byte(0xE0), None);
+    let event = decoder.add_byte(0xC8).expect("arrow up released");
+    assert_eq!((event.key, event.state), (Key::ArrowUp, KeyState::Up));
+
+    let event = decoder.add_byte(0x3B).expect("F1 pressed");
+    assert_eq!((event.key, event.state), (Key::F1, KeyState::Down));
+
+    let event = decoder.add_byte(0xBB).expect("F1 released");
+    assert_eq!((event.key, event.state), (Key::F1, KeyState::Up));
+}
+
+#[test_case]
+fn test_decode_tracks_modifiers() {
+    let mut decoder = KeyEventDecoder::new();
+
+    // left shift pressed, then F5 while it is held.
+    decoder.add_byte(0x2A).expect("shift pressed");
+    let event = decoder.add_byte(0x3F).expect("F5 pressed");
+    assert_eq!(event.key, Key::F5);
+    assert!(event.modifiers.shift);
+
+    decoder.add_byte(0xAA).expect("shift released");
+    let event = decoder.add_byte(0xBF).expect("F5 released");
+    assert!(!event.modifiers.shift);
+}
+
+#[test_case]
+fn test_poll_keyboard_decodes_queued_scan_codes() {
+    // 'a' pressed and released, left shift held while 'b' is pressed.
+    for &scan_code in &[0x1E, 0x9E, 0x2A, 0x30, 0xB0, 0xAA] {
+        push_scan_code(scan_code);
+    }
+
+    assert_eq!(poll_keyboard(), Some(DecodedKey::Unicode('a')));
+    assert_eq!(poll_keyboard(), Some(DecodedKey::Unicode('B')));
+    assert_eq!(poll_keyboard(), None);
+}
+
+#[test_case]
+fn test_read_line() {
+    // "hx", backspace, "i" and enter, each pressed and released.
+    for &scan_code in &[0x23, 0xA3, 0x2D, 0xAD, 0x0E, 0x8E, 0x17, 0x97, 0x1C, 0x9C] {
+        push_scan_code(scan_code);
+    }
+
+    let mut line = [0u8; 16];
+    let len = read_line(&mut line);
+
+    assert_eq!(len, 2);
+    assert_eq!(&line[..len], b"hi");
+}
+
+#[test_case]
+fn test_set_layout() {
+    // the key left of W types 'q' on a US keyboard and 'a' on an AZERTY one.
+    set_layout(KeyboardLayout::Azerty);
+    push_scan_code(0x10);
+    push_scan_code(0x90);
+    assert_eq!(poll_keyboard(), Some(DecodedKey::Unicode('a')));
+
+    set_layout(KeyboardLayout::Us104);
+    push_scan_code(0x10);
+    push_scan_code(0x90);
+    assert_eq!(poll_keyboard(), Some(DecodedKey::Unicode('q')));
+}
+
+#[test_case]
+fn test_modifiers_follow_key_state() {
+    // left shift pressed and released.
+    push_scan_code(0x2A);
+    assert!(modifiers().shift);
+
+    push_scan_code(0xAA);
+    assert!(!modifiers().shift);
+
+    // caps lock toggles on each press, releasing it changes nothing.
+    let caps_lock = modifiers().caps_lock;
+    push_scan_code(0x3A);
+    push_scan_code(0xBA);
+    assert_eq!(modifiers().caps_lock, !caps_lock);
+
+    push_scan_code(0x3A);
+    push_scan_code(0xBA);
+    assert_eq!(modifiers().caps_lock, caps_lock);
+
+    while next_event().is_some() {}
+}
@@ -0,0 +1,169 @@
+use core::fmt::{self, Write};
+
+use crate::std::panic::lock_for_print;
+use crate::std::serial::serial1;
+use crate::std::sync::TryMutex;
+
+/// The number of bytes of output kept by the kernel log, once full the oldest output is
+/// overwritten.
+pub const KLOG_SIZE: usize = 8 * 1024;
+
+// The character written in place of bytes that are not valid UTF-8, e.g. a
+// character cut in half by the oldest output being overwritten.
+const REPLACEMENT_CHARACTER: char = '\u{FFFD}';
+
+// A ring buffer of the most recent output, oldest first.
+struct Ring {
+    bytes: [u8; KLOG_SIZE],
+    start: usize,
+    len: usize,
+}
+
+impl Ring {
+    const fn new() -> Ring {
+        Ring {
+            bytes: [0; KLOG_SIZE],
+            start: 0,
+            len: 0,
+        }
+    }
+
+    // The contents in order, split in two where the ring wraps around.
+    fn as_slices(&self) -> (&[u8], &[u8]) {
+        if self.start + self.len <= KLOG_SIZE {
+            (&self.bytes[self.start..self.start + self.len], &[])
+        } else {
+            let end = (self.start + self.len) % KLOG_SIZE;
+            (&self.bytes[self.start..], &self.bytes[..end])
+        }
+    }
+}
+
+impl fmt::Write for Ring {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for &byte in s.as_bytes() {
+            let end = (self.start + self.len) % KLOG_SIZE;
+            self.bytes[end] = byte;
+
+            if self.len < KLOG_SIZE {
+                self.len += 1;
+            } else {
+                self.start = (self.start + 1) % KLOG_SIZE;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+// Locked by the print functions, which run with interrupts disabled.
+static KLOG: TryMutex<Ring> = TryMutex::new("KLOG", Ring::new());
+
+/// Appends the formatted output to the kernel log, called by `print!` and the other screen
+/// output macros so that the recent output can be recovered after a panic.
+#[doc(hidden)]
+pub fn _append(args: fmt::Arguments) {
+    use x86_64::instructions::interrupts;
+
+    interrupts::without_interrupts(|| {
+        let _ = lock_for_print(&KLOG).write_fmt(args);
+    })
+}
+
+/// Writes the contents of the kernel log to the output, oldest first. Once the log has wrapped
+/// around the oldest line is incomplete.
+///
+/// # Arguments
+///
+/// `out` The output the log is written to.
+///
+/// # Example
+///
+/// ```
+/// klog::dump_klog_to(&mut *serial1().lock()).unwrap();
+/// ```
+pub fn dump_klog_to(out: &mut impl fmt::Write) -> fmt::Result {
+    use x86_64::instructions::interrupts;
+
+    interrupts::without_interrupts(|| {
+        let ring = lock_for_print(&KLOG);
+        let (first, second) = ring.as_slices();
+
+        write_lossy(out, first)?;
+        write_lossy(out, second)
+    })
+}
+
+/// Writes the contents of the kernel log to the first serial port, called by the panic handler
+/// so that the recent output is available even when the screen is garbled or has scrolled.
+pub fn dump_klog() {
+    use x86_64::instructions::interrupts;
+
+    interrupts::without_interrupts(|| {
+        let mut serial = lock_for_print(serial1());
+
+        let _ = writeln!(serial, "----- KLOG -----");
+        let _ = dump_klog_to(&mut *serial);
+        let _ = writeln!(serial, "----- END KLOG -----");
+    })
+}
+
+// Writes the bytes as UTF-8, replacing any invalid sequences.
+fn write_lossy(out: &mut impl fmt::Write, mut bytes: &[u8]) -> fmt::Result {
+    while !bytes.is_empty() {
+        match core::str::from_utf8(bytes) {
+            Ok(s) => return out.write_str(s),
+            Err(error) => {
+                let (valid, rest) = bytes.split_at(error.valid_up_to());
+                out.write_str(unsafe { core::str::from_utf8_unchecked(valid) })?;
+                out.write_char(REPLACEMENT_CHARACTER)?;
+
+                // an incomplete sequence at the end has no error length.
+                bytes = &rest[error.error_len().unwrap_or(rest.len())..];
+            }
+        }
+    }
+
+    Ok(())
+}
+
+// Tests
+
+// `dump_klog` writes the same output to the serial port.
+#[test_case]
+fn test_dump_klog_contains_lines_in_order() {
+    use crate::println;
+
+    let lines = ["klog line one", "klog line two", "klog line three"];
+    for line in lines.iter() {
+        println!("{}", line);
+    }
+
+    let mut capture: crate::TestOutput<KLOG_SIZE> = crate::TestOutput::new();
+    dump_klog_to(&mut capture).unwrap();
+
+    let captured = capture.as_bytes();
+    let mut position = 0;
+    for line in lines.iter() {
+        let found = captured[position..]
+            .windows(line.len())
+            .position(|window| window == line.as_bytes());
+
+        assert!(found.is_some(), "{} missing from the klog", line);
+        position += found.unwrap() + line.len();
+    }
+}
+
+#[test_case]
+fn test_ring_overwrites_oldest() {
+    let mut ring = Ring::new();
+    for _ in 0..KLOG_SIZE {
+        ring.write_str("a").unwrap();
+    }
+    ring.write_str("bc").unwrap();
+
+    let (first, second) = ring.as_slices();
+    assert_eq!(first.len() + second.len(), KLOG_SIZE);
+    assert_eq!(first[0], b'a');
+    assert_eq!(second, b"bc");
+}
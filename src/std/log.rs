@@ -0,0 +1,174 @@
+use core::fmt;
+use core::sync::atomic::{AtomicU8, Ordering};
+
+use crate::std::vga_buffer::{self, Color};
+
+/// The severity of a log message, from the most to the least severe.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[repr(u8)]
+pub enum Level {
+    Error = 1,
+    Warn = 2,
+    Info = 3,
+    Debug = 4,
+    Trace = 5,
+}
+
+impl Level {
+    /// The label written in front of each message of the level.
+    pub fn name(self) -> &'static str {
+        match self {
+            Level::Error => "ERROR",
+            Level::Warn => "WARN",
+            Level::Info => "INFO",
+            Level::Debug => "DEBUG",
+            Level::Trace => "TRACE",
+        }
+    }
+
+    /// The color messages of the level are written in.
+    pub fn color(self) -> Color {
+        match self {
+            Level::Error => Color::Red,
+            Level::Warn => Color::Yellow,
+            Level::Info => Color::White,
+            Level::Debug => Color::LightGray,
+            Level::Trace => Color::DarkGray,
+        }
+    }
+
+    /// Returns the level with the given name, ignoring case, e.g. "debug" from a `loglevel=debug`
+    /// boot argument.
+    pub fn from_name(name: &str) -> Option<Level> {
+        [
+            Level::Error,
+            Level::Warn,
+            Level::Info,
+            Level::Debug,
+            Level::Trace,
+        ]
+        .iter()
+        .copied()
+        .find(|level| level.name().eq_ignore_ascii_case(name))
+    }
+
+    fn from_u8(value: u8) -> Level {
+        match value {
+            1 => Level::Error,
+            2 => Level::Warn,
+            3 => Level::Info,
+            4 => Level::Debug,
+            _ => Level::Trace,
+        }
+    }
+}
+
+// The least severe level that is still written, messages of any less severe
+// level are dropped.
+static MAX_LEVEL: AtomicU8 = AtomicU8::new(Level::Info as u8);
+
+/// Sets the least severe level that is written, messages of less severe levels are dropped.
+///
+/// # Arguments
+///
+/// `level` The least severe level still written.
+///
+/// # Example
+///
+/// ```
+/// // only errors and warnings from now on.
+/// log::set_max_level(Level::Warn);
+/// ```
+pub fn set_max_level(level: Level) {
+    MAX_LEVEL.store(level as u8, Ordering::Relaxed);
+}
+
+/// Returns the least severe level that is written.
+pub fn max_level() -> Level {
+    Level::from_u8(MAX_LEVEL.load(Ordering::Relaxed))
+}
+
+/// Returns true if messages of the given level are written.
+pub fn enabled(level: Level) -> bool {
+    level <= max_level()
+}
+
+/// Writes a message of the given level to the screen in the color of the level, prefixed with
+/// the level name, unless the level is filtered out.
+#[macro_export]
+macro_rules! log {
+    ($level:expr, $($arg:tt)*) => {
+        $crate::std::log::_log($level, format_args!($($arg)*))
+    };
+}
+
+/// Writes an error message, see `log!`.
+#[macro_export]
+macro_rules! log_error {
+    ($($arg:tt)*) => ($crate::log!($crate::std::log::Level::Error, $($arg)*));
+}
+
+/// Writes a warning message, see `log!`.
+#[macro_export]
+macro_rules! log_warn {
+    ($($arg:tt)*) => ($crate::log!($crate::std::log::Level::Warn, $($arg)*));
+}
+
+/// Writes an informational message, see `log!`.
+#[macro_export]
+macro_rules! log_info {
+    ($($arg:tt)*) => ($crate::log!($crate::std::log::Level::Info, $($arg)*));
+}
+
+/// Writes a debug message, see `log!`.
+#[macro_export]
+macro_rules! log_debug {
+    ($($arg:tt)*) => ($crate::log!($crate::std::log::Level::Debug, $($arg)*));
+}
+
+/// Writes a trace message, see `log!`.
+#[macro_export]
+macro_rules! log_trace {
+    ($($arg:tt)*) => ($crate::log!($crate::std::log::Level::Trace, $($arg)*));
+}
+
+#[doc(hidden)]
+pub fn _log(level: Level, args: fmt::Arguments) {
+    if !enabled(level) {
+        return;
+    }
+
+    vga_buffer::_cprint(level.color(), format_args!("[{}] {}\n", level.name(), args));
+}
+
+// Tests
+
+#[test_case]
+fn test_level_from_name() {
+    assert_eq!(Level::from_name("debug"), Some(Level::Debug));
+    assert_eq!(Level::from_name("WARN"), Some(Level::Warn));
+    assert_eq!(Level::from_name("verbose"), None);
+}
+
+#[test_case]
+fn test_max_level_filters_messages() {
+    use x86_64::instructions::interrupts;
+
+    let previous = max_level();
+    set_max_level(Level::Warn);
+
+    // nothing else may write to the screen between logging and checking.
+    interrupts::without_interrupts(|| {
+        crate::log_info!("test_max_level_filters_messages info");
+        crate::log_error!("test_max_level_filters_messages error");
+
+        assert!(!vga_buffer::screen_contains(
+            "test_max_level_filters_messages info"
+        ));
+        assert!(vga_buffer::screen_contains(
+            "[ERROR] test_max_level_filters_messages error"
+        ));
+    });
+
+    set_max_level(previous);
+}
@@ -4,8 +4,116 @@ use x86_64::{
 };
 
 use bootloader::bootinfo::{MemoryMap, MemoryRegionType};
+use core::sync::atomic::{AtomicU64, Ordering};
+use x86_64::structures::idt::PageFaultErrorCode;
 
-/// Initialize a new OffsetPageTable.
+use crate::println;
+use crate::std::interrupts::{register_timer_callback, TimerCallbackError};
+
+/// The size of the kernel stack set up by the bootloader, which places an unmapped guard page
+/// directly below it. This matches the bootloader's default `kernel-stack-size` of 80 pages.
+pub const KERNEL_STACK_SIZE: u64 = 80 * 4096;
+
+// The lowest usable address of the kernel stack, zero until `init_stack_bounds`
+// has been called.
+static STACK_LIMIT: AtomicU64 = AtomicU64::new(0);
+
+/// Records the bounds of the current stack for `stack_headroom`, must be called early during
+/// boot while still running close to the top of the kernel stack. The top of the stack is taken
+/// to be the end of the page holding the current stack pointer.
+pub fn init_stack_bounds() {
+    let top = (stack_pointer() + 4095) & !4095;
+    STACK_LIMIT.store(top - KERNEL_STACK_SIZE, Ordering::SeqCst);
+}
+
+/// Returns the number of bytes left between the current stack pointer and the guard page below
+/// the kernel stack, or `usize::MAX` if the stack bounds have not been recorded. Returns zero
+/// when running on another stack below the kernel stack (e.g. an interrupt stack).
+pub fn stack_headroom() -> usize {
+    match STACK_LIMIT.load(Ordering::SeqCst) {
+        0 => usize::MAX,
+        limit => stack_pointer().saturating_sub(limit) as usize,
+    }
+}
+
+/// The headroom below which `watch_stack` prints a warning.
+pub const STACK_HEADROOM_WARNING: usize = 16 * 1024;
+
+/// Registers a timer callback that prints a warning whenever the interrupted code was running
+/// with less than `STACK_HEADROOM_WARNING` bytes of stack left, giving early warning of deep
+/// recursion before it overflows into the guard page.
+///
+/// # Arguments
+///
+/// `period_ticks` The number of timer ticks between each check.
+pub fn watch_stack(period_ticks: u64) -> Result<usize, TimerCallbackError> {
+    fn check_stack() {
+        let headroom = stack_headroom();
+        if headroom < STACK_HEADROOM_WARNING {
+            println!("WARNING: only {} bytes of stack left", headroom);
+        }
+    }
+
+    register_timer_callback("stack watch", period_ticks, check_stack)
+}
+
+fn stack_pointer() -> u64 {
+    let rsp: u64;
+    unsafe { asm!("mov {}, rsp", out(reg) rsp, options(nomem, nostack, preserves_flags)) };
+    rsp
+}
+
+// The offset at which the bootloader mapped the complete physical memory, zero
+// until `set_physical_memory_offset` has been called.
+static PHYSICAL_MEMORY_OFFSET: AtomicU64 = AtomicU64::new(0);
+
+/// Records the offset of the complete physical memory mapping handed over in the boot info, for
+/// use by code that has no access to the boot info (e.g. the shell).
+pub fn set_physical_memory_offset(physical_memory_offset: VirtAddr) {
+    PHYSICAL_MEMORY_OFFSET.store(physical_memory_offset.as_u64(), Ordering::SeqCst);
+}
+
+/// Returns the offset recorded by `set_physical_memory_offset`, if any.
+pub fn physical_memory_offset() -> Option<VirtAddr> {
+    match PHYSICAL_MEMORY_OFFSET.load(Ordering::SeqCst) {
+        0 => None,
+        offset => Some(VirtAddr::new(offset)),
+    }
+}
+
+/// Returns true if every page of the physical range `[phys, phys + len)` is mapped through the
+/// complete physical memory mapping, making it safe to read with `phys_slice`.
+///
+/// This function is unsafe because the caller must guarantee that the complete physical memory
+/// is mapped to virtual memory at the passed `physical_memory_offset`.
+pub unsafe fn is_phys_range_mapped(phys: PhysAddr, len: usize, physical_memory_offset: VirtAddr) -> bool {
+    if len == 0 {
+        return true;
+    }
+
+    let start = physical_memory_offset + phys.as_u64();
+    let end = match start.as_u64().checked_add(len as u64 - 1) {
+        Some(end) => VirtAddr::new(end),
+        None => return false,
+    };
+
+    let mut page = start.align_down(4096u64);
+    while page <= end {
+        if translate_addr(page, physical_memory_offset).is_none() {
+            return false;
+        }
+
+        page += 4096u64;
+    }
+
+    true
+}
+
+/// Initialize a new OffsetPageTable, giving access to the `translate_addr` and `map_to` APIs
+/// of the `x86_64` crate through the `Translate` and `Mapper` traits.
+///
+/// The `physical_memory_offset` must be the one passed by the bootloader in
+/// `BootInfo::physical_memory_offset`.
 ///
 /// This function is unsafe because the caller must guarantee that the
 /// complete physical memory is mapped to virtual memory at the passed
@@ -80,6 +188,139 @@ pub fn create_example_mapping(
     map_to_result.expect("map_to failed").flush();
 }
 
+// The page table and frame allocator used to resolve page faults in the heap
+// growth range, `None` until `init_demand_paging` has been called. Locked by
+// the page fault handler, so only ever locked with interrupts disabled.
+struct DemandPaging {
+    mapper: OffsetPageTable<'static>,
+    frame_allocator: BootInfoFrameAllocator,
+}
+
+static DEMAND_PAGING: spin::Mutex<Option<DemandPaging>> = spin::Mutex::new(None);
+
+// The number of pages mapped by `handle_demand_fault` since boot.
+static DEMAND_MAPPED_PAGES: AtomicU64 = AtomicU64::new(0);
+
+/// Hands the page table and frame allocator over to the page fault handler, after which the
+/// first access to a page of the heap growth range maps it to a fresh zeroed frame instead of
+/// halting. See `allocator::HEAP_GROWTH_START`.
+///
+/// # Arguments
+///
+/// `mapper` The active page table, as returned by `init`.
+/// `frame_allocator` Used to allocate the frames backing the pages and any page tables required.
+///
+/// # Example
+///
+/// ```
+/// allocator::init_heap(&mut mapper, &mut frame_allocator).expect("heap initialization failed");
+/// memory::init_demand_paging(mapper, frame_allocator);
+/// ```
+pub fn init_demand_paging(
+    mapper: OffsetPageTable<'static>,
+    frame_allocator: BootInfoFrameAllocator,
+) {
+    use x86_64::instructions::interrupts;
+
+    interrupts::without_interrupts(|| {
+        *DEMAND_PAGING.lock() = Some(DemandPaging {
+            mapper,
+            frame_allocator,
+        });
+    })
+}
+
+/// Returns the number of pages mapped by the page fault handler since boot.
+pub fn demand_mapped_pages() -> u64 {
+    DEMAND_MAPPED_PAGES.load(Ordering::SeqCst)
+}
+
+/// Resolves a page fault by mapping a fresh zeroed frame, called by the page fault handler.
+/// Returns true if the page was mapped and the faulting instruction can be retried, false if
+/// the fault was not a read or write of a page that is not present in the heap growth range,
+/// or demand paging has not been enabled.
+///
+/// # Arguments
+///
+/// `addr` The accessed address, read from CR2.
+/// `error_code` The error code of the page fault.
+pub fn handle_demand_fault(addr: VirtAddr, error_code: PageFaultErrorCode) -> bool {
+    use crate::std::allocator::{HEAP_GROWTH_SIZE, HEAP_GROWTH_START};
+    use x86_64::structures::paging::PageTableFlags as Flags;
+
+    let growth_start = HEAP_GROWTH_START as u64;
+    let growth_end = growth_start + HEAP_GROWTH_SIZE as u64;
+    if addr.as_u64() < growth_start || addr.as_u64() >= growth_end {
+        return false;
+    }
+
+    // protection violations are faults on present pages, and code is never
+    // executed from the heap.
+    if error_code.intersects(
+        PageFaultErrorCode::PROTECTION_VIOLATION | PageFaultErrorCode::INSTRUCTION_FETCH,
+    ) {
+        return false;
+    }
+
+    // the fault may have interrupted code holding the lock, in which case it
+    // can not be resolved.
+    let mut demand_paging = match DEMAND_PAGING.try_lock() {
+        Some(demand_paging) => demand_paging,
+        None => return false,
+    };
+
+    let demand_paging = match demand_paging.as_mut() {
+        Some(demand_paging) => demand_paging,
+        None => return false,
+    };
+
+    let page: Page<Size4KiB> = Page::containing_address(addr);
+    let frame = match demand_paging.frame_allocator.allocate_frame() {
+        Some(frame) => frame,
+        None => return false,
+    };
+
+    let flags = Flags::PRESENT | Flags::WRITABLE;
+    let result = unsafe {
+        demand_paging
+            .mapper
+            .map_to(page, frame, flags, &mut demand_paging.frame_allocator)
+    };
+
+    match result {
+        Ok(flush) => flush.flush(),
+        Err(_) => return false,
+    }
+
+    // frames are handed out with whatever the last user left in them.
+    unsafe { core::ptr::write_bytes(page.start_address().as_mut_ptr::<u8>(), 0, 4096) };
+
+    DEMAND_MAPPED_PAGES.fetch_add(1, Ordering::SeqCst);
+    true
+}
+
+/// Returns a slice over `len` bytes of physical memory starting at `phys`, accessed through
+/// the complete physical memory mapping at `physical_memory_offset`.
+///
+/// This function is unsafe because the caller must guarantee that the complete physical memory
+/// is mapped at the passed `physical_memory_offset` and that no mutable reference to any part of
+/// the region exists for the lifetime of the returned slice.
+pub unsafe fn phys_slice(phys: PhysAddr, len: usize, physical_memory_offset: VirtAddr) -> &'static [u8] {
+    let virtual_addr = physical_memory_offset + phys.as_u64();
+    core::slice::from_raw_parts(virtual_addr.as_ptr(), len)
+}
+
+/// Returns a mutable slice over `len` bytes of physical memory starting at `phys`, accessed
+/// through the complete physical memory mapping at `physical_memory_offset`.
+///
+/// This function is unsafe because the caller must guarantee that the complete physical memory
+/// is mapped at the passed `physical_memory_offset` and that no other reference to any part of
+/// the region exists for the lifetime of the returned slice (which would be aliasing `&mut`).
+pub unsafe fn phys_slice_mut(phys: PhysAddr, len: usize, physical_memory_offset: VirtAddr) -> &'static mut [u8] {
+    let virtual_addr = physical_memory_offset + phys.as_u64();
+    core::slice::from_raw_parts_mut(virtual_addr.as_mut_ptr(), len)
+}
+
 /// Returns a mutable reference to the active level table
 ///
 /// This function is unsafe because the caller must guarantee that the
@@ -99,7 +340,11 @@ pub unsafe fn active_level_4_table(physical_memory_offset: VirtAddr) -> &'static
 }
 
 /// Translates the given virtual address to the mapped physical address, or
-/// `None` if the address is not mapped.
+/// `None` if the address is not mapped. Addresses within huge pages (such as
+/// the bootloader's mapping of the physical memory) are translated as well.
+///
+/// The `physical_memory_offset` must be the one passed by the bootloader in
+/// `BootInfo::physical_memory_offset`, see also `physical_memory_offset`.
 ///
 /// This function is unsafe because the caller must guarantee that the
 /// complete physical memory is mapped to virtual memory at the passed
@@ -127,8 +372,12 @@ fn translate_addr_inner(addr: VirtAddr, physical_memory_offset: VirtAddr) -> Opt
 
     let mut frame = level_4_table_frame;
 
+    // The size of the memory covered by a single entry at each level, used
+    // to compute the offset into huge pages (1GiB at level 3, 2MiB at level 2).
+    let entry_sizes: [u64; 4] = [1 << 39, 1 << 30, 1 << 21, 1 << 12];
+
     // traverse the multi-level page table
-    for &index in &table_indexes {
+    for (&index, &entry_size) in table_indexes.iter().zip(entry_sizes.iter()) {
 
         // go and convert the frame into a page table reference
         let virtual_address = physical_memory_offset + frame.start_address().as_u64();
@@ -141,10 +390,92 @@ fn translate_addr_inner(addr: VirtAddr, physical_memory_offset: VirtAddr) -> Opt
         frame = match entry.frame() {
             Ok(frame) => frame,
             Err(FrameError::FrameNotPresent) => return None,
-            Err(FrameError::HugeFrame) => panic!("huge page not supported")
+            // the entry maps a huge page directly, the remaining bits of the
+            // address are the offset into that page.
+            Err(FrameError::HugeFrame) => {
+                return Some(entry.addr() + (addr.as_u64() & (entry_size - 1)))
+            }
         }
     }
 
     // calculate the physical address by adding the page offset.
     Some(frame.start_address() + u64::from(addr.page_offset()))
-}
\ No newline at end of file
+}
+
+/// Returns true if the page holding the given virtual address is present and accessible from
+/// user mode, which requires the user accessible flag on the entries of every level.
+///
+/// This function is unsafe because the caller must guarantee that the complete physical memory
+/// is mapped to virtual memory at the passed `physical_memory_offset`.
+pub unsafe fn is_user_accessible(addr: VirtAddr, physical_memory_offset: VirtAddr) -> bool {
+    use x86_64::registers::control::Cr3;
+    use x86_64::structures::paging::PageTableFlags;
+
+    let (level_4_table_frame, _) = Cr3::read();
+
+    let table_indexes = [
+        addr.p4_index(), addr.p3_index(), addr.p2_index(), addr.p1_index()
+    ];
+
+    let mut table_address = level_4_table_frame.start_address();
+
+    for &index in &table_indexes {
+        let table_ptr: *const PageTable = (physical_memory_offset + table_address.as_u64()).as_ptr();
+        let entry = &(*table_ptr)[index];
+
+        if !entry.flags().contains(PageTableFlags::PRESENT | PageTableFlags::USER_ACCESSIBLE) {
+            return false;
+        }
+
+        // a huge page ends the walk early, the entry maps the page itself.
+        if entry.flags().contains(PageTableFlags::HUGE_PAGE) {
+            return true;
+        }
+
+        table_address = entry.addr();
+    }
+
+    true
+}
+
+// Tests
+
+#[test_case]
+fn test_stack_headroom_decreases_with_recursion() {
+    const DEPTH: usize = 8;
+
+    #[inline(never)]
+    fn recurse(depth: usize, headroom: &mut [usize; DEPTH]) {
+        // consume some stack in every frame that cannot be optimized away.
+        let padding = [0u8; 256];
+        volatile::Volatile::new(padding).read();
+
+        headroom[depth] = stack_headroom();
+        if depth + 1 < DEPTH {
+            recurse(depth + 1, headroom);
+        }
+    }
+
+    let mut headroom = [0; DEPTH];
+    recurse(0, &mut headroom);
+
+    assert!(headroom[0] < KERNEL_STACK_SIZE as usize);
+    for pair in headroom.windows(2) {
+        assert!(pair[1] < pair[0]);
+    }
+}
+
+#[test_case]
+fn test_translate_addr() {
+    let offset = physical_memory_offset().expect("offset not recorded");
+
+    unsafe {
+        // the VGA buffer is identity mapped, and mapped again within the
+        // physical memory mapping of the bootloader.
+        let vga = PhysAddr::new(0xb8000);
+        assert_eq!(translate_addr(VirtAddr::new(0xb8000), offset), Some(vga));
+        assert_eq!(translate_addr(offset + 0xb8010u64, offset), Some(vga + 0x10u64));
+
+        assert_eq!(translate_addr(VirtAddr::new(0xdead_beaf_000), offset), None);
+    }
+}
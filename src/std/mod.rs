@@ -1,5 +1,39 @@
+pub mod allocator;
+pub mod ata;
+pub mod boot;
+pub mod checksum;
+pub mod clock;
+pub mod cmdline;
+pub mod config;
+pub mod console;
+pub mod cpu;
+pub mod cpuid;
+pub mod critical_section;
+pub mod debug;
+pub mod framebuffer;
 pub mod gdt;
+#[cfg(feature = "hpet")]
+pub mod hpet;
 pub mod interrupts;
+pub mod keyboard;
+pub mod klog;
+pub mod log;
 pub mod memory;
+pub mod mouse;
+pub mod once;
+pub mod panic;
+pub mod pci;
+pub mod pit;
+pub mod power;
+pub mod ps2;
+pub mod queue;
+pub mod random;
+pub mod rtc;
 pub mod serial;
+pub mod shell;
+pub mod sync;
+pub mod syscall;
+pub mod task;
+pub mod trace;
 pub mod vga_buffer;
+pub mod vga_graphics;
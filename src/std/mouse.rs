@@ -0,0 +1,226 @@
+use spin::Mutex;
+
+use crate::println;
+use crate::std::interrupts::{unmask_irq, CASCADE_IRQ};
+use crate::std::ps2::{self, Ps2Error};
+use crate::std::queue::ByteQueue;
+
+/// The IRQ line of the PS/2 mouse, on the slave PIC.
+pub const MOUSE_IRQ: u8 = 12;
+
+// The mouse commands used during initialization.
+// https://wiki.osdev.org/PS/2_Mouse
+const COMMAND_SET_DEFAULTS: u8 = 0xF6;
+const COMMAND_SET_SAMPLE_RATE: u8 = 0xF3;
+const COMMAND_ENABLE_REPORTING: u8 = 0xF4;
+
+/// The number of packets per second the mouse is set to report by `init`.
+pub const DEFAULT_SAMPLE_RATE: u8 = 100;
+
+// The bits of the first byte of a packet, the flags.
+const FLAG_LEFT: u8 = 1 << 0;
+const FLAG_RIGHT: u8 = 1 << 1;
+const FLAG_MIDDLE: u8 = 1 << 2;
+// Always set, used to find the start of a packet after losing a byte.
+const FLAG_ALWAYS_SET: u8 = 1 << 3;
+const FLAG_X_SIGN: u8 = 1 << 4;
+const FLAG_Y_SIGN: u8 = 1 << 5;
+const FLAG_X_OVERFLOW: u8 = 1 << 6;
+const FLAG_Y_OVERFLOW: u8 = 1 << 7;
+
+// The number of bytes of a standard PS/2 mouse packet.
+const PACKET_SIZE: usize = 3;
+
+/// The movement and buttons reported by a single packet of the mouse. The movement is relative
+/// to the previous packet, with positive `dy` being upwards as reported by the mouse.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MouseEvent {
+    pub dx: i16,
+    pub dy: i16,
+    pub left: bool,
+    pub right: bool,
+    pub middle: bool,
+}
+
+/// Assembles the bytes sent by the mouse into packets and decodes them.
+pub struct PacketAssembler {
+    bytes: [u8; PACKET_SIZE],
+    len: usize,
+}
+
+impl PacketAssembler {
+    pub const fn new() -> PacketAssembler {
+        PacketAssembler {
+            bytes: [0; PACKET_SIZE],
+            len: 0,
+        }
+    }
+
+    /// Adds a byte sent by the mouse, returning the event once the last byte of a packet has
+    /// been added. A first byte without the always set bit is dropped, so that the assembler
+    /// gets back in sync with the packets after a byte was lost.
+    ///
+    /// # Arguments
+    ///
+    /// `byte` The byte read from the data port of the PS/2 controller.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let mut assembler = PacketAssembler::new();
+    /// assembler.add_byte(0b0000_1001);
+    /// assembler.add_byte(5);
+    /// let event = assembler.add_byte(0).unwrap();
+    /// assert!(event.left);
+    /// ```
+    pub fn add_byte(&mut self, byte: u8) -> Option<MouseEvent> {
+        if self.len == 0 && byte & FLAG_ALWAYS_SET == 0 {
+            return None;
+        }
+
+        self.bytes[self.len] = byte;
+        self.len += 1;
+
+        if self.len < PACKET_SIZE {
+            return None;
+        }
+
+        self.len = 0;
+        Some(decode(self.bytes))
+    }
+}
+
+/// Decodes a complete packet, movement that overflowed is dropped.
+fn decode(bytes: [u8; PACKET_SIZE]) -> MouseEvent {
+    let flags = bytes[0];
+
+    // the movement is a 9 bit two's complement value, with the sign bit in
+    // the flags.
+    let movement = |value: u8, sign: u8, overflow: u8| {
+        if flags & overflow != 0 {
+            0
+        } else if flags & sign != 0 {
+            value as i16 - 0x100
+        } else {
+            value as i16
+        }
+    };
+
+    MouseEvent {
+        dx: movement(bytes[1], FLAG_X_SIGN, FLAG_X_OVERFLOW),
+        dy: movement(bytes[2], FLAG_Y_SIGN, FLAG_Y_OVERFLOW),
+        left: flags & FLAG_LEFT != 0,
+        right: flags & FLAG_RIGHT != 0,
+        middle: flags & FLAG_MIDDLE != 0,
+    }
+}
+
+const PACKET_QUEUE_SIZE: usize = 96;
+
+// Raw packet bytes pushed by the mouse interrupt handler, popped by
+// `poll_mouse` while it holds the `ASSEMBLER` lock.
+static PACKET_BYTES: ByteQueue<PACKET_QUEUE_SIZE> = ByteQueue::new();
+
+static ASSEMBLER: Mutex<PacketAssembler> = Mutex::new(PacketAssembler::new());
+
+/// Queues a byte sent by the mouse to be assembled outside of the interrupt, called from the
+/// mouse interrupt handler. Bytes are dropped when the queue is full.
+pub fn push_packet_byte(byte: u8) {
+    PACKET_BYTES.push(byte);
+}
+
+/// Returns the oldest mouse event that has not been read yet, or `None` if no complete packet
+/// has been received.
+///
+/// # Example
+///
+/// ```
+/// while let Some(event) = mouse::poll_mouse() {
+///     println!("moved by {}, {}", event.dx, event.dy);
+/// }
+/// ```
+pub fn poll_mouse() -> Option<MouseEvent> {
+    let mut assembler = ASSEMBLER.lock();
+
+    while let Some(byte) = PACKET_BYTES.pop() {
+        if let Some(event) = assembler.add_byte(byte) {
+            return Some(event);
+        }
+    }
+
+    None
+}
+
+/// Enables the mouse on the second PS/2 port with its interrupt, and has it report packets at
+/// the default sample rate. Failures are reported on the screen, the kernel works without a
+/// mouse.
+pub fn init() {
+    if let Err(error) = try_init(DEFAULT_SAMPLE_RATE) {
+        println!("mouse: initialization failed: {:?}", error);
+        return;
+    }
+
+    unmask_irq(CASCADE_IRQ);
+    unmask_irq(MOUSE_IRQ);
+}
+
+fn try_init(sample_rate: u8) -> Result<(), Ps2Error> {
+    use x86_64::instructions::interrupts;
+
+    interrupts::without_interrupts(|| {
+        let mut controller = ps2::CONTROLLER.lock();
+
+        ps2::enable_second_port_with(&mut *controller)?;
+
+        let config = ps2::read_config_with(&mut *controller)?;
+        let config =
+            (config | ps2::CONFIG_SECOND_PORT_INTERRUPT) & !ps2::CONFIG_SECOND_PORT_CLOCK_DISABLED;
+        ps2::write_config_with(&mut *controller, config)
+    })?;
+
+    ps2::send_mouse_command(COMMAND_SET_DEFAULTS, None)?;
+    ps2::send_mouse_command(COMMAND_SET_SAMPLE_RATE, Some(sample_rate))?;
+    ps2::send_mouse_command(COMMAND_ENABLE_REPORTING, None)
+}
+
+// Tests
+
+#[test_case]
+fn test_assemble_packet() {
+    let mut assembler = PacketAssembler::new();
+
+    // left button held, moving left by 16 and up by 5.
+    assert_eq!(
+        assembler.add_byte(FLAG_ALWAYS_SET | FLAG_LEFT | FLAG_X_SIGN),
+        None
+    );
+    assert_eq!(assembler.add_byte(0xF0), None);
+    assert_eq!(
+        assembler.add_byte(0x05),
+        Some(MouseEvent {
+            dx: -16,
+            dy: 5,
+            left: true,
+            right: false,
+            middle: false,
+        })
+    );
+}
+
+#[test_case]
+fn test_assemble_packet_resyncs() {
+    let mut assembler = PacketAssembler::new();
+
+    // a lost flags byte leaves the movement bytes, which are dropped until
+    // the next flags byte.
+    assert_eq!(assembler.add_byte(0x00), None);
+    assert_eq!(
+        assembler.add_byte(FLAG_ALWAYS_SET | FLAG_RIGHT | FLAG_Y_OVERFLOW),
+        None
+    );
+    assert_eq!(assembler.add_byte(0x01), None);
+
+    let event = assembler.add_byte(0xFF).unwrap();
+    assert_eq!((event.dx, event.dy), (1, 0));
+    assert!(event.right);
+}
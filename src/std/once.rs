@@ -0,0 +1,38 @@
+/// One time initialization of global state.
+///
+/// Subsystems that need to build their global state at runtime (e.g. because it touches the
+/// hardware) keep it in a `Once` and expose an accessor that passes the initializer to
+/// `call_once`. The first caller runs the initializer, every later caller, including any that
+/// were racing the first, gets the already initialized value.
+///
+/// # Example
+///
+/// ```
+/// static SERIAL1: Once<Mutex<SerialPort>> = Once::new();
+///
+/// pub fn serial1() -> &'static Mutex<SerialPort> {
+///     SERIAL1.call_once(|| ...)
+/// }
+/// ```
+pub use spin::Once;
+
+// Tests
+
+#[test_case]
+fn test_once_runs_initializer_once() {
+    use core::sync::atomic::{AtomicUsize, Ordering};
+
+    static CALLS: AtomicUsize = AtomicUsize::new(0);
+    static VALUE: Once<u32> = Once::new();
+
+    for _ in 0..3 {
+        let value = VALUE.call_once(|| {
+            CALLS.fetch_add(1, Ordering::SeqCst);
+            42
+        });
+
+        assert_eq!(*value, 42);
+    }
+
+    assert_eq!(CALLS.load(Ordering::SeqCst), 1);
+}
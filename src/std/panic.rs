@@ -0,0 +1,84 @@
+use core::panic::PanicInfo;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use spin::MutexGuard;
+
+use crate::std::sync::TryMutex;
+use crate::{println, serial_println};
+
+// The number of panic handlers that have started running, more than one
+// means the panic handler itself panicked. Once set the output paths no
+// longer wait on locks that the panicking code may be holding.
+static PANIC_DEPTH: AtomicUsize = AtomicUsize::new(0);
+
+/// Marks the kernel as panicking, must be called at the start of every panic handler before
+/// anything is printed. Returns the panic depth, 1 for the first panic and higher if the panic
+/// happened while handling an earlier one.
+pub fn begin() -> usize {
+    PANIC_DEPTH.fetch_add(1, Ordering::SeqCst) + 1
+}
+
+/// Returns true if a panic handler has started running.
+pub fn is_panicking() -> bool {
+    depth() > 0
+}
+
+/// Returns the number of panic handlers that have started running.
+pub fn depth() -> usize {
+    PANIC_DEPTH.load(Ordering::SeqCst)
+}
+
+/// Reports the panic on the screen, followed by the registers, the stack trace and the kernel
+/// log, used by the panic handler of the kernel.
+///
+/// A panic while reporting an earlier panic most likely came from the screen output itself, so
+/// it is only reported on the serial port. A panic while doing that is not reported at all.
+///
+/// # Arguments
+///
+/// `info` The information passed to the panic handler.
+///
+/// # Example
+///
+/// ```
+/// #[panic_handler]
+/// fn panic(info: &PanicInfo) -> ! {
+///     std::panic::report(info);
+///     std::interrupts::htl_loop();
+/// }
+/// ```
+pub fn report(info: &PanicInfo) {
+    match begin() {
+        1 => {}
+        2 => {
+            serial_println!("panic while panicking: {}", info);
+            return;
+        }
+        _ => return,
+    }
+
+    crate::std::vga_graphics::set_text_mode();
+    println!("{}", info);
+    crate::std::debug::dump_registers();
+    crate::std::trace::dump();
+    crate::std::klog::dump_klog();
+}
+
+/// Locks the mutex of an output device for printing.
+///
+/// Outside of a panic this is a normal lock. While panicking the lock may be held by the code
+/// that panicked (e.g. a `Display` implementation that panicked in the middle of a `println!`),
+/// which will never release it. In that case the lock is forcibly released so that the panic
+/// can still be reported, at the cost of possibly interleaving with the interrupted output.
+pub fn lock_for_print<T>(mutex: &TryMutex<T>) -> MutexGuard<T> {
+    if is_panicking() {
+        if let Some(guard) = mutex.try_lock() {
+            return guard;
+        }
+
+        // the holder can never run again, there is only a single CPU and the
+        // panic handler never returns.
+        unsafe { mutex.force_unlock() };
+    }
+
+    mutex.lock()
+}
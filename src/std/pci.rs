@@ -0,0 +1,167 @@
+use core::fmt;
+use x86_64::instructions::port::Port;
+
+// The configuration space access ports, the address of the register is
+// written to the address port and the register is then read from the data port.
+// https://wiki.osdev.org/PCI#Configuration_Space_Access_Mechanism_.231
+const CONFIG_ADDRESS_PORT: u16 = 0xCF8;
+const CONFIG_DATA_PORT: u16 = 0xCFC;
+
+// Set in the configuration address to enable the access.
+const CONFIG_ENABLE: u32 = 1 << 31;
+
+// Offsets of the registers in the configuration space header.
+const OFFSET_VENDOR_DEVICE: u8 = 0x00;
+const OFFSET_CLASS: u8 = 0x08;
+const OFFSET_HEADER_TYPE: u8 = 0x0C;
+
+// The vendor ID read for functions that do not exist.
+const NO_VENDOR: u16 = 0xFFFF;
+
+// Set in the header type of devices that implement more than one function.
+const HEADER_TYPE_MULTI_FUNCTION: u8 = 1 << 7;
+
+const BUSES: u16 = 256;
+const DEVICES: u8 = 32;
+const FUNCTIONS: u8 = 8;
+
+/// A single function of a device found on the PCI bus.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PciDevice {
+    pub bus: u8,
+    pub device: u8,
+    pub function: u8,
+    pub vendor_id: u16,
+    pub device_id: u16,
+    pub class: u8,
+    pub subclass: u8,
+}
+
+impl fmt::Display for PciDevice {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{:02x}:{:02x}.{} {:04x}:{:04x} class {:02x}:{:02x}",
+            self.bus,
+            self.device,
+            self.function,
+            self.vendor_id,
+            self.device_id,
+            self.class,
+            self.subclass
+        )
+    }
+}
+
+/// Returns an iterator over every function of every device on the PCI buses, scanning all 256
+/// buses through the configuration space ports.
+///
+/// # Example
+///
+/// ```
+/// for device in pci::enumerate() {
+///     println!("{}", device);
+/// }
+/// ```
+pub fn enumerate() -> impl Iterator<Item = PciDevice> {
+    PciScan {
+        bus: 0,
+        device: 0,
+        function: 0,
+    }
+}
+
+// The position of the next function to be checked.
+struct PciScan {
+    bus: u16,
+    device: u8,
+    function: u8,
+}
+
+impl PciScan {
+    /// Moves to the next function, or to the next device if the current device only has a
+    /// single function.
+    fn advance(&mut self, multi_function: bool) {
+        self.function += 1;
+
+        if self.function == FUNCTIONS || !multi_function {
+            self.function = 0;
+            self.device += 1;
+        }
+
+        if self.device == DEVICES {
+            self.device = 0;
+            self.bus += 1;
+        }
+    }
+}
+
+impl Iterator for PciScan {
+    type Item = PciDevice;
+
+    fn next(&mut self) -> Option<PciDevice> {
+        while self.bus < BUSES {
+            let (bus, device, function) = (self.bus as u8, self.device, self.function);
+
+            let vendor_device = read_config(bus, device, function, OFFSET_VENDOR_DEVICE);
+            let vendor_id = vendor_device as u16;
+            if vendor_id == NO_VENDOR {
+                // a missing function 0 means that the whole device is missing.
+                self.advance(function != 0);
+                continue;
+            }
+
+            // only function 0 reports if the device has further functions.
+            let multi_function = function != 0
+                || (read_config(bus, device, 0, OFFSET_HEADER_TYPE) >> 16) as u8
+                    & HEADER_TYPE_MULTI_FUNCTION
+                    != 0;
+            self.advance(multi_function);
+
+            let class = read_config(bus, device, function, OFFSET_CLASS);
+            return Some(PciDevice {
+                bus,
+                device,
+                function,
+                vendor_id,
+                device_id: (vendor_device >> 16) as u16,
+                class: (class >> 24) as u8,
+                subclass: (class >> 16) as u8,
+            });
+        }
+
+        None
+    }
+}
+
+/// Reads the 32 bit register at the given offset of the configuration space of a function.
+fn read_config(bus: u8, device: u8, function: u8, offset: u8) -> u32 {
+    use x86_64::instructions::interrupts;
+
+    let address = CONFIG_ENABLE
+        | (bus as u32) << 16
+        | (device as u32) << 11
+        | (function as u32) << 8
+        | (offset & 0xFC) as u32;
+
+    let mut address_port: Port<u32> = Port::new(CONFIG_ADDRESS_PORT);
+    let mut data_port: Port<u32> = Port::new(CONFIG_DATA_PORT);
+
+    // the address and data must be accessed as a pair.
+    interrupts::without_interrupts(|| unsafe {
+        address_port.write(address);
+        data_port.read()
+    })
+}
+
+// Tests
+
+#[test_case]
+fn test_enumerate_finds_host_bridge() {
+    // the host bridge of both QEMU machines is an Intel device on bus 0.
+    let host_bridge = enumerate().find(|device| device.class == 0x06 && device.subclass == 0x00);
+    let host_bridge = host_bridge.expect("no host bridge found");
+
+    assert_eq!(host_bridge.vendor_id, 0x8086);
+    assert_eq!((host_bridge.bus, host_bridge.device), (0, 0));
+}
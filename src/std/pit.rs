@@ -0,0 +1,110 @@
+use core::sync::atomic::{AtomicU32, Ordering};
+use x86_64::instructions::port::{Port, PortWriteOnly};
+
+/// The frequency of the PIT input clock, the timer interrupt fires at this frequency divided by
+/// the reload value.
+pub const BASE_FREQUENCY_HZ: u32 = 1_193_182;
+
+/// The frequency the timer is set to by `init`.
+pub const DEFAULT_FREQUENCY_HZ: u32 = 100;
+
+// The largest reload value, written to the PIT as zero. This is the value the
+// PIT starts with, giving ~18.2Hz.
+const MAX_RELOAD: u32 = 65_536;
+
+// The ports of the PIT, channel 0 is connected to IRQ 0.
+// https://wiki.osdev.org/Programmable_Interval_Timer#I.2FO_Ports
+const CHANNEL_0_PORT: u16 = 0x40;
+const COMMAND_PORT: u16 = 0x43;
+
+// Channel 0, access mode lobyte/hibyte, mode 3 (square wave generator), binary.
+const COMMAND_CHANNEL_0_SQUARE_WAVE: u8 = 0x36;
+
+// The reload value currently programmed into channel 0.
+static RELOAD: AtomicU32 = AtomicU32::new(MAX_RELOAD);
+
+/// Sets the frequency of the timer interrupt. The frequency is rounded to the nearest one the
+/// PIT can produce and clamped to the supported range of ~18.2Hz to 1.19MHz.
+///
+/// Tick based conversions (`interrupts::ms_to_ticks`, `clock::uptime`) use the current frequency,
+/// changing it does not convert ticks that were counted at the previous frequency.
+///
+/// # Arguments
+///
+/// `hz` The number of timer interrupts per second.
+///
+/// # Example
+///
+/// ```
+/// pit::set_pit_frequency(1000);
+/// ```
+pub fn set_pit_frequency(hz: u32) {
+    use x86_64::instructions::interrupts;
+
+    let reload = ((BASE_FREQUENCY_HZ + hz / 2) / hz.max(1)).max(1).min(MAX_RELOAD);
+
+    let mut command: PortWriteOnly<u8> = PortWriteOnly::new(COMMAND_PORT);
+    let mut channel_0: Port<u8> = Port::new(CHANNEL_0_PORT);
+
+    // the two bytes of the reload value must not be split by another access.
+    interrupts::without_interrupts(|| unsafe {
+        command.write(COMMAND_CHANNEL_0_SQUARE_WAVE);
+        channel_0.write(reload as u8);
+        channel_0.write((reload >> 8) as u8);
+    });
+
+    RELOAD.store(reload, Ordering::SeqCst);
+}
+
+/// Returns the reload value currently programmed into the timer channel.
+pub fn reload() -> u32 {
+    RELOAD.load(Ordering::SeqCst)
+}
+
+/// Returns the current frequency of the timer interrupt in Hz, rounded down.
+pub fn frequency_hz() -> u32 {
+    BASE_FREQUENCY_HZ / reload()
+}
+
+// Tests
+
+#[test_case]
+fn test_set_pit_frequency() {
+    use crate::std::interrupts::{self, ticks};
+    use x86_64::instructions::port::Port;
+
+    // reads the seconds of the CMOS real time clock, an independent clock to
+    // measure the tick rate against.
+    fn rtc_seconds() -> u8 {
+        let mut select: Port<u8> = Port::new(0x70);
+        let mut data: Port<u8> = Port::new(0x71);
+
+        x86_64::instructions::interrupts::without_interrupts(|| unsafe {
+            select.write(0x00);
+            data.read()
+        })
+    }
+
+    fn wait_for_next_second() {
+        let second = rtc_seconds();
+        while rtc_seconds() == second {
+            core::hint::spin_loop();
+        }
+    }
+
+    let dots = interrupts::timer_dots();
+    interrupts::set_timer_dots(false);
+    set_pit_frequency(1000);
+    assert_eq!(reload(), 1193);
+
+    // count the ticks over exactly one second of the real time clock.
+    wait_for_next_second();
+    let start = ticks();
+    wait_for_next_second();
+    let elapsed = ticks() - start;
+
+    set_pit_frequency(DEFAULT_FREQUENCY_HZ);
+    interrupts::set_timer_dots(dots);
+
+    assert!(elapsed > 800 && elapsed < 1200, "{} ticks in one second", elapsed);
+}
@@ -0,0 +1,85 @@
+use x86_64::instructions::port::{Port, PortReadOnly, PortWriteOnly};
+
+use crate::{exit_qemu, running_under_qemu, QemuExitCode};
+
+// The status and command port of the 8042 PS/2 controller.
+const PS2_STATUS_PORT: u16 = 0x64;
+const PS2_COMMAND_PORT: u16 = 0x64;
+
+// Set while the controller has not yet read the last written byte.
+const PS2_STATUS_INPUT_FULL: u8 = 1 << 1;
+
+// Pulses output line 0 of the controller, which is wired to the CPU reset line.
+const PS2_COMMAND_PULSE_RESET: u8 = 0xFE;
+
+// The number of times the controller status is polled before the reset
+// command is sent anyway.
+const PS2_POLL_LIMIT: usize = 100_000;
+
+// The PM1a control port of the q35 and recent i440fx machines, and the value
+// selecting the soft off sleep state with the sleep enable bit.
+const QEMU_PM1A_CONTROL: u16 = 0x604;
+const SLEEP_S5: u16 = 0x2000;
+
+/// Resets the machine by pulsing the CPU reset line through the 8042 keyboard controller. Halts
+/// if the machine is still running afterwards, e.g. when there is no 8042 controller.
+///
+/// # Example
+///
+/// ```
+/// if unrecoverable {
+///     power::reboot();
+/// }
+/// ```
+pub fn reboot() -> ! {
+    x86_64::instructions::interrupts::disable();
+
+    let mut status: PortReadOnly<u8> = PortReadOnly::new(PS2_STATUS_PORT);
+    let mut command: PortWriteOnly<u8> = PortWriteOnly::new(PS2_COMMAND_PORT);
+
+    unsafe {
+        for _ in 0..PS2_POLL_LIMIT {
+            if status.read() & PS2_STATUS_INPUT_FULL == 0 {
+                break;
+            }
+        }
+
+        command.write(PS2_COMMAND_PULSE_RESET);
+    }
+
+    crate::std::interrupts::htl_loop();
+}
+
+/// Powers off the machine, halting if it is still running afterwards.
+///
+/// Under QEMU the isa-debug-exit device (present when running the tests) is used first, since
+/// powering off through ACPI exits QEMU with a status the test runner reports as a failure.
+/// Without the device an ACPI shutdown is done through the fixed PM1a control port of QEMU and
+/// Bochs. Real hardware places the port elsewhere and requires the ACPI tables to be parsed to
+/// find it, which is not done yet, so there the machine is only halted.
+///
+/// # Example
+///
+/// ```
+/// power::shutdown();
+/// ```
+pub fn shutdown() -> ! {
+    x86_64::instructions::interrupts::disable();
+
+    exit_qemu(QemuExitCode::Success);
+    acpi_shutdown();
+
+    crate::std::interrupts::htl_loop();
+}
+
+/// Powers off QEMU through the ACPI PM1a control register, does nothing on real hardware.
+fn acpi_shutdown() {
+    if !running_under_qemu() {
+        return;
+    }
+
+    unsafe {
+        let mut port: Port<u16> = Port::new(QEMU_PM1A_CONTROL);
+        port.write(SLEEP_S5);
+    }
+}
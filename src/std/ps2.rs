@@ -0,0 +1,422 @@
+use spin::Mutex;
+use x86_64::instructions::port::{Port, PortReadOnly, PortWriteOnly};
+
+// The data and status ports of the PS/2 controller, the status port is the
+// command port when written to.
+// https://wiki.osdev.org/%228042%22_PS/2_Controller#PS.2F2_Controller_IO_Ports
+const DATA_PORT: u16 = 0x60;
+const STATUS_PORT: u16 = 0x64;
+const COMMAND_PORT: u16 = 0x64;
+
+// Controller commands to read and write the configuration byte.
+const COMMAND_READ_CONFIG: u8 = 0x20;
+const COMMAND_WRITE_CONFIG: u8 = 0x60;
+
+// Controller commands enabling the second PS/2 port, and sending the next
+// data byte to the device on the second port instead of the first.
+const COMMAND_ENABLE_SECOND_PORT: u8 = 0xA8;
+const COMMAND_WRITE_SECOND_PORT: u8 = 0xD4;
+
+/// Configuration byte bit enabling the interrupt of the first PS/2 port (IRQ 1).
+pub const CONFIG_FIRST_PORT_INTERRUPT: u8 = 1 << 0;
+/// Configuration byte bit enabling the interrupt of the second PS/2 port (IRQ 12).
+pub const CONFIG_SECOND_PORT_INTERRUPT: u8 = 1 << 1;
+/// Configuration byte bit disabling the clock of the first PS/2 port.
+pub const CONFIG_FIRST_PORT_CLOCK_DISABLED: u8 = 1 << 4;
+/// Configuration byte bit disabling the clock of the second PS/2 port.
+pub const CONFIG_SECOND_PORT_CLOCK_DISABLED: u8 = 1 << 5;
+/// Configuration byte bit enabling translation of scan codes to scan code set 1.
+pub const CONFIG_FIRST_PORT_TRANSLATION: u8 = 1 << 6;
+
+const STATUS_OUTPUT_FULL: u8 = 1 << 0;
+const STATUS_INPUT_FULL: u8 = 1 << 1;
+
+/// The response of a device that accepted the last byte.
+pub const ACK: u8 = 0xFA;
+/// The response of a device that wants the last byte to be sent again.
+pub const RESEND: u8 = 0xFE;
+
+// The number of times a byte is sent again after a resend response.
+const MAX_RETRIES: u32 = 3;
+
+// The number of time stamp counter cycles to wait for the controller before
+// giving up, a few milliseconds on any modern CPU.
+const TIMEOUT_CYCLES: u64 = 10_000_000;
+
+// The number of cycles to wait after the first resend response, doubled on
+// every further resend.
+const BACKOFF_CYCLES: u64 = 100_000;
+
+/// The errors that can occur while sending a command to a PS/2 device.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Ps2Error {
+    // The controller did not become ready within the timeout.
+    Timeout,
+    // The device asked for the byte to be resent more than the retry limit.
+    TooManyResends,
+    // The device responded with something other than an ACK or resend.
+    UnexpectedResponse(u8),
+}
+
+/// The two ports of the PS/2 controller, the keyboard is usually on the first and the mouse on
+/// the second.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Ps2Port {
+    First,
+    Second,
+}
+
+/// The operations of the PS/2 controller needed to communicate with a device, allowing the
+/// handshake to be exercised against a fake controller during testing.
+pub trait Ps2Controller {
+    fn read_status(&mut self) -> u8;
+    fn read_data(&mut self) -> u8;
+    fn write_data(&mut self, value: u8);
+    fn write_command(&mut self, value: u8);
+}
+
+/// The PS/2 controller accessed through its I/O ports.
+pub struct PortController {
+    data: Port<u8>,
+    status: PortReadOnly<u8>,
+    command: PortWriteOnly<u8>,
+}
+
+impl PortController {
+    /// This function is unsafe since the caller must guarantee that the PS/2
+    /// controller exists at the standard ports.
+    pub const unsafe fn new() -> PortController {
+        PortController {
+            data: Port::new(DATA_PORT),
+            status: PortReadOnly::new(STATUS_PORT),
+            command: PortWriteOnly::new(COMMAND_PORT),
+        }
+    }
+}
+
+impl Ps2Controller for PortController {
+    fn read_status(&mut self) -> u8 {
+        unsafe { self.status.read() }
+    }
+
+    fn read_data(&mut self) -> u8 {
+        unsafe { self.data.read() }
+    }
+
+    fn write_data(&mut self, value: u8) {
+        unsafe { self.data.write(value) }
+    }
+
+    fn write_command(&mut self, value: u8) {
+        unsafe { self.command.write(value) }
+    }
+}
+
+pub static CONTROLLER: Mutex<PortController> = Mutex::new(unsafe { PortController::new() });
+
+/// Sends a command and an optional data byte to the first PS/2 device, waiting for an ACK after
+/// each byte. Used by all keyboard configuration commands (LEDs, typematic rate, scancode set).
+///
+/// # Arguments
+///
+/// `command` The command byte sent to the device.
+/// `data` The optional data byte that follows the command.
+///
+/// # Example
+///
+/// ```
+/// // set the scroll, number and caps lock LEDs.
+/// ps2::send_command(0xED, Some(0b111))?;
+/// ```
+pub fn send_command(command: u8, data: Option<u8>) -> Result<(), Ps2Error> {
+    use x86_64::instructions::interrupts;
+
+    // the keyboard interrupt handler would otherwise consume the ACK.
+    interrupts::without_interrupts(|| send_command_with(&mut *CONTROLLER.lock(), command, data))
+}
+
+/// Sends a command and an optional data byte through the given controller, see `send_command`.
+pub fn send_command_with(
+    controller: &mut impl Ps2Controller,
+    command: u8,
+    data: Option<u8>,
+) -> Result<(), Ps2Error> {
+    send_port_command_with(controller, Ps2Port::First, command, data)
+}
+
+/// Sends a command and an optional data byte to the device on the second PS/2 port, usually the
+/// mouse, see `send_command`.
+pub fn send_mouse_command(command: u8, data: Option<u8>) -> Result<(), Ps2Error> {
+    use x86_64::instructions::interrupts;
+
+    interrupts::without_interrupts(|| {
+        send_port_command_with(&mut *CONTROLLER.lock(), Ps2Port::Second, command, data)
+    })
+}
+
+/// Sends a command and an optional data byte to the device on the given port through the given
+/// controller.
+pub fn send_port_command_with(
+    controller: &mut impl Ps2Controller,
+    port: Ps2Port,
+    command: u8,
+    data: Option<u8>,
+) -> Result<(), Ps2Error> {
+    send_byte(controller, port, command)?;
+
+    if let Some(data) = data {
+        send_byte(controller, port, data)?;
+    }
+
+    Ok(())
+}
+
+/// Enables the second PS/2 port through the given controller. Its interrupt and clock are
+/// controlled separately by the configuration byte.
+pub fn enable_second_port_with(controller: &mut impl Ps2Controller) -> Result<(), Ps2Error> {
+    wait_for_status(controller, |status| status & STATUS_INPUT_FULL == 0)?;
+    controller.write_command(COMMAND_ENABLE_SECOND_PORT);
+
+    Ok(())
+}
+
+/// Reads the configuration byte of the PS/2 controller.
+pub fn read_config() -> Result<u8, Ps2Error> {
+    use x86_64::instructions::interrupts;
+
+    interrupts::without_interrupts(|| read_config_with(&mut *CONTROLLER.lock()))
+}
+
+/// Writes the configuration byte of the PS/2 controller, the value should be based on the one
+/// returned by `read_config` with only the required bits changed.
+///
+/// # Example
+///
+/// ```
+/// let config = ps2::read_config()?;
+/// ps2::write_config(config | ps2::CONFIG_SECOND_PORT_INTERRUPT)?;
+/// ```
+pub fn write_config(config: u8) -> Result<(), Ps2Error> {
+    use x86_64::instructions::interrupts;
+
+    interrupts::without_interrupts(|| write_config_with(&mut *CONTROLLER.lock(), config))
+}
+
+/// Reads the configuration byte through the given controller, see `read_config`.
+pub fn read_config_with(controller: &mut impl Ps2Controller) -> Result<u8, Ps2Error> {
+    wait_for_status(controller, |status| status & STATUS_INPUT_FULL == 0)?;
+    controller.write_command(COMMAND_READ_CONFIG);
+
+    wait_for_status(controller, |status| status & STATUS_OUTPUT_FULL != 0)?;
+    Ok(controller.read_data())
+}
+
+/// Writes the configuration byte through the given controller, see `write_config`.
+pub fn write_config_with(controller: &mut impl Ps2Controller, config: u8) -> Result<(), Ps2Error> {
+    wait_for_status(controller, |status| status & STATUS_INPUT_FULL == 0)?;
+    controller.write_command(COMMAND_WRITE_CONFIG);
+
+    wait_for_status(controller, |status| status & STATUS_INPUT_FULL == 0)?;
+    controller.write_data(config);
+
+    Ok(())
+}
+
+/// Sends a single byte to the device on the port and waits for the ACK, sending the byte again
+/// with an increasing delay each time the device responds with a resend.
+fn send_byte(controller: &mut impl Ps2Controller, port: Ps2Port, byte: u8) -> Result<(), Ps2Error> {
+    for attempt in 0..=MAX_RETRIES {
+        if attempt > 0 {
+            delay_cycles(BACKOFF_CYCLES << (attempt - 1));
+        }
+
+        // every byte for the second port has to be announced to the controller.
+        if port == Ps2Port::Second {
+            wait_for_status(controller, |status| status & STATUS_INPUT_FULL == 0)?;
+            controller.write_command(COMMAND_WRITE_SECOND_PORT);
+        }
+
+        wait_for_status(controller, |status| status & STATUS_INPUT_FULL == 0)?;
+        controller.write_data(byte);
+
+        wait_for_status(controller, |status| status & STATUS_OUTPUT_FULL != 0)?;
+        match controller.read_data() {
+            ACK => return Ok(()),
+            RESEND => continue,
+            response => return Err(Ps2Error::UnexpectedResponse(response)),
+        }
+    }
+
+    Err(Ps2Error::TooManyResends)
+}
+
+/// Polls the status register until the given condition holds or the timeout has passed.
+fn wait_for_status(
+    controller: &mut impl Ps2Controller,
+    condition: impl Fn(u8) -> bool,
+) -> Result<(), Ps2Error> {
+    let deadline = read_tsc() + TIMEOUT_CYCLES;
+
+    while read_tsc() < deadline {
+        if condition(controller.read_status()) {
+            return Ok(());
+        }
+    }
+
+    Err(Ps2Error::Timeout)
+}
+
+fn delay_cycles(cycles: u64) {
+    let deadline = read_tsc() + cycles;
+
+    while read_tsc() < deadline {
+        core::hint::spin_loop();
+    }
+}
+
+fn read_tsc() -> u64 {
+    unsafe { core::arch::x86_64::_rdtsc() }
+}
+
+// Tests
+
+/// A byte written to the fake controller.
+#[cfg(test)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FakeWrite {
+    Data(u8),
+    Command(u8),
+}
+
+/// A fake controller that replays a fixed list of responses, reporting the
+/// output buffer as full while any responses remain, and records the first
+/// few bytes written to it.
+#[cfg(test)]
+struct FakeController {
+    responses: &'static [u8],
+    next: usize,
+    written: usize,
+    log: [Option<FakeWrite>; 8],
+}
+
+#[cfg(test)]
+impl FakeController {
+    fn new(responses: &'static [u8]) -> FakeController {
+        FakeController {
+            responses,
+            next: 0,
+            written: 0,
+            log: [None; 8],
+        }
+    }
+
+    fn record(&mut self, write: FakeWrite) {
+        if let Some(entry) = self.log.get_mut(self.written) {
+            *entry = Some(write);
+        }
+
+        self.written += 1;
+    }
+}
+
+#[cfg(test)]
+impl Ps2Controller for FakeController {
+    fn read_status(&mut self) -> u8 {
+        if self.next < self.responses.len() {
+            STATUS_OUTPUT_FULL
+        } else {
+            0
+        }
+    }
+
+    fn read_data(&mut self) -> u8 {
+        let response = self.responses[self.next];
+        self.next += 1;
+        response
+    }
+
+    fn write_data(&mut self, value: u8) {
+        self.record(FakeWrite::Data(value));
+    }
+
+    fn write_command(&mut self, value: u8) {
+        self.record(FakeWrite::Command(value));
+    }
+}
+
+#[test_case]
+fn test_send_command_ack() {
+    let mut controller = FakeController::new(&[ACK, ACK]);
+
+    assert_eq!(send_command_with(&mut controller, 0xED, Some(0b111)), Ok(()));
+    assert_eq!(controller.written, 2);
+}
+
+#[test_case]
+fn test_send_command_resend_then_ack() {
+    let mut controller = FakeController::new(&[RESEND, RESEND, ACK]);
+
+    assert_eq!(send_command_with(&mut controller, 0xF4, None), Ok(()));
+    assert_eq!(controller.written, 3);
+}
+
+#[test_case]
+fn test_send_command_too_many_resends() {
+    let mut controller = FakeController::new(&[RESEND, RESEND, RESEND, RESEND]);
+
+    assert_eq!(
+        send_command_with(&mut controller, 0xF4, None),
+        Err(Ps2Error::TooManyResends)
+    );
+}
+
+#[test_case]
+fn test_send_command_timeout() {
+    // no responses, the output buffer never fills.
+    let mut controller = FakeController::new(&[]);
+
+    assert_eq!(
+        send_command_with(&mut controller, 0xF4, None),
+        Err(Ps2Error::Timeout)
+    );
+}
+
+#[test_case]
+fn test_config_read_modify_write() {
+    let mut controller = FakeController::new(&[0b0110_0101]);
+
+    let config = read_config_with(&mut controller).expect("read failed");
+    assert_eq!(config, 0b0110_0101);
+
+    // enable the second port interrupt and clock, as done for the mouse.
+    let config = (config | CONFIG_SECOND_PORT_INTERRUPT) & !CONFIG_SECOND_PORT_CLOCK_DISABLED;
+    write_config_with(&mut controller, config).expect("write failed");
+
+    assert_eq!(
+        controller.log[..3],
+        [
+            Some(FakeWrite::Command(COMMAND_READ_CONFIG)),
+            Some(FakeWrite::Command(COMMAND_WRITE_CONFIG)),
+            Some(FakeWrite::Data(0b0100_0111)),
+        ]
+    );
+    assert_eq!(controller.written, 3);
+}
+
+#[test_case]
+fn test_send_command_to_second_port() {
+    let mut controller = FakeController::new(&[ACK, ACK]);
+
+    let result = send_port_command_with(&mut controller, Ps2Port::Second, 0xF3, Some(100));
+    assert_eq!(result, Ok(()));
+
+    assert_eq!(
+        controller.log[..4],
+        [
+            Some(FakeWrite::Command(COMMAND_WRITE_SECOND_PORT)),
+            Some(FakeWrite::Data(0xF3)),
+            Some(FakeWrite::Command(COMMAND_WRITE_SECOND_PORT)),
+            Some(FakeWrite::Data(100)),
+        ]
+    );
+}
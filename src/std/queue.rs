@@ -0,0 +1,64 @@
+use core::sync::atomic::{AtomicU8, AtomicUsize, Ordering};
+
+/// A fixed size lock free queue of bytes with a single producer (usually an interrupt handler)
+/// and a single consumer. Pushing is safe from interrupt context since it never blocks, the
+/// consumer must make sure that only one caller pops at a time.
+pub struct ByteQueue<const N: usize> {
+    bytes: [AtomicU8; N],
+    // The number of bytes pushed and popped, only ever incremented.
+    head: AtomicUsize,
+    tail: AtomicUsize,
+}
+
+impl<const N: usize> ByteQueue<N> {
+    pub const fn new() -> ByteQueue<N> {
+        const EMPTY: AtomicU8 = AtomicU8::new(0);
+
+        ByteQueue {
+            bytes: [EMPTY; N],
+            head: AtomicUsize::new(0),
+            tail: AtomicUsize::new(0),
+        }
+    }
+
+    /// Adds the byte to the back of the queue, returning false if the queue is full.
+    pub fn push(&self, byte: u8) -> bool {
+        let tail = self.tail.load(Ordering::Relaxed);
+        if tail - self.head.load(Ordering::Acquire) == N {
+            return false;
+        }
+
+        self.bytes[tail % N].store(byte, Ordering::Relaxed);
+        self.tail.store(tail + 1, Ordering::Release);
+        true
+    }
+
+    /// Removes the byte at the front of the queue.
+    pub fn pop(&self) -> Option<u8> {
+        let head = self.head.load(Ordering::Relaxed);
+        if head == self.tail.load(Ordering::Acquire) {
+            return None;
+        }
+
+        let byte = self.bytes[head % N].load(Ordering::Relaxed);
+        self.head.store(head + 1, Ordering::Release);
+        Some(byte)
+    }
+}
+
+// Tests
+
+#[test_case]
+fn test_byte_queue_push_pop() {
+    let queue: ByteQueue<2> = ByteQueue::new();
+
+    assert!(queue.push(1));
+    assert!(queue.push(2));
+    assert!(!queue.push(3));
+
+    assert_eq!(queue.pop(), Some(1));
+    assert!(queue.push(4));
+    assert_eq!(queue.pop(), Some(2));
+    assert_eq!(queue.pop(), Some(4));
+    assert_eq!(queue.pop(), None);
+}
@@ -0,0 +1,86 @@
+use crate::std::cpuid::{self, CpuFeature};
+
+// The number of times rdrand is retried when the generator has no random
+// number ready, as recommended by Intel.
+const RDRAND_RETRIES: usize = 10;
+
+/// Returns a random number from the hardware random number generator, or `None` if the CPU does
+/// not support RDRAND or the generator did not produce a number after several retries.
+///
+/// # Example
+///
+/// ```
+/// let seed = random::rdrand_u64().expect("no hardware random numbers");
+/// ```
+pub fn rdrand_u64() -> Option<u64> {
+    if !cpuid::has_feature(CpuFeature::Rdrand) {
+        return None;
+    }
+
+    for _ in 0..RDRAND_RETRIES {
+        let value: u64;
+        let success: u8;
+
+        // the carry flag is cleared when no random number was ready.
+        unsafe {
+            asm!(
+                "rdrand {value}",
+                "setc {success}",
+                value = out(reg) value,
+                success = out(reg_byte) success,
+                options(nomem, nostack),
+            );
+        }
+
+        if success != 0 {
+            return Some(value);
+        }
+    }
+
+    None
+}
+
+/// Returns a random number within `min` (inclusive) and `max` (exclusive). Without RDRAND the
+/// time stamp counter is used instead, which is only suitable where unpredictability does not
+/// matter. The modulo slightly favours the lower values of ranges that do not evenly divide
+/// 2^64.
+///
+/// # Arguments
+///
+/// `min` The smallest number that can be returned.
+/// `max` The number above the largest number that can be returned, must be larger than `min`.
+///
+/// # Example
+///
+/// ```
+/// let dice = random::random_range(1, 7);
+/// ```
+pub fn random_range(min: u64, max: u64) -> u64 {
+    assert!(min < max, "random_range called with an empty range");
+
+    let value = rdrand_u64().unwrap_or_else(|| unsafe { core::arch::x86_64::_rdtsc() });
+    min + value % (max - min)
+}
+
+// Tests
+
+#[test_case]
+fn test_rdrand_values_differ() {
+    let first = match rdrand_u64() {
+        Some(value) => value,
+        // the CPU emulated by QEMU without -cpu host may not support RDRAND.
+        None => return,
+    };
+
+    assert!((0..8).any(|_| rdrand_u64() != Some(first)));
+}
+
+#[test_case]
+fn test_random_range_within_bounds() {
+    for _ in 0..100 {
+        let value = random_range(10, 20);
+        assert!(value >= 10 && value < 20);
+    }
+
+    assert_eq!(random_range(5, 6), 5);
+}
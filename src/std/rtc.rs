@@ -0,0 +1,332 @@
+use core::fmt;
+use core::sync::atomic::{AtomicU64, Ordering};
+use x86_64::instructions::port::Port;
+
+// The CMOS index and data ports, the register is selected through the index
+// port and then read through the data port.
+// https://wiki.osdev.org/CMOS#Accessing_CMOS_Registers
+const CMOS_INDEX_PORT: u16 = 0x70;
+const CMOS_DATA_PORT: u16 = 0x71;
+
+// The real time clock registers in the CMOS.
+const REGISTER_SECONDS: u8 = 0x00;
+const REGISTER_MINUTES: u8 = 0x02;
+const REGISTER_HOURS: u8 = 0x04;
+const REGISTER_DAY: u8 = 0x07;
+const REGISTER_MONTH: u8 = 0x08;
+const REGISTER_YEAR: u8 = 0x09;
+const REGISTER_STATUS_A: u8 = 0x0A;
+const REGISTER_STATUS_B: u8 = 0x0B;
+const REGISTER_STATUS_C: u8 = 0x0C;
+
+// Set in status register A while the clock is updating its registers.
+const STATUS_A_UPDATE_IN_PROGRESS: u8 = 1 << 7;
+
+// The bits of status register A selecting the rate of the periodic interrupt.
+const STATUS_A_RATE_MASK: u8 = 0x0F;
+
+// Set in status register B to enable the periodic interrupt.
+const STATUS_B_PERIODIC_INTERRUPT: u8 = 1 << 6;
+
+// Set in status register B when the values are stored in binary instead of
+// BCD, and when the hours are in 24 hour instead of 12 hour format.
+const STATUS_B_24_HOUR: u8 = 1 << 1;
+const STATUS_B_BINARY: u8 = 1 << 2;
+
+// Set in the hours register for hours after noon in 12 hour format.
+const HOURS_PM: u8 = 1 << 7;
+
+// The year register only holds the last two digits, the century register is
+// not standardised so the 21st century is assumed.
+const CENTURY: u16 = 2000;
+
+/// A wall clock date and time, as kept by the real time clock.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DateTime {
+    pub year: u16,
+    pub month: u8,
+    pub day: u8,
+    pub hour: u8,
+    pub minute: u8,
+    pub second: u8,
+}
+
+impl fmt::Display for DateTime {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{:04}-{:02}-{:02} {:02}:{:02}:{:02}",
+            self.year, self.month, self.day, self.hour, self.minute, self.second
+        )
+    }
+}
+
+// The registers as read from the CMOS, before any conversion.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct RawDateTime {
+    second: u8,
+    minute: u8,
+    hour: u8,
+    day: u8,
+    month: u8,
+    year: u8,
+}
+
+/// Reads the current date and time from the real time clock.
+///
+/// # Example
+///
+/// ```
+/// println!("booted at {}", rtc::read_datetime());
+/// ```
+pub fn read_datetime() -> DateTime {
+    use x86_64::instructions::interrupts;
+
+    // an interrupt between selecting a register and reading it could select
+    // another register in between.
+    let (raw, status_b) = interrupts::without_interrupts(|| {
+        // the registers may be read part way through an update, which is
+        // detected by reading them again until two reads agree.
+        let mut raw = read_raw();
+        loop {
+            let again = read_raw();
+            if again == raw {
+                break;
+            }
+
+            raw = again;
+        }
+
+        (raw, read_register(REGISTER_STATUS_B))
+    });
+
+    decode(raw, status_b)
+}
+
+/// Waits for any update in progress to finish, then reads the date and time registers.
+fn read_raw() -> RawDateTime {
+    while read_register(REGISTER_STATUS_A) & STATUS_A_UPDATE_IN_PROGRESS != 0 {
+        core::hint::spin_loop();
+    }
+
+    RawDateTime {
+        second: read_register(REGISTER_SECONDS),
+        minute: read_register(REGISTER_MINUTES),
+        hour: read_register(REGISTER_HOURS),
+        day: read_register(REGISTER_DAY),
+        month: read_register(REGISTER_MONTH),
+        year: read_register(REGISTER_YEAR),
+    }
+}
+
+/// Converts the raw registers into a date and time, using the format given by status register B.
+fn decode(raw: RawDateTime, status_b: u8) -> DateTime {
+    let convert = |value: u8| {
+        if status_b & STATUS_B_BINARY != 0 {
+            value
+        } else {
+            (value >> 4) * 10 + (value & 0x0F)
+        }
+    };
+
+    let mut hour = convert(raw.hour & !HOURS_PM);
+    if status_b & STATUS_B_24_HOUR == 0 {
+        // 12 AM is midnight and 12 PM is noon.
+        hour %= 12;
+        if raw.hour & HOURS_PM != 0 {
+            hour += 12;
+        }
+    }
+
+    DateTime {
+        year: CENTURY + convert(raw.year) as u16,
+        month: convert(raw.month),
+        day: convert(raw.day),
+        hour,
+        minute: convert(raw.minute),
+        second: convert(raw.second),
+    }
+}
+
+/// The IRQ line of the real time clock, on the slave PIC.
+pub const RTC_IRQ: u8 = 8;
+
+/// The fastest (8192Hz) and slowest (2Hz) rate of the periodic interrupt, see
+/// `enable_periodic_interrupt`.
+pub const MIN_RATE: u8 = 3;
+pub const MAX_RATE: u8 = 15;
+
+// The number of periodic interrupts handled since boot.
+static TICKS: AtomicU64 = AtomicU64::new(0);
+
+/// Enables the periodic interrupt of the real time clock on IRQ 8, raised at a frequency of
+/// `32768 >> (rate - 1)` Hz. Each interrupt increments the counter returned by `ticks`.
+///
+/// # Arguments
+///
+/// `rate` The rate divisor, from `MIN_RATE` (8192 Hz) to `MAX_RATE` (2 Hz).
+///
+/// # Example
+///
+/// ```
+/// // 1024 Hz, the default rate of the real time clock.
+/// rtc::enable_periodic_interrupt(6);
+/// ```
+pub fn enable_periodic_interrupt(rate: u8) {
+    use crate::std::interrupts::{unmask_irq, CASCADE_IRQ};
+    use x86_64::instructions::interrupts;
+
+    assert!(
+        (MIN_RATE..=MAX_RATE).contains(&rate),
+        "the periodic interrupt rate must be from {} to {}",
+        MIN_RATE,
+        MAX_RATE
+    );
+
+    interrupts::without_interrupts(|| {
+        let status_a = read_register(REGISTER_STATUS_A);
+        write_register(REGISTER_STATUS_A, (status_a & !STATUS_A_RATE_MASK) | rate);
+
+        let status_b = read_register(REGISTER_STATUS_B);
+        write_register(REGISTER_STATUS_B, status_b | STATUS_B_PERIODIC_INTERRUPT);
+
+        // an interrupt that is already pending blocks all further ones until
+        // status register C has been read.
+        read_register(REGISTER_STATUS_C);
+    });
+
+    unmask_irq(CASCADE_IRQ);
+    unmask_irq(RTC_IRQ);
+}
+
+/// Disables the periodic interrupt of the real time clock.
+pub fn disable_periodic_interrupt() {
+    use x86_64::instructions::interrupts;
+
+    crate::std::interrupts::mask_irq(RTC_IRQ);
+
+    interrupts::without_interrupts(|| {
+        let status_b = read_register(REGISTER_STATUS_B);
+        write_register(REGISTER_STATUS_B, status_b & !STATUS_B_PERIODIC_INTERRUPT);
+    });
+}
+
+/// Returns the number of periodic interrupts handled since boot.
+pub fn ticks() -> u64 {
+    TICKS.load(Ordering::Relaxed)
+}
+
+/// Acknowledges an interrupt of the real time clock and counts it, called from the interrupt
+/// handler. Until status register C is read no further interrupts are raised.
+pub fn handle_interrupt() {
+    read_register(REGISTER_STATUS_C);
+    TICKS.fetch_add(1, Ordering::Relaxed);
+}
+
+fn read_register(register: u8) -> u8 {
+    let mut index: Port<u8> = Port::new(CMOS_INDEX_PORT);
+    let mut data: Port<u8> = Port::new(CMOS_DATA_PORT);
+
+    unsafe {
+        index.write(register);
+        data.read()
+    }
+}
+
+fn write_register(register: u8, value: u8) {
+    let mut index: Port<u8> = Port::new(CMOS_INDEX_PORT);
+    let mut data: Port<u8> = Port::new(CMOS_DATA_PORT);
+
+    unsafe {
+        index.write(register);
+        data.write(value);
+    }
+}
+
+// Tests
+
+#[test_case]
+fn test_decode_bcd_12_hour() {
+    // 2021-03-09 11:05:59 PM, stored as BCD in 12 hour format.
+    let raw = RawDateTime {
+        second: 0x59,
+        minute: 0x05,
+        hour: HOURS_PM | 0x11,
+        day: 0x09,
+        month: 0x03,
+        year: 0x21,
+    };
+
+    let datetime = decode(raw, 0);
+    assert_eq!(
+        datetime,
+        DateTime {
+            year: 2021,
+            month: 3,
+            day: 9,
+            hour: 23,
+            minute: 5,
+            second: 59,
+        }
+    );
+
+    // 12 AM is the first hour of the day.
+    let midnight = RawDateTime { hour: 0x12, ..raw };
+    assert_eq!(decode(midnight, 0).hour, 0);
+}
+
+#[test_case]
+fn test_decode_binary_24_hour() {
+    let raw = RawDateTime {
+        second: 59,
+        minute: 5,
+        hour: 23,
+        day: 9,
+        month: 3,
+        year: 21,
+    };
+
+    let datetime = decode(raw, STATUS_B_BINARY | STATUS_B_24_HOUR);
+    assert_eq!(
+        (datetime.year, datetime.hour, datetime.second),
+        (2021, 23, 59)
+    );
+}
+
+#[test_case]
+fn test_read_datetime() {
+    let first = read_datetime();
+
+    for _ in 0..10_000 {
+        core::hint::spin_loop();
+    }
+
+    let second = read_datetime();
+
+    assert!(first.second < 60 && second.second < 60);
+    assert!(first.month >= 1 && first.month <= 12);
+    assert!(first.day >= 1 && first.day <= 31);
+    assert!(first.hour < 24);
+
+    // only a moment has passed, at most a single second boundary was crossed.
+    let elapsed = (second.second + 60 - first.second) % 60;
+    assert!(elapsed <= 1, "{} then {}", first, second);
+}
+
+#[test_case]
+fn test_periodic_interrupt_advances_ticks() {
+    let start = ticks();
+
+    // 64 Hz, a few interrupts arrive within the timer ticks waited for.
+    enable_periodic_interrupt(10);
+    for _ in 0..20 {
+        if ticks() > start {
+            break;
+        }
+
+        x86_64::instructions::hlt();
+    }
+    disable_periodic_interrupt();
+
+    assert!(ticks() > start);
+}
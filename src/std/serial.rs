@@ -1,17 +1,89 @@
+use crate::std::once::Once;
+use crate::std::panic::lock_for_print;
+use crate::std::queue::ByteQueue;
+use crate::std::sync::TryMutex;
 use core::fmt::Arguments;
-use lazy_static::lazy_static;
 use spin::Mutex;
 use uart_16550::SerialPort;
 
 // Globally accessible implementation of the first serial port of the virtual machine. This will
 // be used during testing to ensure that the tests can run headless and process the output to the
 // virtual machines terminal output. Using a spin lock to ensure mutual exclusion.
-lazy_static! {
-    pub static ref SERIAL1: Mutex<SerialPort> = {
-        let mut serial_port = unsafe { SerialPort::new(0x3F8) };
+static SERIAL1: Once<TryMutex<SerialPort>> = Once::new();
+
+// The base I/O port of the first serial port.
+const COM1: u16 = 0x3F8;
+
+/// Returns the first serial port, initializing it on the first call. The port raises an
+/// interrupt whenever received data is available, see `serial_read_byte`.
+pub fn serial1() -> &'static TryMutex<SerialPort> {
+    SERIAL1.call_once(|| {
+        use x86_64::instructions::port::PortWriteOnly;
+
+        let mut serial_port = unsafe { SerialPort::new(COM1) };
         serial_port.init();
-        Mutex::new(serial_port)
-    };
+
+        let mut interrupt_enable: PortWriteOnly<u8> =
+            PortWriteOnly::new(COM1 + INTERRUPT_ENABLE_OFFSET);
+        unsafe { interrupt_enable.write(INTERRUPT_ENABLE_RECEIVED_DATA) };
+
+        TryMutex::new("SERIAL1", serial_port)
+    })
+}
+
+// The second serial port, kept separate from the first so logs can be sent
+// to a different host file than the test output.
+static SERIAL2: Once<TryMutex<SerialPort>> = Once::new();
+
+// The base I/O port of the second serial port.
+const COM2: u16 = 0x2F8;
+
+/// Returns the second serial port, initializing it on the first call.
+pub fn serial2() -> &'static TryMutex<SerialPort> {
+    SERIAL2.call_once(|| {
+        let mut serial_port = unsafe { SerialPort::new(COM2) };
+        serial_port.init();
+        TryMutex::new("SERIAL2", serial_port)
+    })
+}
+
+/// The number of received bytes that can be waiting to be read before new ones are dropped.
+const RECEIVE_QUEUE_SIZE: usize = 64;
+
+// Bytes received on the first serial port, pushed by the interrupt handler.
+static RECEIVED: ByteQueue<RECEIVE_QUEUE_SIZE> = ByteQueue::new();
+
+// Held while popping, the received queue only supports a single consumer.
+static READER: Mutex<()> = Mutex::new(());
+
+/// Moves every byte waiting in the receive buffer of the first serial port into the received
+/// queue, called from the serial interrupt handler. Bytes are dropped when the queue is full.
+pub fn receive_pending() {
+    use x86_64::instructions::port::PortReadOnly;
+
+    let mut line_status: PortReadOnly<u8> = PortReadOnly::new(COM1 + LINE_STATUS_OFFSET);
+    let mut data: PortReadOnly<u8> = PortReadOnly::new(COM1 + DATA_OFFSET);
+
+    unsafe {
+        while line_status.read() & LINE_STATUS_DATA_READY != 0 {
+            RECEIVED.push(data.read());
+        }
+    }
+}
+
+/// Returns the oldest byte received on the first serial port that has not been read yet. When
+/// running under QEMU with `-serial stdio` this is the input typed into the terminal.
+///
+/// # Example
+///
+/// ```
+/// while let Some(byte) = serial::serial_read_byte() {
+///     print!("{}", byte as char);
+/// }
+/// ```
+pub fn serial_read_byte() -> Option<u8> {
+    let _reader = READER.lock();
+    RECEIVED.pop()
 }
 
 /// Prints to the host through the serial interface.
@@ -31,6 +103,23 @@ macro_rules! serial_println {
         concat!($fmt, "\n"), $($arg)*));
 }
 
+/// Prints to the host through the second serial interface.
+#[macro_export]
+macro_rules! serial2_print {
+    ($($arg:tt)*) => {
+        $crate::std::serial::_print2(format_args!($($arg)*));
+    };
+}
+
+/// Prints to the host through the second serial interface, appending a newline.
+#[macro_export]
+macro_rules! serial2_println {
+    () => ($crate::serial2_print!("\n"));
+    ($fmt:expr) => ($crate::serial2_print!(concat!($fmt, "\n")));
+    ($fmt:expr, $($arg:tt)*) => ($crate::serial2_print!(
+        concat!($fmt, "\n"), $($arg)*));
+}
+
 #[doc(hidden)]
 pub fn _print(args: Arguments) {
     use core::fmt::Write;
@@ -41,9 +130,353 @@ pub fn _print(args: Arguments) {
     // it to ensure that no interrupt can occur as long as
     // the Mutex is locked.
     interrupts::without_interrupts(|| {
-        SERIAL1
-            .lock()
+        lock_for_print(serial1())
             .write_fmt(args)
             .expect("Printing to serial failed");
     })
 }
+
+#[doc(hidden)]
+pub fn _print2(args: Arguments) {
+    use core::fmt::Write;
+    use x86_64::instructions::interrupts;
+
+    interrupts::without_interrupts(|| {
+        lock_for_print(serial2())
+            .write_fmt(args)
+            .expect("Printing to serial failed");
+    })
+}
+
+/// Writes the data as a hex dump, 16 bytes per line, each line starting with the address of its
+/// first byte and ending with the printable ascii characters.
+///
+/// # Arguments
+///
+/// `out` The output the dump is written to.
+/// `address` The address of the first byte, used only for the labels.
+/// `data` The bytes being dumped.
+///
+/// # Example
+///
+/// ```
+/// 00000000000b8000: 48 0e 65 0e 6c 0e 6c 0e 6f 0e 20 0e 20 0e 20 0e  |H.e.l.l.o. . . .|
+/// ```
+pub fn write_hexdump(out: &mut impl core::fmt::Write, address: u64, data: &[u8]) -> core::fmt::Result {
+    for (line, chunk) in data.chunks(16).enumerate() {
+        write!(out, "{:016x}:", address + line as u64 * 16)?;
+
+        for col in 0..16 {
+            match chunk.get(col) {
+                Some(byte) => write!(out, " {:02x}", byte)?,
+                None => out.write_str("   ")?,
+            }
+        }
+
+        out.write_str("  |")?;
+        for &byte in chunk {
+            let character = match byte {
+                0x20..=0x7e => byte as char,
+                _ => '.',
+            };
+            out.write_char(character)?;
+        }
+        out.write_str("|\n")?;
+    }
+
+    Ok(())
+}
+
+/// Writes the data as a hex dump to the serial port, see `write_hexdump`.
+pub fn serial_hexdump(address: u64, data: &[u8]) {
+    use x86_64::instructions::interrupts;
+
+    interrupts::without_interrupts(|| {
+        write_hexdump(&mut *lock_for_print(serial1()), address, data)
+            .expect("Printing to serial failed");
+    })
+}
+
+/// Prints to the serial port unless it is already locked, returning false if nothing was
+/// printed. See `vga_buffer::try_print`.
+pub fn try_print(args: Arguments) -> bool {
+    use core::fmt::Write;
+    use x86_64::instructions::interrupts;
+
+    interrupts::without_interrupts(|| match serial1().try_lock() {
+        Some(mut serial) => serial.write_fmt(args).is_ok(),
+        None => false,
+    })
+}
+
+/// The baud rate of the UART clock, the baud rate is set by dividing this value.
+const UART_CLOCK_BAUD_RATE: u32 = 115_200;
+
+/// The baud rate used by `configure_default`, the common 115200 8N1.
+pub const DEFAULT_BAUD_RATE: u32 = 115_200;
+
+// Offsets of the UART registers from the base port.
+// https://wiki.osdev.org/Serial_Ports#Port_Addresses
+const DATA_OFFSET: u16 = 0;
+const INTERRUPT_ENABLE_OFFSET: u16 = 1;
+const DIVISOR_LOW_OFFSET: u16 = 0;
+const DIVISOR_HIGH_OFFSET: u16 = 1;
+const LINE_CONTROL_OFFSET: u16 = 3;
+const MODEM_CONTROL_OFFSET: u16 = 4;
+const LINE_STATUS_OFFSET: u16 = 5;
+
+// Raises an interrupt whenever a received byte is waiting in the data register.
+const INTERRUPT_ENABLE_RECEIVED_DATA: u8 = 1 << 0;
+
+// Sends transmitted bytes straight back to the receiver instead of the line.
+const MODEM_CONTROL_LOOPBACK: u8 = 1 << 4;
+
+// Set while a received byte is waiting in the data register.
+const LINE_STATUS_DATA_READY: u8 = 1 << 0;
+
+// Setting the divisor latch access bit (DLAB) maps the divisor onto the first two registers.
+const LINE_CONTROL_DLAB: u8 = 1 << 7;
+
+// Set once both the transmit holding register and the shift register are empty.
+const LINE_STATUS_TRANSMITTER_EMPTY: u8 = 1 << 6;
+
+// The number of times the line status is polled by `flush` before giving up,
+// a disconnected or missing UART must not hang the caller.
+const FLUSH_POLL_LIMIT: usize = 100_000;
+
+/// The parity bit sent with each character.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum Parity {
+    None = 0b000,
+    Odd = 0b001,
+    Even = 0b011,
+    Mark = 0b101,
+    Space = 0b111,
+}
+
+/// The errors that can be returned when configuring the serial line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SerialConfigError {
+    // The baud rate is zero or cannot be derived from the UART clock.
+    InvalidBaudRate,
+    // The number of data bits is not within 5 and 8.
+    InvalidDataBits,
+    // The number of stop bits is not 1 or 2.
+    InvalidStopBits,
+}
+
+/// Configures the line protocol of the serial port at the given base, programming the divisor
+/// latch for the baud rate and the line control register for the frame format.
+///
+/// # Arguments
+///
+/// `base` The base I/O port of the serial port, e.g. 0x3F8 for COM1.
+/// `baud` The baud rate, must evenly divide 115200.
+/// `data_bits` The number of data bits in each character, 5 to 8.
+/// `parity` The parity bit sent with each character.
+/// `stop_bits` The number of stop bits, 1 or 2.
+///
+/// # Example
+///
+/// ```
+/// serial::configure(0x3F8, 9600, 7, Parity::Even, 1)?;
+/// ```
+pub fn configure(
+    base: u16,
+    baud: u32,
+    data_bits: u8,
+    parity: Parity,
+    stop_bits: u8,
+) -> Result<(), SerialConfigError> {
+    use x86_64::instructions::port::Port;
+
+    if baud == 0 || UART_CLOCK_BAUD_RATE % baud != 0 {
+        return Err(SerialConfigError::InvalidBaudRate);
+    }
+
+    if !(5..=8).contains(&data_bits) {
+        return Err(SerialConfigError::InvalidDataBits);
+    }
+
+    let stop_bits = match stop_bits {
+        1 => 0,
+        2 => 1 << 2,
+        _ => return Err(SerialConfigError::InvalidStopBits),
+    };
+
+    let divisor = (UART_CLOCK_BAUD_RATE / baud) as u16;
+    let line_control = (data_bits - 5) | stop_bits | (parity as u8) << 3;
+
+    let mut divisor_low: Port<u8> = Port::new(base + DIVISOR_LOW_OFFSET);
+    let mut divisor_high: Port<u8> = Port::new(base + DIVISOR_HIGH_OFFSET);
+    let mut line_control_port: Port<u8> = Port::new(base + LINE_CONTROL_OFFSET);
+
+    unsafe {
+        line_control_port.write(LINE_CONTROL_DLAB);
+        divisor_low.write(divisor as u8);
+        divisor_high.write((divisor >> 8) as u8);
+
+        // writing the frame format also clears DLAB.
+        line_control_port.write(line_control);
+    }
+
+    Ok(())
+}
+
+/// Configures the serial port at the given base for the common 115200 8N1.
+pub fn configure_default(base: u16) {
+    configure(base, DEFAULT_BAUD_RATE, 8, Parity::None, 1)
+        .expect("the default serial configuration is valid");
+}
+
+/// Waits until every byte written to the first serial port has been sent on the line, so that no
+/// output is lost when the machine is shut down straight after.
+pub fn flush() {
+    use x86_64::instructions::port::PortReadOnly;
+
+    let mut line_status: PortReadOnly<u8> = PortReadOnly::new(COM1 + LINE_STATUS_OFFSET);
+
+    for _ in 0..FLUSH_POLL_LIMIT {
+        if unsafe { line_status.read() } & LINE_STATUS_TRANSMITTER_EMPTY != 0 {
+            return;
+        }
+    }
+}
+
+/// Returns the current value of the line control register.
+pub fn read_line_control(base: u16) -> u8 {
+    use x86_64::instructions::port::Port;
+
+    let mut line_control: Port<u8> = Port::new(base + LINE_CONTROL_OFFSET);
+    unsafe { line_control.read() }
+}
+
+/// Returns the current value of the divisor latch.
+pub fn read_divisor(base: u16) -> u16 {
+    use x86_64::instructions::port::Port;
+
+    let mut divisor_low: Port<u8> = Port::new(base + DIVISOR_LOW_OFFSET);
+    let mut divisor_high: Port<u8> = Port::new(base + DIVISOR_HIGH_OFFSET);
+    let mut line_control: Port<u8> = Port::new(base + LINE_CONTROL_OFFSET);
+
+    unsafe {
+        let previous = line_control.read();
+        line_control.write(previous | LINE_CONTROL_DLAB);
+        let divisor = divisor_low.read() as u16 | (divisor_high.read() as u16) << 8;
+        line_control.write(previous);
+        divisor
+    }
+}
+
+// Tests
+
+#[test_case]
+fn test_configure_sets_divisor_and_line_control() {
+    use x86_64::instructions::interrupts;
+
+    interrupts::without_interrupts(|| {
+        // hold the port so no output is written while it is being reconfigured.
+        let _serial = serial1().lock();
+
+        configure(COM1, 9600, 7, Parity::Even, 2).expect("configure failed");
+        assert_eq!(read_divisor(COM1), 12);
+        assert_eq!(read_line_control(COM1), 0b0001_1110);
+
+        configure_default(COM1);
+        assert_eq!(read_divisor(COM1), 1);
+        assert_eq!(read_line_control(COM1), 0b0000_0011);
+    });
+
+    assert_eq!(
+        configure(COM1, 1000, 8, Parity::None, 1),
+        Err(SerialConfigError::InvalidBaudRate)
+    );
+}
+
+#[test_case]
+fn test_serial_read_byte_pops_received_bytes() {
+    for &byte in b"ok" {
+        RECEIVED.push(byte);
+    }
+
+    assert_eq!(serial_read_byte(), Some(b'o'));
+    assert_eq!(serial_read_byte(), Some(b'k'));
+    assert_eq!(serial_read_byte(), None);
+}
+
+#[test_case]
+fn test_serial_input_interrupt() {
+    use x86_64::instructions::port::Port;
+
+    // QEMU passes the host terminal to COM1 with `-serial stdio`, which the
+    // test runner cannot type into. Instead the UART is put into loopback
+    // mode so the written byte is received and raises the interrupt.
+    let mut data: Port<u8> = Port::new(COM1 + DATA_OFFSET);
+    let mut modem_control: Port<u8> = Port::new(COM1 + MODEM_CONTROL_OFFSET);
+
+    let previous = x86_64::instructions::interrupts::without_interrupts(|| {
+        let _serial = serial1().lock();
+
+        unsafe {
+            let previous = modem_control.read();
+            modem_control.write(previous | MODEM_CONTROL_LOOPBACK);
+            data.write(b'x');
+            previous
+        }
+    });
+
+    // the interrupt handler queues the byte, waiting a few timer ticks at most.
+    let mut received = None;
+    for _ in 0..10 {
+        received = serial_read_byte();
+        if received.is_some() {
+            break;
+        }
+
+        x86_64::instructions::hlt();
+    }
+
+    unsafe { modem_control.write(previous) };
+    assert_eq!(received, Some(b'x'));
+}
+
+#[test_case]
+fn test_serial2_println() {
+    crate::serial2_println!("test_serial2_println output");
+}
+
+#[test_case]
+fn test_serial_mirror() {
+    use crate::std::vga_buffer;
+    use x86_64::instructions::interrupts;
+    use x86_64::instructions::port::Port;
+
+    const LINE: &str = "mirror ok\n";
+
+    while serial_read_byte().is_some() {}
+
+    // the mirrored output is captured by looping it back into the receive
+    // queue, the line must fit into the 16 byte FIFO of the UART.
+    let mut modem_control: Port<u8> = Port::new(COM1 + MODEM_CONTROL_OFFSET);
+    interrupts::without_interrupts(|| unsafe {
+        let previous = modem_control.read();
+        modem_control.write(previous | MODEM_CONTROL_LOOPBACK);
+
+        vga_buffer::set_serial_mirror(true);
+        crate::print!("{}", LINE);
+        vga_buffer::set_serial_mirror(false);
+
+        receive_pending();
+        modem_control.write(previous);
+
+        assert!(vga_buffer::screen_contains("mirror ok"));
+    });
+
+    let mut captured = [0; LINE.len()];
+    for byte in captured.iter_mut() {
+        *byte = serial_read_byte().unwrap_or(0);
+    }
+
+    assert_eq!(&captured, LINE.as_bytes());
+}
@@ -0,0 +1,229 @@
+use core::fmt;
+use x86_64::PhysAddr;
+
+use crate::println;
+use crate::std::ata::{AtaError, DriveInfo};
+use crate::std::memory;
+
+/// The errors that can be returned when executing a shell command.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShellError {
+    // The command line did not contain a command.
+    EmptyCommand,
+    // The command is not known to the shell.
+    UnknownCommand,
+    // The command requires an argument that was not given.
+    MissingArgument,
+    // An argument was given that the command does not understand.
+    InvalidArgument,
+    // The offset of the physical memory mapping has not been recorded.
+    NoPhysicalMemoryMapping,
+    // Part of the requested physical memory range is not mapped.
+    NotMapped,
+    // No setting has the given name.
+    UnknownSetting,
+}
+
+/// Executes a single command line, the first word being the command and the rest its arguments.
+///
+/// # Arguments
+///
+/// `line` The command line to execute.
+///
+/// # Example
+///
+/// ```
+/// shell::execute("fault bp").expect("command failed");
+/// shell::execute("dump 0xb8000 160").expect("command failed");
+/// shell::execute("config timer_dots off").expect("command failed");
+/// shell::execute("disk").expect("command failed");
+/// ```
+pub fn execute(line: &str) -> Result<(), ShellError> {
+    let mut words = line.split_whitespace();
+
+    match words.next() {
+        None => Err(ShellError::EmptyCommand),
+        Some("fault") => fault(words.next()),
+        Some("dump") => {
+            let phys = parse_number(words.next())?;
+            let len = parse_number(words.next())?;
+            dump_to(&mut SerialOutput, phys, len as usize)
+        }
+        Some("config") => config_to(&mut SerialOutput, words.next(), words.next()),
+        Some("timers") => {
+            crate::std::interrupts::print_timer_callbacks();
+            Ok(())
+        }
+        Some("disk") => disk_to(&mut SerialOutput, crate::std::ata::identify()),
+        Some(_) => Err(ShellError::UnknownCommand),
+    }
+}
+
+/// Deliberately triggers the given exception to demonstrate (and smoke test) the registered
+/// exception handlers.
+///
+/// `bp` Breakpoint via int3, execution continues after the handler returns.
+/// `de` Divide by zero.
+/// `ud` Invalid opcode via ud2.
+/// `pf` Page fault by reading an unmapped address.
+/// `df` Double fault by overflowing the stack.
+fn fault(kind: Option<&str>) -> Result<(), ShellError> {
+    match kind.ok_or(ShellError::MissingArgument)? {
+        "bp" => x86_64::instructions::interrupts::int3(),
+        "de" => unsafe {
+            asm!("div {0}", in(reg) 0u64, inout("rax") 1u64 => _, inout("rdx") 0u64 => _);
+        },
+        "ud" => unsafe { asm!("ud2") },
+        "pf" => unsafe {
+            core::ptr::read_volatile(0xdead_beaf_000 as *const u64);
+        },
+        "df" => overflow_stack(),
+        _ => return Err(ShellError::InvalidArgument),
+    }
+
+    println!("fault: returned from the exception handler");
+    Ok(())
+}
+
+/// Writes a hex dump of the physical memory range `[phys, phys + len)`, after checking that every
+/// page of the range is mapped.
+fn dump_to(out: &mut impl fmt::Write, phys: u64, len: usize) -> Result<(), ShellError> {
+    let offset = memory::physical_memory_offset().ok_or(ShellError::NoPhysicalMemoryMapping)?;
+    let phys = PhysAddr::try_new(phys).map_err(|_| ShellError::InvalidArgument)?;
+
+    let data = unsafe {
+        if !memory::is_phys_range_mapped(phys, len, offset) {
+            return Err(ShellError::NotMapped);
+        }
+
+        memory::phys_slice(phys, len, offset)
+    };
+
+    crate::std::serial::write_hexdump(out, phys.as_u64(), data).map_err(|_| ShellError::InvalidArgument)
+}
+
+/// Lists every setting when no name is given, writes the value of the named setting, or changes
+/// it when a value is given as well.
+fn config_to(
+    out: &mut impl fmt::Write,
+    name: Option<&str>,
+    value: Option<&str>,
+) -> Result<(), ShellError> {
+    use crate::std::config::{self, ConfigError};
+
+    let result = match (name, value) {
+        (None, _) => return config::write_settings(out).map_err(|_| ShellError::InvalidArgument),
+        (Some(name), None) => config::get(name).map(|value| {
+            let _ = writeln!(out, "{} = {}", name, value);
+        }),
+        (Some(name), Some(value)) => config::set_from_str(name, value),
+    };
+
+    result.map_err(|error| match error {
+        ConfigError::UnknownKey => ShellError::UnknownSetting,
+        ConfigError::TypeMismatch | ConfigError::InvalidValue => ShellError::InvalidArgument,
+    })
+}
+
+/// Writes the model, serial number and size of the primary master drive, or why it could not be
+/// identified.
+fn disk_to(
+    out: &mut impl fmt::Write,
+    drive: Result<DriveInfo, AtaError>,
+) -> Result<(), ShellError> {
+    let result = match drive {
+        Ok(info) => writeln!(out, "disk: {}", info),
+        Err(error) => writeln!(out, "disk: {:?}", error),
+    };
+
+    result.map_err(|_| ShellError::InvalidArgument)
+}
+
+/// Parses a decimal or `0x` prefixed hexadecimal number.
+fn parse_number(word: Option<&str>) -> Result<u64, ShellError> {
+    let word = word.ok_or(ShellError::MissingArgument)?;
+
+    let parsed = match word.strip_prefix("0x") {
+        Some(hex) => u64::from_str_radix(hex, 16),
+        None => word.parse(),
+    };
+
+    parsed.map_err(|_| ShellError::InvalidArgument)
+}
+
+/// Writes the output of commands to the serial port.
+pub(crate) struct SerialOutput;
+
+impl fmt::Write for SerialOutput {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        crate::serial_print!("{}", s);
+        Ok(())
+    }
+}
+
+#[allow(unconditional_recursion)]
+fn overflow_stack() {
+    overflow_stack(); // for each recursion, the return address is pushed.
+    volatile::Volatile::new(0).read(); // prevent tail recursion optimizations.
+}
+
+// Tests
+
+#[test_case]
+fn test_dump_vga_buffer() {
+    use crate::std::vga_buffer::{kernel_writer, Color, ColorCode};
+    use x86_64::instructions::interrupts;
+
+    // the two characters are centered on the first row, starting at column 39.
+    interrupts::without_interrupts(|| {
+        let mut writer = kernel_writer().lock();
+        writer.write_centered(0, "ZZ", ColorCode::new(Color::Yellow, Color::Black));
+        writer.flush();
+    });
+
+    let mut out: crate::TestOutput<512> = crate::TestOutput::new();
+    dump_to(&mut out, 0xb8000 + 39 * 2, 4).expect("dump failed");
+
+    let output = out.as_str();
+    assert!(output.starts_with("00000000000b804e: 5a 0e 5a 0e "));
+    assert!(output.ends_with("|\n"));
+}
+
+#[test_case]
+fn test_dump_invalid_arguments() {
+    assert_eq!(execute("dump"), Err(ShellError::MissingArgument));
+    assert_eq!(execute("dump 0xb8000"), Err(ShellError::MissingArgument));
+    assert_eq!(execute("dump 0xzz 16"), Err(ShellError::InvalidArgument));
+}
+
+#[test_case]
+fn test_config_lists_settings() {
+    use crate::std::config;
+
+    let mut out: crate::TestOutput<1024> = crate::TestOutput::new();
+    let dots = config::get("timer_dots").expect("get failed");
+
+    config_to(&mut out, Some("timer_dots"), Some("off")).expect("set failed");
+    config_to(&mut out, None, None).expect("list failed");
+    config::set("timer_dots", dots).expect("restore failed");
+
+    let output = out.as_str();
+    assert!(output.contains("timer_dots = false"));
+    assert!(output.contains("log_level = "));
+    assert!(output.contains("serial_mirror = "));
+    assert!(output.contains("handler_benchmark = "));
+    assert!(output.contains("double_fault_exits_qemu = "));
+
+    assert_eq!(execute("config missing"), Err(ShellError::UnknownSetting));
+    assert_eq!(execute("config timer_dots maybe"), Err(ShellError::InvalidArgument));
+}
+
+#[test_case]
+fn test_disk_reports_drive() {
+    let mut out: crate::TestOutput<512> = crate::TestOutput::new();
+
+    disk_to(&mut out, Err(AtaError::NoDrive)).expect("disk failed");
+    assert_eq!(out.as_str(), "disk: NoDrive\n");
+
+    assert_eq!(execute("disk"), Ok(()));
+}
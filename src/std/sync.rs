@@ -0,0 +1,120 @@
+use spin::{Mutex, MutexGuard};
+
+use crate::std::trace;
+
+// The number of time stamp counter cycles `TryMutex::lock` waits for the
+// lock, about a second on any modern CPU. Locks are only ever held briefly and
+// there is a single CPU, so a lock that is held this long is never released.
+const DEADLOCK_TIMEOUT_CYCLES: u64 = 2_000_000_000;
+
+/// A spin lock with a name that panics instead of spinning forever when the lock is not released
+/// in time.
+///
+/// With a single CPU and no preemption, waiting on a held lock usually means the holder was
+/// interrupted by the code now waiting on it (e.g. printing from an interrupt handler while the
+/// writer is locked), which would otherwise hang the kernel silently.
+pub struct TryMutex<T> {
+    name: &'static str,
+    inner: Mutex<T>,
+}
+
+impl<T> TryMutex<T> {
+    /// Creates a new unlocked mutex.
+    ///
+    /// # Arguments
+    ///
+    /// `name` The name reported when a potential deadlock is detected.
+    /// `value` The value protected by the mutex.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// static COUNTER: TryMutex<u64> = TryMutex::new("COUNTER", 0);
+    /// ```
+    pub const fn new(name: &'static str, value: T) -> TryMutex<T> {
+        TryMutex {
+            name,
+            inner: Mutex::new(value),
+        }
+    }
+
+    /// The name reported when a potential deadlock is detected.
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// Locks the mutex, panicking with "potential deadlock on <name>" if it has not been released
+    /// after about a second.
+    pub fn lock(&self) -> MutexGuard<T> {
+        self.lock_with_timeout(DEADLOCK_TIMEOUT_CYCLES)
+    }
+
+    /// Locks the mutex, panicking with "potential deadlock on <name>" if it has not been released
+    /// within the given number of time stamp counter cycles. The time stamp counter is used
+    /// rather than the timer ticks since locks are usually taken with interrupts disabled.
+    ///
+    /// # Arguments
+    ///
+    /// `cycles` The number of cycles to wait for the lock.
+    pub fn lock_with_timeout(&self, cycles: u64) -> MutexGuard<T> {
+        let deadline = read_tsc() + cycles;
+
+        loop {
+            if let Some(guard) = self.inner.try_lock() {
+                crate::trace!(trace::LOCK_ACQUIRED, self as *const Self as u64);
+                return guard;
+            }
+
+            if read_tsc() >= deadline {
+                panic!("potential deadlock on {}", self.name);
+            }
+
+            core::hint::spin_loop();
+        }
+    }
+
+    /// Locks the mutex if it is not already locked.
+    pub fn try_lock(&self) -> Option<MutexGuard<T>> {
+        self.inner.try_lock()
+    }
+
+    /// Releases the lock regardless of who holds it, see `spin::Mutex::force_unlock`.
+    ///
+    /// This function is unsafe since the holder of the lock must never use its guard again.
+    pub unsafe fn force_unlock(&self) {
+        self.inner.force_unlock()
+    }
+}
+
+fn read_tsc() -> u64 {
+    unsafe { core::arch::x86_64::_rdtsc() }
+}
+
+// Tests
+
+#[test_case]
+fn test_try_mutex_lock_and_release() {
+    static VALUE: TryMutex<u32> = TryMutex::new("VALUE", 1);
+
+    *VALUE.lock() += 1;
+
+    let guard = VALUE.lock();
+    assert_eq!(*guard, 2);
+    assert!(VALUE.try_lock().is_none());
+
+    drop(guard);
+    assert!(VALUE.try_lock().is_some());
+}
+
+#[test_case]
+fn test_lock_is_traced() {
+    static VALUE: TryMutex<u32> = TryMutex::new("VALUE", 0);
+
+    // a timer interrupt may record its own event after the lock.
+    drop(VALUE.lock());
+
+    let address = &VALUE as *const TryMutex<u32> as u64;
+    assert!(trace::TRACE
+        .events()
+        .any(|event| event.kind == trace::LOCK_ACQUIRED && event.data == address));
+}
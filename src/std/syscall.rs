@@ -0,0 +1,263 @@
+use x86_64::VirtAddr;
+
+use crate::std::memory;
+use crate::{print, serial_print};
+
+/// The interrupt vector of the syscall entry, which user mode may issue with `int 0x80`.
+pub const SYSCALL_VECTOR: usize = 0x80;
+
+/// The number of the write syscall, matching the numbering of the i386 int 0x80 interface.
+pub const SYS_WRITE: u64 = 4;
+
+/// The file descriptors accepted by the write syscall.
+pub const STDOUT: u64 = 1;
+pub const STDERR: u64 = 2;
+
+// The first address above the lower half of the address space, user memory
+// must lie entirely below it.
+const USER_SPACE_END: u64 = 0x0000_8000_0000_0000;
+
+// The number of bytes copied from user space at a time by the write syscall.
+const WRITE_CHUNK_SIZE: usize = 256;
+
+/// The errors that can be returned by a syscall.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SyscallError {
+    // A pointer passed by the caller is not mapped or not accessible from
+    // user mode.
+    BadAddress,
+    // The file descriptor is not open.
+    BadFileDescriptor,
+    // An argument is out of range.
+    InvalidArgument,
+    // The syscall number is not known.
+    NoSuchSyscall,
+}
+
+impl SyscallError {
+    /// The negated errno value returned to user space in place of a result.
+    pub fn as_errno(&self) -> i64 {
+        match self {
+            SyscallError::BadAddress => -14,
+            SyscallError::BadFileDescriptor => -9,
+            SyscallError::InvalidArgument => -22,
+            SyscallError::NoSuchSyscall => -38,
+        }
+    }
+}
+
+/// Executes the syscall with the given number and arguments as passed in the registers by the
+/// int 0x80 entry, returning the result or the negated errno value.
+///
+/// # Arguments
+///
+/// `number` The syscall number, e.g. `SYS_WRITE`.
+/// `arg0` `arg1` `arg2` The raw argument registers.
+pub fn dispatch(number: u64, arg0: u64, arg1: u64, arg2: u64) -> i64 {
+    let result = match number {
+        SYS_WRITE => sys_write(arg0, arg1, arg2 as usize),
+        _ => Err(SyscallError::NoSuchSyscall),
+    };
+
+    match result {
+        Ok(value) => value as i64,
+        Err(error) => error.as_errno(),
+    }
+}
+
+/// The handler of the `SYSCALL_VECTOR` gate. The syscall number is passed in `rax` and the
+/// arguments in `rdi`, `rsi` and `rdx`, the result replaces `rax` and every other register is
+/// preserved.
+///
+/// A naked function since an `x86-interrupt` handler restores every register it was entered with,
+/// leaving no way to hand the result back in `rax`.
+#[naked]
+pub unsafe extern "C" fn syscall_entry() {
+    asm!(
+        // save the registers the System V ABI lets `dispatch_from_entry` clobber.
+        "push rcx",
+        "push rdx",
+        "push rsi",
+        "push rdi",
+        "push r8",
+        "push r9",
+        "push r10",
+        "push r11",
+        // move the registers into the argument order of `dispatch`.
+        "mov rcx, rdx",
+        "mov rdx, rsi",
+        "mov rsi, rdi",
+        "mov rdi, rax",
+        // the CPU leaves the stack 8 bytes off the 16 byte alignment a call
+        // requires after pushing the five quadwords of the interrupt frame.
+        "sub rsp, 8",
+        "cld",
+        "call {dispatch}",
+        "add rsp, 8",
+        "pop r11",
+        "pop r10",
+        "pop r9",
+        "pop r8",
+        "pop rdi",
+        "pop rsi",
+        "pop rdx",
+        "pop rcx",
+        "iretq",
+        dispatch = sym dispatch_from_entry,
+        options(noreturn)
+    );
+}
+
+// Called by `syscall_entry`, which needs a function with a stable calling
+// convention.
+extern "C" fn dispatch_from_entry(number: u64, arg0: u64, arg1: u64, arg2: u64) -> i64 {
+    dispatch(number, arg0, arg1, arg2)
+}
+
+/// Writes `len` bytes from the user buffer at `ptr` to the screen (stdout) or the serial port
+/// (stderr), returning the number of bytes written. Bytes outside of printable ascii are written
+/// as '?'.
+pub fn sys_write(fd: u64, ptr: u64, len: usize) -> Result<usize, SyscallError> {
+    if fd != STDOUT && fd != STDERR {
+        return Err(SyscallError::BadFileDescriptor);
+    }
+
+    // validate the whole range up front so that nothing is written for a bad
+    // range.
+    check_user_range(ptr, len)?;
+
+    let mut chunk = [0u8; WRITE_CHUNK_SIZE];
+    let mut written = 0;
+
+    while written < len {
+        let size = (len - written).min(WRITE_CHUNK_SIZE);
+        copy_from_user(ptr + written as u64, size, &mut chunk)?;
+
+        for byte in chunk[..size].iter_mut() {
+            if !matches!(byte, 0x20..=0x7e | b'\n') {
+                *byte = b'?';
+            }
+        }
+
+        // only ascii remains, the conversion can not fail.
+        let text = core::str::from_utf8(&chunk[..size]).map_err(|_| SyscallError::InvalidArgument)?;
+        match fd {
+            STDOUT => print!("{}", text),
+            _ => serial_print!("{}", text),
+        }
+
+        written += size;
+    }
+
+    Ok(written)
+}
+
+/// Copies `len` bytes from user memory at `ptr` into the start of `out`, after checking that
+/// every page of the range is present and accessible from user mode. A bad range returns an
+/// error instead of faulting or leaking kernel memory.
+///
+/// # Arguments
+///
+/// `ptr` The user space address of the first byte.
+/// `len` The number of bytes to copy, at most the length of `out`.
+/// `out` The kernel buffer the bytes are copied into.
+///
+/// # Example
+///
+/// ```
+/// let mut buffer = [0u8; 64];
+/// syscall::copy_from_user(ptr, len, &mut buffer)?;
+/// ```
+pub fn copy_from_user(ptr: u64, len: usize, out: &mut [u8]) -> Result<(), SyscallError> {
+    if len > out.len() {
+        return Err(SyscallError::InvalidArgument);
+    }
+
+    check_user_range(ptr, len)?;
+
+    unsafe {
+        core::ptr::copy_nonoverlapping(ptr as *const u8, out.as_mut_ptr(), len);
+    }
+
+    Ok(())
+}
+
+/// Checks that every page of `[ptr, ptr + len)` lies in the lower half of the address space and
+/// is mapped as user accessible.
+fn check_user_range(ptr: u64, len: usize) -> Result<(), SyscallError> {
+    if len == 0 {
+        return Ok(());
+    }
+
+    let end = ptr
+        .checked_add(len as u64)
+        .filter(|&end| end <= USER_SPACE_END)
+        .ok_or(SyscallError::BadAddress)?;
+
+    let offset = memory::physical_memory_offset().ok_or(SyscallError::BadAddress)?;
+
+    let mut page = ptr & !4095;
+    while page < end {
+        if !unsafe { memory::is_user_accessible(VirtAddr::new(page), offset) } {
+            return Err(SyscallError::BadAddress);
+        }
+
+        page += 4096;
+    }
+
+    Ok(())
+}
+
+// Tests
+
+#[test_case]
+fn test_write_bad_user_pointer() {
+    // unmapped, null and kernel memory must all be rejected without faulting.
+    static KERNEL_DATA: [u8; 16] = [b'k'; 16];
+    let kernel_ptr = KERNEL_DATA.as_ptr() as u64;
+
+    assert_eq!(dispatch(SYS_WRITE, STDOUT, 0xdead_beaf_000, 16), SyscallError::BadAddress.as_errno());
+    assert_eq!(dispatch(SYS_WRITE, STDOUT, 0, 16), SyscallError::BadAddress.as_errno());
+    assert_eq!(dispatch(SYS_WRITE, STDOUT, kernel_ptr, 16), SyscallError::BadAddress.as_errno());
+    assert_eq!(dispatch(SYS_WRITE, STDOUT, u64::MAX - 4, 16), SyscallError::BadAddress.as_errno());
+
+    let mut out = [0u8; 16];
+    assert_eq!(copy_from_user(kernel_ptr, 16, &mut out), Err(SyscallError::BadAddress));
+    assert_eq!(out, [0u8; 16]);
+}
+
+// Issues the syscall through the `int 0x80` gate, as user mode would.
+#[cfg(test)]
+fn int80(number: u64, arg0: u64, arg1: u64, arg2: u64) -> i64 {
+    let result: i64;
+    unsafe {
+        asm!(
+            "int 0x80",
+            inlateout("rax") number as i64 => result,
+            in("rdi") arg0,
+            in("rsi") arg1,
+            in("rdx") arg2,
+        )
+    };
+    result
+}
+
+#[test_case]
+fn test_write_through_int80() {
+    static KERNEL_DATA: [u8; 16] = [b'k'; 16];
+    let kernel_ptr = KERNEL_DATA.as_ptr() as u64;
+
+    // each argument register reaches `dispatch` and the result comes back in rax.
+    assert_eq!(int80(SYS_WRITE, STDOUT, 0, 0), 0);
+    assert_eq!(int80(SYS_WRITE, 7, 0, 0), SyscallError::BadFileDescriptor.as_errno());
+    assert_eq!(int80(SYS_WRITE, STDOUT, kernel_ptr, 16), SyscallError::BadAddress.as_errno());
+    assert_eq!(int80(SYS_WRITE, STDOUT, kernel_ptr, 0), 0);
+    assert_eq!(int80(0xffff, 0, 0, 0), SyscallError::NoSuchSyscall.as_errno());
+}
+
+#[test_case]
+fn test_write_bad_arguments() {
+    assert_eq!(dispatch(SYS_WRITE, 7, 0, 0), SyscallError::BadFileDescriptor.as_errno());
+    assert_eq!(dispatch(0xffff, 0, 0, 0), SyscallError::NoSuchSyscall.as_errno());
+    assert_eq!(dispatch(SYS_WRITE, STDOUT, 0, 0), 0);
+}
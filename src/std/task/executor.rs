@@ -0,0 +1,137 @@
+use alloc::collections::BTreeMap;
+use alloc::sync::Arc;
+use alloc::task::Wake;
+use core::task::{Context, Poll, Waker};
+use crossbeam_queue::ArrayQueue;
+
+use super::{Task, TaskId};
+
+/// The maximum number of woken tasks that can be waiting to be polled. Wakers push onto the queue
+/// from interrupt handlers, so it is allocated up front and never grows.
+const TASK_QUEUE_SIZE: usize = 100;
+
+/// Runs tasks cooperatively, polling each task only once it has been woken.
+pub struct Executor {
+    tasks: BTreeMap<TaskId, Task>,
+    // The identifiers of the woken tasks, shared with their wakers.
+    task_queue: Arc<ArrayQueue<TaskId>>,
+    // One waker per task, created on its first poll and reused afterwards.
+    waker_cache: BTreeMap<TaskId, Waker>,
+}
+
+impl Executor {
+    pub fn new() -> Executor {
+        Executor {
+            tasks: BTreeMap::new(),
+            task_queue: Arc::new(ArrayQueue::new(TASK_QUEUE_SIZE)),
+            waker_cache: BTreeMap::new(),
+        }
+    }
+
+    /// Adds the task to the executor, it is polled for the first time by the next run.
+    ///
+    /// # Arguments
+    ///
+    /// `task` The task being spawned.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let mut executor = Executor::new();
+    /// executor.spawn(Task::new(keyboard::print_keypresses()));
+    /// executor.run();
+    /// ```
+    pub fn spawn(&mut self, task: Task) {
+        let id = task.id;
+        if self.tasks.insert(id, task).is_some() {
+            panic!("task with same ID already in tasks");
+        }
+
+        self.task_queue.push(id).expect("task queue full");
+    }
+
+    /// Runs the tasks forever, halting the CPU whenever no task is ready to be polled.
+    pub fn run(&mut self) -> ! {
+        loop {
+            self.run_ready_tasks();
+            self.sleep_if_idle();
+        }
+    }
+
+    /// Polls every task that has been woken since the last call, removing the tasks that have
+    /// completed. Returns once no woken task is left.
+    pub fn run_ready_tasks(&mut self) {
+        // destructure self to borrow the fields separately within the loop.
+        let Executor {
+            tasks,
+            task_queue,
+            waker_cache,
+        } = self;
+
+        while let Ok(task_id) = task_queue.pop() {
+            // the task may have completed after being woken more than once.
+            let task = match tasks.get_mut(&task_id) {
+                Some(task) => task,
+                None => continue,
+            };
+
+            let waker = waker_cache
+                .entry(task_id)
+                .or_insert_with(|| TaskWaker::new(task_id, task_queue.clone()));
+            let mut context = Context::from_waker(waker);
+
+            if let Poll::Ready(()) = task.poll(&mut context) {
+                tasks.remove(&task_id);
+                waker_cache.remove(&task_id);
+            }
+        }
+    }
+
+    /// Returns the number of tasks that have not completed yet.
+    pub fn task_count(&self) -> usize {
+        self.tasks.len()
+    }
+
+    /// Halts until the next interrupt if no task has been woken. Interrupts are disabled while
+    /// checking the queue, otherwise a task woken by an interrupt right after the check would
+    /// not be polled until the next interrupt.
+    fn sleep_if_idle(&self) {
+        use x86_64::instructions::interrupts;
+
+        interrupts::disable();
+        if self.task_queue.is_empty() {
+            interrupts::enable_and_hlt();
+        } else {
+            interrupts::enable();
+        }
+    }
+}
+
+// Wakes a task by pushing its identifier onto the task queue of the executor.
+struct TaskWaker {
+    task_id: TaskId,
+    task_queue: Arc<ArrayQueue<TaskId>>,
+}
+
+impl TaskWaker {
+    fn new(task_id: TaskId, task_queue: Arc<ArrayQueue<TaskId>>) -> Waker {
+        Waker::from(Arc::new(TaskWaker {
+            task_id,
+            task_queue,
+        }))
+    }
+
+    fn wake_task(&self) {
+        self.task_queue.push(self.task_id).expect("task queue full");
+    }
+}
+
+impl Wake for TaskWaker {
+    fn wake(self: Arc<Self>) {
+        self.wake_task();
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        self.wake_task();
+    }
+}
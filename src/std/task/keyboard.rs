@@ -0,0 +1,79 @@
+use core::pin::Pin;
+use core::task::{Context, Poll};
+use futures_util::stream::{Stream, StreamExt};
+use futures_util::task::AtomicWaker;
+use pc_keyboard::DecodedKey;
+
+use crate::print;
+use crate::std::keyboard::{self, KeyEventDecoder};
+
+// The waker of the task waiting on the next scan code, woken by the keyboard
+// interrupt handler.
+static WAKER: AtomicWaker = AtomicWaker::new();
+
+/// Wakes the task waiting on the next scan code, called from the keyboard interrupt handler once
+/// a scan code has been queued.
+pub(crate) fn wake() {
+    WAKER.wake();
+}
+
+/// A stream of the raw scan codes received from the keyboard, allowing key presses to be handled
+/// in async code. The scan codes are taken from the same queue that `keyboard::poll_keyboard`
+/// decodes, so only one of the two should be used.
+pub struct ScancodeStream {
+    _private: (),
+}
+
+impl ScancodeStream {
+    pub fn new() -> ScancodeStream {
+        ScancodeStream { _private: () }
+    }
+}
+
+impl Stream for ScancodeStream {
+    type Item = u8;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<u8>> {
+        if let Some(scan_code) = keyboard::pop_scan_code() {
+            return Poll::Ready(Some(scan_code));
+        }
+
+        WAKER.register(cx.waker());
+
+        // a scan code may have been queued between the check above and the
+        // registration, in which case the waker would never be woken.
+        match keyboard::pop_scan_code() {
+            Some(scan_code) => {
+                WAKER.take();
+                Poll::Ready(Some(scan_code))
+            }
+            None => Poll::Pending,
+        }
+    }
+}
+
+/// Prints every character typed on the keyboard, an async counterpart of the polling loop in
+/// `kernel_main`.
+///
+/// # Example
+///
+/// ```
+/// executor.spawn(Task::new(keyboard::print_keypresses()));
+/// ```
+pub async fn print_keypresses() {
+    let mut scan_codes = ScancodeStream::new();
+    let mut decoder = KeyEventDecoder::new();
+
+    while let Some(scan_code) = scan_codes.next().await {
+        let event = match decoder.add_byte(scan_code) {
+            Some(event) => event,
+            None => continue,
+        };
+
+        match event.decoded {
+            Some(DecodedKey::Unicode(character)) => print!("{}", character),
+            Some(DecodedKey::RawKey(key)) => print!("{:?}", key),
+            None => {}
+        }
+    }
+}
@@ -0,0 +1,86 @@
+use alloc::collections::VecDeque;
+use alloc::sync::Arc;
+use alloc::task::Wake;
+use core::sync::atomic::{AtomicBool, Ordering};
+use core::task::{Context, Poll, Waker};
+
+use super::Task;
+
+/// Runs tasks by polling each in turn, a lighter alternative to `Executor` that does not track
+/// which task was woken. After polling every task once the CPU is halted until the next
+/// interrupt, unless a task was woken in the meantime.
+pub struct SimpleExecutor {
+    task_queue: VecDeque<Task>,
+}
+
+impl SimpleExecutor {
+    pub fn new() -> SimpleExecutor {
+        SimpleExecutor {
+            task_queue: VecDeque::new(),
+        }
+    }
+
+    /// Adds the task to the back of the queue.
+    ///
+    /// # Arguments
+    ///
+    /// `task` The task being spawned.
+    pub fn spawn(&mut self, task: Task) {
+        self.task_queue.push_back(task)
+    }
+
+    /// Polls the tasks until all of them have completed.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let mut executor = SimpleExecutor::new();
+    /// executor.spawn(Task::new(async_number_task()));
+    /// executor.run();
+    /// ```
+    pub fn run(&mut self) {
+        use x86_64::instructions::interrupts;
+
+        // every task shares the same waker, which only records that a task
+        // was woken since the last pass.
+        let woken = Arc::new(WokenFlag(AtomicBool::new(false)));
+        let waker = Waker::from(woken.clone());
+        let mut context = Context::from_waker(&waker);
+
+        while !self.task_queue.is_empty() {
+            for _ in 0..self.task_queue.len() {
+                let mut task = match self.task_queue.pop_front() {
+                    Some(task) => task,
+                    None => break,
+                };
+
+                if let Poll::Pending = task.poll(&mut context) {
+                    self.task_queue.push_back(task);
+                }
+            }
+
+            // interrupts are disabled while checking the flag, otherwise a wake
+            // from an interrupt right after the check would be slept through.
+            interrupts::disable();
+            if self.task_queue.is_empty() || woken.0.swap(false, Ordering::SeqCst) {
+                interrupts::enable();
+            } else {
+                interrupts::enable_and_hlt();
+            }
+        }
+    }
+}
+
+// Set by any task being woken, the simple executor polls every task again
+// regardless of which one it was.
+struct WokenFlag(AtomicBool);
+
+impl Wake for WokenFlag {
+    fn wake(self: Arc<Self>) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+}
@@ -0,0 +1,193 @@
+use core::future::Future;
+use core::pin::Pin;
+use core::task::{Context, Poll, Waker};
+use spin::Mutex;
+
+use crate::std::clock::ticks;
+use crate::std::interrupts::ms_to_ticks;
+
+/// The maximum number of sleeping tasks that can be waiting on the timer at once.
+const MAX_TIMERS: usize = 32;
+
+struct Timer {
+    // The tick at which the waker is woken.
+    due: u64,
+    waker: Waker,
+}
+
+const NO_TIMER: Option<Timer> = None;
+
+// The sleeping tasks waiting to be woken by the timer interrupt handler. Only
+// ever locked with interrupts disabled, since the handler locks it as well.
+static TIMERS: Mutex<[Option<Timer>; MAX_TIMERS]> = Mutex::new([NO_TIMER; MAX_TIMERS]);
+
+/// A future that completes once the clock has reached the due tick.
+pub struct Sleep {
+    due: u64,
+}
+
+/// Returns a future that completes after at least `ms` milliseconds, rounded up to whole timer
+/// ticks. This is the async counterpart of a blocking sleep, other tasks keep running while the
+/// sleeping task waits to be woken by the timer interrupt handler.
+///
+/// # Arguments
+///
+/// `ms` The number of milliseconds to sleep for.
+///
+/// # Example
+///
+/// ```
+/// timer::sleep(20).await;
+/// ```
+pub fn sleep(ms: u64) -> Sleep {
+    Sleep {
+        due: ticks() + ms_to_ticks(ms),
+    }
+}
+
+impl Future for Sleep {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<()> {
+        if ticks() >= self.due {
+            return Poll::Ready(());
+        }
+
+        register(self.due, cx.waker());
+
+        // the due tick may have passed between the check above and the
+        // registration, in which case the waker would never be woken.
+        if ticks() >= self.due {
+            Poll::Ready(())
+        } else {
+            Poll::Pending
+        }
+    }
+}
+
+/// Registers the waker to be woken at the due tick, replacing any earlier registration of the
+/// same waker. If all slots are in use the waker is woken immediately, the task will then be
+/// polled again and retry the registration.
+fn register(due: u64, waker: &Waker) {
+    use x86_64::instructions::interrupts;
+
+    interrupts::without_interrupts(|| {
+        let mut timers = TIMERS.lock();
+
+        let existing = timers
+            .iter()
+            .position(|slot| matches!(slot, Some(timer) if timer.waker.will_wake(waker)));
+
+        match existing.or_else(|| timers.iter().position(|slot| slot.is_none())) {
+            Some(index) => {
+                timers[index] = Some(Timer {
+                    due,
+                    waker: waker.clone(),
+                })
+            }
+            None => waker.wake_by_ref(),
+        }
+    });
+}
+
+/// Wakes every task whose due tick has been reached, called from the timer interrupt handler.
+///
+/// # Arguments
+///
+/// `now` The current value of the tick counter.
+pub fn wake_due(now: u64) {
+    // the handler may have interrupted a registration, in which case the due
+    // tasks are woken on the next tick instead.
+    let mut timers = match TIMERS.try_lock() {
+        Some(timers) => timers,
+        None => return,
+    };
+
+    for slot in timers.iter_mut() {
+        if matches!(slot, Some(timer) if timer.due <= now) {
+            if let Some(timer) = slot.take() {
+                timer.waker.wake();
+            }
+        }
+    }
+}
+
+// Tests
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use core::sync::atomic::{AtomicBool, Ordering};
+    use core::task::{RawWaker, RawWakerVTable};
+
+    // A waker that sets the flag it points to when woken.
+    fn flag_waker(flag: &'static AtomicBool) -> Waker {
+        fn clone(data: *const ()) -> RawWaker {
+            RawWaker::new(data, &VTABLE)
+        }
+
+        fn wake(data: *const ()) {
+            unsafe { &*(data as *const AtomicBool) }.store(true, Ordering::SeqCst);
+        }
+
+        fn drop_waker(_data: *const ()) {}
+
+        static VTABLE: RawWakerVTable = RawWakerVTable::new(clone, wake, wake, drop_waker);
+
+        let raw = RawWaker::new(flag as *const AtomicBool as *const (), &VTABLE);
+        unsafe { Waker::from_raw(raw) }
+    }
+
+    #[test_case]
+    fn test_sleep_resumes_after_due_tick() {
+        static SLEEPER_WOKEN: AtomicBool = AtomicBool::new(true);
+        static SPINNER_WOKEN: AtomicBool = AtomicBool::new(true);
+
+        let sleeper_waker = flag_waker(&SLEEPER_WOKEN);
+        let spinner_waker = flag_waker(&SPINNER_WOKEN);
+
+        let start = ticks();
+        let mut sleeper = sleep(20);
+        let mut spinner_polls = 0u64;
+
+        // a minimal round robin executor, the sleeper is only polled once it
+        // has been woken while the spinner runs continuously.
+        loop {
+            if SLEEPER_WOKEN.swap(false, Ordering::SeqCst) {
+                let mut cx = Context::from_waker(&sleeper_waker);
+                if Pin::new(&mut sleeper).poll(&mut cx).is_ready() {
+                    break;
+                }
+            }
+
+            SPINNER_WOKEN.store(false, Ordering::SeqCst);
+            spinner_polls += 1;
+            spinner_waker.wake_by_ref();
+        }
+
+        assert!(ticks() >= start + ms_to_ticks(20));
+        assert!(spinner_polls > 0);
+    }
+
+    #[test_case]
+    fn test_sleep_with_fake_clock() {
+        use crate::std::clock::{self, FakeClock};
+
+        static FAKE: FakeClock = FakeClock::new();
+        static WOKEN: AtomicBool = AtomicBool::new(false);
+
+        clock::set_clock(&FAKE);
+
+        let waker = flag_waker(&WOKEN);
+        let mut cx = Context::from_waker(&waker);
+        let mut sleeper = sleep(10_000);
+
+        assert!(Pin::new(&mut sleeper).poll(&mut cx).is_pending());
+
+        // no real time passes, the sleep completes as soon as the clock moves.
+        FAKE.advance(ms_to_ticks(10_000));
+        assert!(Pin::new(&mut sleeper).poll(&mut cx).is_ready());
+
+        clock::set_clock(&clock::PIT_CLOCK);
+    }
+}
@@ -0,0 +1,190 @@
+use core::sync::atomic::{AtomicU64, AtomicU8, AtomicUsize, Ordering};
+
+use crate::serial_println;
+
+/// The number of events kept in the trace ring buffer before the oldest are overwritten.
+pub const TRACE_BUFFER_SIZE: usize = 64;
+
+/// Event kind recorded on entry to the timer interrupt handler.
+pub const TIMER_INTERRUPT: u8 = 1;
+/// Event kind recorded on entry to the keyboard interrupt handler, data is the scan code.
+pub const KEYBOARD_INTERRUPT: u8 = 2;
+/// Event kind recorded when a `TryMutex` has been locked, data is the address of the mutex.
+pub const LOCK_ACQUIRED: u8 = 3;
+/// Event kind recorded on every heap allocation, data is the requested size in bytes.
+pub const ALLOCATION: u8 = 4;
+
+/// Records an event into the global trace buffer.
+///
+/// # Example
+///
+/// ```
+/// trace!(trace::KEYBOARD_INTERRUPT, scan_code);
+/// ```
+#[macro_export]
+macro_rules! trace {
+    ($kind:expr, $data:expr) => {
+        $crate::std::trace::record($kind, $data as u64)
+    };
+}
+
+/// A single recorded event, the tick is the value of the CPU time stamp counter at the point
+/// the event was recorded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TraceEvent {
+    pub tick: u64,
+    pub kind: u8,
+    pub data: u64,
+}
+
+// The storage for a single event. Each field is atomic so that the buffer can be written from
+// interrupt handlers without taking a lock, at the cost of a reader possibly observing a slot
+// that is halfway through being overwritten.
+struct TraceSlot {
+    tick: AtomicU64,
+    kind: AtomicU8,
+    data: AtomicU64,
+}
+
+impl TraceSlot {
+    const fn new() -> TraceSlot {
+        TraceSlot {
+            tick: AtomicU64::new(0),
+            kind: AtomicU8::new(0),
+            data: AtomicU64::new(0),
+        }
+    }
+}
+
+/// A fixed size ring buffer of the most recent events, used to build a timeline of what
+/// happened just before a crash without the overhead of printing live.
+pub struct TraceBuffer {
+    slots: [TraceSlot; TRACE_BUFFER_SIZE],
+    // The total number of events ever recorded, the slot to write is this modulo the size.
+    next: AtomicUsize,
+}
+
+impl TraceBuffer {
+    pub const fn new() -> TraceBuffer {
+        const EMPTY: TraceSlot = TraceSlot::new();
+
+        TraceBuffer {
+            slots: [EMPTY; TRACE_BUFFER_SIZE],
+            next: AtomicUsize::new(0),
+        }
+    }
+
+    /// Records a new event, overwriting the oldest event once the buffer is full. The write
+    /// index is claimed with a single atomic add so concurrent writers never share a slot.
+    ///
+    /// # Arguments
+    ///
+    /// `kind` The kind of the event being recorded.
+    /// `data` Any additional data related to the event.
+    pub fn record(&self, kind: u8, data: u64) {
+        let index = self.next.fetch_add(1, Ordering::Relaxed);
+        let slot = &self.slots[index % TRACE_BUFFER_SIZE];
+
+        slot.tick.store(read_tick(), Ordering::Relaxed);
+        slot.kind.store(kind, Ordering::Relaxed);
+        slot.data.store(data, Ordering::Release);
+    }
+
+    /// Returns the number of events currently held in the buffer.
+    pub fn len(&self) -> usize {
+        core::cmp::min(self.next.load(Ordering::Acquire), TRACE_BUFFER_SIZE)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns an iterator over the held events, ordered from the oldest to the newest.
+    pub fn events(&self) -> impl Iterator<Item = TraceEvent> + '_ {
+        let next = self.next.load(Ordering::Acquire);
+        let start = next.saturating_sub(TRACE_BUFFER_SIZE);
+
+        (start..next).map(move |index| {
+            let slot = &self.slots[index % TRACE_BUFFER_SIZE];
+
+            TraceEvent {
+                tick: slot.tick.load(Ordering::Relaxed),
+                kind: slot.kind.load(Ordering::Relaxed),
+                data: slot.data.load(Ordering::Acquire),
+            }
+        })
+    }
+
+    /// Writes all held events to the serial port, oldest first.
+    pub fn dump(&self) {
+        serial_println!("trace: {} events", self.len());
+
+        for event in self.events() {
+            serial_println!(
+                "  tick={} kind={} data={:#x}",
+                event.tick,
+                event.kind,
+                event.data
+            );
+        }
+    }
+}
+
+/// The global trace buffer written to by the `trace!` macro.
+pub static TRACE: TraceBuffer = TraceBuffer::new();
+
+/// Records an event into the global trace buffer.
+pub fn record(kind: u8, data: u64) {
+    TRACE.record(kind, data);
+}
+
+/// Writes the global trace buffer to the serial port.
+pub fn dump() {
+    TRACE.dump();
+}
+
+fn read_tick() -> u64 {
+    // reading the time stamp counter has no side effects, its only unsafe
+    // since the instruction is not available on every target.
+    unsafe { core::arch::x86_64::_rdtsc() }
+}
+
+// Tests
+
+#[test_case]
+fn test_trace_records_events_in_order() {
+    let buffer = TraceBuffer::new();
+    assert!(buffer.is_empty());
+
+    for data in 0..5 {
+        buffer.record(TIMER_INTERRUPT, data);
+    }
+
+    assert_eq!(buffer.len(), 5);
+    for (expected, event) in buffer.events().enumerate() {
+        assert_eq!(event.kind, TIMER_INTERRUPT);
+        assert_eq!(event.data, expected as u64);
+    }
+
+    buffer.dump();
+}
+
+#[test_case]
+fn test_trace_wraps_when_full() {
+    let buffer = TraceBuffer::new();
+    let total = TRACE_BUFFER_SIZE as u64 + 3;
+
+    for data in 0..total {
+        buffer.record(KEYBOARD_INTERRUPT, data);
+    }
+
+    // the three oldest events have been overwritten, the remainder
+    // must still be returned from the oldest to the newest.
+    assert_eq!(buffer.len(), TRACE_BUFFER_SIZE);
+    for (offset, event) in buffer.events().enumerate() {
+        assert_eq!(event.data, 3 + offset as u64);
+    }
+
+    let last = buffer.events().last().expect("buffer should not be empty");
+    assert_eq!(last.data, total - 1);
+}
@@ -1,6 +1,16 @@
 use core::fmt;
+use core::sync::atomic::{AtomicBool, Ordering};
 use spin::Mutex;
 use volatile::Volatile;
+use x86_64::instructions::port::{Port, PortWriteOnly};
+use x86_64::{PhysAddr, VirtAddr};
+
+use crate::std::console;
+use crate::std::memory;
+use crate::std::once::Once;
+use crate::std::panic::lock_for_print;
+use crate::std::sync::TryMutex;
+use crate::std::vga_graphics::{self, GraphicsError};
 
 /// The assigned u8 representation of the vga color assignment, this is the color that would be
 /// assigned to the given text being written to the display.
@@ -37,6 +47,11 @@ impl ColorCode {
     pub fn new(foreground: Color, background: Color) -> ColorCode {
         ColorCode((background as u8) << 4 | (foreground as u8))
     }
+
+    /// Returns the color code with the foreground color replaced, keeping the background.
+    pub fn with_foreground(self, foreground: Color) -> ColorCode {
+        ColorCode((self.0 & 0xF0) | foreground as u8)
+    }
 }
 
 /// Since the field ordering in default structs is undefined in Rust, we need the repr(C) attribute.
@@ -44,9 +59,9 @@ impl ColorCode {
 /// guarantees the correct field ordering.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 #[repr(C)]
-struct ScreenCharacter {
-    ascii_character: u8,
-    color_code: ColorCode,
+pub struct ScreenCharacter {
+    pub ascii_character: u8,
+    pub color_code: ColorCode,
 }
 
 /// There are modes with a character box width of 9 dots (e.g. the default 80×25 mode), however the
@@ -55,245 +70,1560 @@ struct ScreenCharacter {
 const TEXT_BUFFER_HEIGHT: usize = 25;
 const TEXT_BUFFER_WIDTH: usize = 80;
 
+/// The number of rows of the largest supported text mode, the buffer is sized to hold them.
+const MAX_TEXT_BUFFER_HEIGHT: usize = 50;
+
+/// The number of columns and rows of a text mode. The VGA buffer is sized for the largest
+/// supported mode, and a writer only uses the part of it within its dimensions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VgaDimensions {
+    pub width: usize,
+    pub height: usize,
+}
+
+impl VgaDimensions {
+    /// The default text mode, with characters 16 scanlines high.
+    pub const TEXT_80X25: VgaDimensions = VgaDimensions {
+        width: TEXT_BUFFER_WIDTH,
+        height: TEXT_BUFFER_HEIGHT,
+    };
+
+    /// The text mode set by `set_80x50_mode`, with characters 8 scanlines high.
+    pub const TEXT_80X50: VgaDimensions = VgaDimensions {
+        width: TEXT_BUFFER_WIDTH,
+        height: MAX_TEXT_BUFFER_HEIGHT,
+    };
+
+    // Limits the dimensions to the size of the VGA buffer, keeping at least
+    // one row and column.
+    fn clamped(self) -> VgaDimensions {
+        VgaDimensions {
+            width: self.width.max(1).min(TEXT_BUFFER_WIDTH),
+            height: self.height.max(1).min(MAX_TEXT_BUFFER_HEIGHT),
+        }
+    }
+}
+
+/// The number of columns between tab stops.
+const TAB_WIDTH: usize = 8;
+
+/// The bytes sent by the backspace and delete keys, both remove the last character.
+const BACKSPACE: u8 = 0x08;
+const DELETE: u8 = 0x7f;
+
+/// The code page 437 glyphs of a single lined box, drawn by `Writer::draw_box`.
+const BOX_TOP_LEFT: u8 = 0xDA;
+const BOX_TOP_RIGHT: u8 = 0xBF;
+const BOX_BOTTOM_LEFT: u8 = 0xC0;
+const BOX_BOTTOM_RIGHT: u8 = 0xD9;
+const BOX_HORIZONTAL: u8 = 0xC4;
+const BOX_VERTICAL: u8 = 0xB3;
+
+/// The ANSI escape sequence that erases the whole display, recognized by `Writer::write_string`.
+const ANSI_CLEAR_SCREEN: &[u8] = b"\x1b[2J";
+
+/// The number of rows that scrolled off the top of the screen kept by a `Scrollback`.
+pub const SCROLLBACK_ROWS: usize = 200;
+
+/// A ring buffer of the rows that scrolled off the top of the screen, oldest first, once full
+/// the oldest row is overwritten.
+pub struct Scrollback {
+    rows: [[ScreenCharacter; TEXT_BUFFER_WIDTH]; SCROLLBACK_ROWS],
+    start: usize,
+    len: usize,
+}
+
+impl Scrollback {
+    pub const fn new() -> Scrollback {
+        Scrollback {
+            rows: [[BLANK_CHARACTER; TEXT_BUFFER_WIDTH]; SCROLLBACK_ROWS],
+            start: 0,
+            len: 0,
+        }
+    }
+
+    /// The number of rows currently kept.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns true if no row has scrolled off the screen yet.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    fn push(&mut self, row: [ScreenCharacter; TEXT_BUFFER_WIDTH]) {
+        if self.len < SCROLLBACK_ROWS {
+            self.rows[(self.start + self.len) % SCROLLBACK_ROWS] = row;
+            self.len += 1;
+        } else {
+            self.rows[self.start] = row;
+            self.start = (self.start + 1) % SCROLLBACK_ROWS;
+        }
+    }
+
+    // The row at the given index, zero being the oldest row kept.
+    fn row(&self, index: usize) -> &[ScreenCharacter; TEXT_BUFFER_WIDTH] {
+        &self.rows[(self.start + index) % SCROLLBACK_ROWS]
+    }
+}
+
 /// The text buffer for the vga input, ensure to keep the same memory layout as a char array of u8
 /// instead of the memory layout with the pointer information that would be set by rust.
 #[repr(transparent)]
 pub struct Buffer {
-    chars: [[Volatile<ScreenCharacter>; TEXT_BUFFER_WIDTH]; TEXT_BUFFER_HEIGHT],
+    chars: [[Volatile<ScreenCharacter>; TEXT_BUFFER_WIDTH]; MAX_TEXT_BUFFER_HEIGHT],
+}
+
+// The physical address of the VGA text buffer, of which the bootloader only
+// identity maps the first page, enough for the 25 rows of 80x25 text mode.
+const BUFFER_ADDRESS: u64 = 0xb8000;
+
+/// Returns the VGA text buffer accessed through the complete physical memory mapping, which
+/// unlike the identity mapped page at 0xb8000 covers all rows of 80x50 text mode.
+///
+/// This function is unsafe because the caller must guarantee that the complete physical memory
+/// is mapped at the passed `physical_memory_offset`. Like the buffer at 0xb8000 it is shared by
+/// every writer, which must only write to it while shown.
+pub unsafe fn mapped_buffer(physical_memory_offset: VirtAddr) -> &'static mut Buffer {
+    let buffer = memory::phys_slice_mut(
+        PhysAddr::new(BUFFER_ADDRESS),
+        core::mem::size_of::<Buffer>(),
+        physical_memory_offset,
+    );
+    &mut *(buffer.as_mut_ptr() as *mut Buffer)
 }
 
+/// A blank character used to fill the back buffer before it has been written to.
+const BLANK_CHARACTER: ScreenCharacter = ScreenCharacter {
+    ascii_character: b' ',
+    color_code: ColorCode(0),
+};
+
 /// The 'static lifetime specifies that the reference is valid for the whole program run time (which
 /// is true for the VGA text buffer).
+///
+/// When batched, all writes go into the in memory back buffer instead of the VGA buffer, and only
+/// reach the screen once `flush` is called. This coalesces many scrolls into a single screen update
+/// when printing large amounts of output.
+///
+/// When hidden, all writes go into the back buffer as well and nothing reaches the screen until
+/// the writer is shown again, allowing several writers to share the screen (see `console`).
+///
+/// With a scrollback, rows that scroll off the top of the screen are kept and can be viewed
+/// again with `scroll_up`. While scrolled back all writes go into the back buffer, and the first
+/// write snaps the view back to the bottom.
 pub struct Writer {
     pub column_position: usize,
+    // The row the next byte is written to, the bottom row unless moved with
+    // `set_position`. New lines move down a row until the bottom is reached.
+    row_position: usize,
     pub color_code: ColorCode,
     pub buffer: &'static mut Buffer,
+    // Set once the last column of the row has been written, the next byte
+    // then starts a new line. This keeps the column within the row while still
+    // allowing a full row to be followed by a newline without a blank row.
+    wrap_pending: bool,
+    batched: bool,
+    hidden: bool,
+    dirty: bool,
+    back_buffer: [[ScreenCharacter; TEXT_BUFFER_WIDTH]; MAX_TEXT_BUFFER_HEIGHT],
+    dimensions: VgaDimensions,
+    scrollback: Option<&'static mut Scrollback>,
+    // The number of rows the view is scrolled back from the bottom.
+    scroll_offset: usize,
 }
 
 impl Writer {
-    /// Writes each byte of the input_string into the vga buffer, the input screen buffer values
-    /// must be within the given of 0x20 -> 0x7e.
+    /// Returns a new writer over the given buffer with batching disabled, using the default 80x25
+    /// text mode.
+    pub fn new(color_code: ColorCode, buffer: &'static mut Buffer) -> Writer {
+        Writer::with_dimensions(color_code, buffer, VgaDimensions::TEXT_80X25)
+    }
+
+    /// Returns a new writer over the given buffer with batching disabled, using only the rows and
+    /// columns within the given dimensions. The dimensions are limited to the size of the buffer.
     ///
     /// # Arguments
     ///
-    /// `input_string` The string that is going to be written to the output.
+    /// `color_code` The color of the written text.
+    /// `buffer` The VGA buffer written to.
+    /// `dimensions` The number of columns and rows of the current text mode.
     ///
     /// # Example
     ///
     /// ```
-    /// writer.write_string("Hello, World");
+    /// let writer = Writer::with_dimensions(color_code, buffer, VgaDimensions::TEXT_80X50);
     /// ```
-    pub fn write_string(&mut self, input_string: &str) {
-        for byte in input_string.bytes() {
-            match byte {
-                0x20..=0x7e => self.write_byte(byte),
-                b'\n' => self.new_line(),
-                _ => self.write_byte(0xfe),
-            }
+    pub fn with_dimensions(
+        color_code: ColorCode,
+        buffer: &'static mut Buffer,
+        dimensions: VgaDimensions,
+    ) -> Writer {
+        let dimensions = dimensions.clamped();
+
+        Writer {
+            column_position: 0,
+            row_position: dimensions.height - 1,
+            color_code,
+            buffer,
+            wrap_pending: false,
+            batched: false,
+            hidden: false,
+            dirty: false,
+            back_buffer: [[BLANK_CHARACTER; TEXT_BUFFER_WIDTH]; MAX_TEXT_BUFFER_HEIGHT],
+            dimensions,
+            scrollback: None,
+            scroll_offset: 0,
         }
     }
 
-    /// Writes the specified byte into the VGA buffer, if the byte is a new line then ensures to
-    /// create a new line, otherwise if the buffer is going to overflow, insert a new line.
+    /// Returns the writer keeping the rows that scroll off the screen in the given scrollback.
+    pub fn with_scrollback(self, scrollback: &'static mut Scrollback) -> Writer {
+        Writer {
+            scrollback: Some(scrollback),
+            ..self
+        }
+    }
+
+    /// Returns a new hidden writer over the given buffer with a blank back buffer, the buffer is
+    /// only written to once the writer is shown with `set_hidden`.
+    pub fn new_hidden(color_code: ColorCode, buffer: &'static mut Buffer) -> Writer {
+        Writer {
+            hidden: true,
+            ..Writer::new(color_code, buffer)
+        }
+    }
+
+    /// Changes the color of all following output, the existing text keeps its color.
     ///
     /// # Arguments
     ///
-    /// `byte` - The byte that will be written into the vga buffer.
+    /// `foreground` The color of the characters.
+    /// `background` The color behind the characters.
     ///
     /// # Example
     ///
     /// ```
-    /// writer::write_byte(b'\n');
+    /// writer.set_color(Color::Red, Color::Black);
     /// ```
-    pub fn write_byte(&mut self, byte: u8) {
-        match byte {
-            b'\n' => self.new_line(),
-            byte => {
-                // If the given column is going to overflow by meeting the max current buffer width
-                // insert a new line before continuing. Otherwise continue as normal.
-                if self.column_position >= TEXT_BUFFER_WIDTH {
-                    self.new_line()
-                }
+    pub fn set_color(&mut self, foreground: Color, background: Color) {
+        self.color_code = ColorCode::new(foreground, background);
+    }
 
-                // TODO: Missing support for blinking?
-                self.buffer.chars[TEXT_BUFFER_HEIGHT - 1][self.column_position].write(
-                    ScreenCharacter {
-                        ascii_character: byte,
-                        color_code: self.color_code,
-                    },
-                );
+    /// The number of columns in the text buffer.
+    pub fn width(&self) -> usize {
+        self.dimensions.width
+    }
 
-                self.column_position += 1;
-            }
-        }
+    /// The number of rows in the text buffer.
+    pub fn height(&self) -> usize {
+        self.dimensions.height
     }
 
-    /// Inserts a new line at the bottom of th VGA buffer by shifting all rows up one and clearing
-    /// the bottom row by inserting all spaces. Finally resetting back to the starting position.
+    /// Replaces the VGA buffer written to, e.g. with the `mapped_buffer` before switching to 80x50
+    /// text mode. Both buffers must be the same memory, the screen is not redrawn.
+    pub fn set_buffer(&mut self, buffer: &'static mut Buffer) {
+        self.buffer = buffer;
+    }
+
+    /// Changes the number of columns and rows used, e.g. after switching text modes. The text
+    /// within both the old and new dimensions is kept, the rest of the screen is cleared. The
+    /// position of the next byte is moved within the new dimensions.
+    ///
+    /// # Arguments
+    ///
+    /// `dimensions` The number of columns and rows of the new text mode, limited to the size of
+    /// the buffer.
     ///
     /// # Example
     ///
     /// ```
-    /// writer.write_string("Hello, World\n");
+    /// writer.set_dimensions(VgaDimensions::TEXT_80X50);
     /// ```
-    fn new_line(&mut self) {
-        for row in 1..TEXT_BUFFER_HEIGHT {
-            for col in 0..TEXT_BUFFER_WIDTH {
-                let char = self.buffer.chars[row][col].read();
-                self.buffer.chars[row - 1][col].write(char);
+    pub fn set_dimensions(&mut self, dimensions: VgaDimensions) {
+        let dimensions = dimensions.clamped();
+        let previous = self.dimensions;
+
+        self.scroll_to_bottom();
+        self.dimensions = dimensions;
+
+        let blank = ScreenCharacter {
+            ascii_character: b' ',
+            color_code: self.color_code,
+        };
+
+        // the cells outside of the previous dimensions hold stale content.
+        for row in 0..dimensions.height {
+            let first = if row < previous.height {
+                previous.width
+            } else {
+                0
+            };
+
+            for col in first..dimensions.width {
+                self.write_character(row, col, blank);
             }
         }
 
-        self.clear_row(TEXT_BUFFER_HEIGHT - 1);
-        self.column_position = 0;
+        self.row_position = self.row_position.min(dimensions.height - 1);
+        self.column_position = self.column_position.min(dimensions.width - 1);
+        self.wrap_pending = false;
+        self.update_cursor();
     }
 
-    /// Replaces all characters in the given row with spaces, called after a newline has been
-    /// written into the buffer.
+    /// Returns true if the text is shown anywhere on the screen. The rows are read one after the
+    /// other, so text that wrapped onto the next row is found as well. Used by tests to check
+    /// what reached the screen, whichever writer is shown.
+    ///
+    /// # Arguments
+    ///
+    /// `text` The text searched for.
+    pub fn screen_contains(&self, text: &str) -> bool {
+        let text = text.as_bytes();
+        let width = self.width();
+        let cells = width * self.height();
+        let visible = |i: usize| {
+            self.buffer.chars[i / width][i % width]
+                .read()
+                .ascii_character
+        };
+
+        text.len() <= cells
+            && (0..=cells - text.len()).any(|start| {
+                text.iter()
+                    .enumerate()
+                    .all(|(i, &byte)| visible(start + i) == byte)
+            })
+    }
+
+    /// Enables or disables batched output. Enabling copies the current screen into the back
+    /// buffer, disabling flushes any pending output to the screen.
+    ///
+    /// # Arguments
+    ///
+    /// `batched` If writes should be held in the back buffer until the next flush.
+    pub fn set_batched(&mut self, batched: bool) {
+        if batched == self.batched {
+            return;
+        }
+
+        self.scroll_to_bottom();
+
+        // a hidden writer already holds its content in the back buffer.
+        if batched && !self.hidden {
+            for row in 0..self.dimensions.height {
+                for col in 0..self.dimensions.width {
+                    self.back_buffer[row][col] = self.buffer.chars[row][col].read();
+                }
+            }
+        } else {
+            self.flush();
+        }
+
+        self.batched = batched;
+    }
+
+    /// Returns true if writes are currently being held in the back buffer.
+    pub fn is_batched(&self) -> bool {
+        self.batched
+    }
+
+    /// Hides or shows the writer. Hiding copies the current screen into the back buffer, where all
+    /// further writes are held, showing copies the back buffer onto the screen.
+    ///
+    /// # Arguments
+    ///
+    /// `hidden` If the writer should stop writing to the screen.
+    pub fn set_hidden(&mut self, hidden: bool) {
+        if hidden == self.hidden {
+            return;
+        }
+
+        self.scroll_to_bottom();
+
+        if hidden {
+            if !self.batched {
+                for row in 0..self.dimensions.height {
+                    for col in 0..self.dimensions.width {
+                        self.back_buffer[row][col] = self.buffer.chars[row][col].read();
+                    }
+                }
+            }
+        } else {
+            for row in 0..self.dimensions.height {
+                for col in 0..self.dimensions.width {
+                    self.buffer.chars[row][col].write(self.back_buffer[row][col]);
+                }
+            }
+
+            self.dirty = false;
+        }
+
+        self.hidden = hidden;
+        self.update_cursor();
+    }
+
+    /// Moves the hardware cursor to the position the next byte will be written to. Does nothing
+    /// while the writer is hidden, the cursor belongs to the writer that is shown.
+    pub fn update_cursor(&self) {
+        if self.hidden {
+            return;
+        }
+
+        // the cursor is skipped rather than waited on if the controller is in
+        // use, e.g. by code interrupted by a panic while changing the shape.
+        let position = self.row_position * TEXT_BUFFER_WIDTH + self.column_position;
+        if let Some(mut crtc) = CRTC.try_lock() {
+            set_cursor_position_with(&mut *crtc, position as u16);
+        }
+    }
+
+    /// Hides the hardware cursor, e.g. during graphical output, until `show_cursor` is called.
+    /// Does nothing while the writer is hidden, the cursor belongs to the writer that is shown.
     ///
     /// # Example
     ///
     /// ```
-    /// writer.write_string("Hello, World\n");
+    /// writer.hide_cursor();
     /// ```
-    fn clear_row(&mut self, row: usize) {
-        let blank = ScreenCharacter {
-            ascii_character: b' ',
-            color_code: self.color_code,
-        };
+    pub fn hide_cursor(&mut self) {
+        if self.hidden {
+            return;
+        }
 
-        for col in 0..TEXT_BUFFER_WIDTH {
-            self.buffer.chars[row][col].write(blank);
+        if let Some(mut crtc) = CRTC.try_lock() {
+            hide_cursor_with(&mut *crtc);
         }
     }
-}
 
-impl fmt::Write for Writer {
-    /// Write a string to the VGA Buffer using the fmt::write implementation to provide formatting.
-    /// This will use continue to write to vga buffer as a standard string output when formatted.
+    /// Shows the hardware cursor again, covering the given scanlines of the character cell.
+    /// Does nothing while the writer is hidden.
     ///
     /// # Arguments
     ///
-    /// `s` The string input being written to the VGA buffer.
+    /// `start` The first scanline covered by the cursor, from 0 to 15.
+    /// `end` The last scanline covered by the cursor, from 0 to 15.
     ///
     /// # Example
     ///
     /// ```
-    /// write!(WRITER.lock(), "some numbers: {} {}", 42, 1.23244").unwrap();
+    /// // an underline cursor.
+    /// writer.show_cursor(14, 15);
     /// ```
-    fn write_str(&mut self, s: &str) -> fmt::Result {
-        self.write_string(s);
-        Ok(())
+    pub fn show_cursor(&mut self, start: u8, end: u8) {
+        if self.hidden {
+            return;
+        }
+
+        if let Some(mut crtc) = CRTC.try_lock() {
+            show_cursor_with(&mut *crtc, start, end);
+        }
     }
-}
 
-#[macro_export]
-macro_rules! print {
-    ($($arg:tt)*) => ($crate::std::vga_buffer::_print(format_args!($($arg)*)));
-}
+    /// Returns true if the writer is not currently writing to the screen.
+    pub fn is_hidden(&self) -> bool {
+        self.hidden
+    }
 
-#[macro_export]
-macro_rules! println {
-    () => ($crate::print!("\n"));
-    ($($arg:tt)*) => ($crate::print!("{}\n", format_args!($($arg)*)));
-}
+    /// Copies the back buffer to the VGA buffer if anything has been written since the last
+    /// flush. Does nothing when batching is disabled since all writes already reach the screen,
+    /// or while the writer is hidden or scrolled back.
+    pub fn flush(&mut self) {
+        if self.hidden || !self.batched || !self.dirty || self.scroll_offset > 0 {
+            return;
+        }
 
-#[doc(hidden)]
-pub fn _print(args: core::fmt::Arguments) {
-    use core::fmt::Write;
-    use x86_64::instructions::interrupts;
+        for row in 0..self.dimensions.height {
+            for col in 0..self.dimensions.width {
+                self.buffer.chars[row][col].write(self.back_buffer[row][col]);
+            }
+        }
 
-    // only  lock the vga writer a time when  no interrupts
-    // are occurring. This is use to stop the  chance of the
-    // spin  lock causing  a dead lock between the interrupt
-    // and the kernal.
-    //
-    // The without_interrupts function takes a closure and
-    // executes it in an interrupt-free environment. We use
-    // it to ensure that no interrupt can occur as long as
-    // the Mutex is locked.
-    interrupts::without_interrupts(|| {
-        WRITER.lock().write_fmt(args).unwrap();
-    })
-}
+        self.dirty = false;
+    }
 
-// By introducing the writer as a global static, it begins to ensure that more problems occur. By
-// having a global static means that you cannot easily have mutual exclusion. And need to
-// synchronize. Mutable statics are one way but this is highly discouraged.
-//
-// Instead we are going to be using spin locks to provide safe interior mutability within the
-// static writer.
-lazy_static::lazy_static! {
-     pub static ref WRITER: Mutex<Writer> = Mutex::new(Writer {
-        column_position: 0,
-        color_code: ColorCode::new(Color::Yellow, Color::Black),
-        buffer: unsafe { &mut *(0xb8000 as *mut Buffer) },
-   });
-}
+    /// Scrolls the view back by the given number of rows, showing the rows that scrolled off the
+    /// top of the screen. Stops at the oldest row kept, and does nothing without a scrollback or
+    /// while hidden.
+    ///
+    /// # Arguments
+    ///
+    /// `lines` The number of rows to scroll back by.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// writer.scroll_up(writer.height() / 2);
+    /// ```
+    pub fn scroll_up(&mut self, lines: usize) {
+        let kept = match &self.scrollback {
+            Some(scrollback) if !self.hidden => scrollback.len(),
+            _ => return,
+        };
 
-#[cfg(test)]
-#[macro_use]
-mod test {
-    use crate::println;
-    #[cfg(test)]
-    use crate::std::vga_buffer::*;
+        let offset = (self.scroll_offset + lines).min(kept);
+        if offset == self.scroll_offset {
+            return;
+        }
 
-    #[test_case]
-    fn test_println_single_line() {
-        println!("test_println_single_line output");
+        // the live screen is held in the back buffer while scrolled back.
+        if self.scroll_offset == 0 && !self.batched {
+            for row in 0..self.dimensions.height {
+                for col in 0..self.dimensions.width {
+                    self.back_buffer[row][col] = self.buffer.chars[row][col].read();
+                }
+            }
+        }
+
+        self.scroll_offset = offset;
+        self.draw_scrolled();
     }
 
-    #[test_case]
-    fn test_println_many_short() {
-        for _ in 0..200 {
-            println!("test_println_many_short output");
+    /// Scrolls the view forward by the given number of rows, towards the live output.
+    ///
+    /// # Arguments
+    ///
+    /// `lines` The number of rows to scroll forward by.
+    pub fn scroll_down(&mut self, lines: usize) {
+        if self.scroll_offset == 0 {
+            return;
         }
-    }
 
-    #[test_case]
-    fn test_println_many_long() {
-        for _ in 0..200 {
-            println!(
-                "test_println_many_long output test_println_many_long output \
-            test_println_many_long output test_println_many_long output test_println_many_long \
-            output test_println_many_long output"
-            );
+        if lines >= self.scroll_offset {
+            self.scroll_to_bottom();
+        } else {
+            self.scroll_offset -= lines;
+            self.draw_scrolled();
         }
     }
 
-    #[test_case]
-    fn test_println_single_line_output() {
-        use core::fmt::Write;
-        use x86_64::instructions::interrupts;
+    /// Shows the live output again if the view is scrolled back.
+    pub fn scroll_to_bottom(&mut self) {
+        if self.scroll_offset == 0 {
+            return;
+        }
 
-        let s = "some test string that fits on a single line";
-        interrupts::without_interrupts(|| {
-            let mut writer = WRITER.lock();
-            writeln!(writer, "\n{}", s).expect("writeln failed");
-            for (i, c) in s.chars().enumerate() {
-                let screen_char = writer.buffer.chars[TEXT_BUFFER_HEIGHT - 2][i].read();
-                assert_eq!(char::from(screen_char.ascii_character), c);
+        self.scroll_offset = 0;
+        for row in 0..self.dimensions.height {
+            for col in 0..self.dimensions.width {
+                self.buffer.chars[row][col].write(self.back_buffer[row][col]);
             }
-        });
-    }
+        }
 
-    #[test_case]
-    fn test_println_long_should_overflow() {
-        use core::fmt::Write;
-        use x86_64::instructions::interrupts;
+        self.dirty = false;
+    }
 
-        let input =
-            " Some test string that fits on a single  Some test string that fits on a single ";
+    /// The number of rows the view is currently scrolled back by.
+    pub fn scroll_offset(&self) -> usize {
+        self.scroll_offset
+    }
 
-        interrupts::without_interrupts(|| {
-            let mut writer = WRITER.lock();
+    /// Draws the rows visible at the current scroll offset, taken from the scrollback followed by
+    /// the live screen in the back buffer.
+    fn draw_scrolled(&mut self) {
+        let scrollback = match &self.scrollback {
+            Some(scrollback) => scrollback,
+            None => return,
+        };
 
-            writeln!(writer, "\n{}", input).expect("writeln failed");
-            writeln!(writer, "{}", input).expect("writeln failed");
-            writeln!(writer, "{}", input).expect("writeln failed");
-            writeln!(writer, "").expect("writeln failed");
+        let dimensions = self.dimensions;
+        let first = scrollback.len() - self.scroll_offset;
+        for row in 0..dimensions.height {
+            let index = first + row;
+            let characters = if index < scrollback.len() {
+                scrollback.row(index)
+            } else {
+                &self.back_buffer[index - scrollback.len()]
+            };
 
-            for i in 1..4 {
+            for (col, character) in characters.iter().take(dimensions.width).enumerate() {
+                self.buffer.chars[row][col].write(*character);
+            }
+        }
+    }
+
+    /// Returns true if writes go into the back buffer instead of the VGA buffer.
+    fn is_buffered(&self) -> bool {
+        self.batched || self.hidden || self.scroll_offset > 0
+    }
+
+    /// Reads the character at the given position from the back buffer when batched, hidden or
+    /// scrolled back, otherwise from the VGA buffer.
+    fn read_character(&self, row: usize, col: usize) -> ScreenCharacter {
+        if self.is_buffered() {
+            self.back_buffer[row][col]
+        } else {
+            self.buffer.chars[row][col].read()
+        }
+    }
+
+    /// Writes the character at the given position into the back buffer when batched or hidden,
+    /// otherwise directly into the VGA buffer. New output snaps a scrolled back view to the bottom.
+    fn write_character(&mut self, row: usize, col: usize, character: ScreenCharacter) {
+        self.scroll_to_bottom();
+
+        if self.batched || self.hidden {
+            self.back_buffer[row][col] = character;
+            self.dirty = true;
+        } else {
+            self.buffer.chars[row][col].write(character);
+        }
+    }
+
+    /// Runs the closure on every cell of the screen, passing the row, column and a copy of the
+    /// character in the cell. Any changes made to the copy are written back to the cell, which
+    /// allows for screen wide effects such as color washes or case conversion.
+    ///
+    /// # Arguments
+    ///
+    /// `f` The closure run on each cell, from the top left to the bottom right.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// writer.for_each_cell(|_, _, cell| cell.color_code = ColorCode::new(Color::Red, Color::Black));
+    /// ```
+    pub fn for_each_cell(&mut self, mut f: impl FnMut(usize, usize, &mut ScreenCharacter)) {
+        for row in 0..self.dimensions.height {
+            for col in 0..self.dimensions.width {
+                let mut character = self.read_character(row, col);
+                f(row, col, &mut character);
+                self.write_character(row, col, character);
+            }
+        }
+    }
+
+    /// Writes the string centered horizontally within the given row, without moving the cursor
+    /// used by the flowing output. A string wider than the row starts at the first column and is
+    /// clipped at the end of the row.
+    ///
+    /// # Arguments
+    ///
+    /// `row` The row the string is written to, rows outside of the buffer are ignored.
+    /// `s` The string being written, an empty string writes nothing.
+    /// `color` The color of the written string.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// writer.write_centered(0, "Operating System", ColorCode::new(Color::White, Color::Blue));
+    /// ```
+    pub fn write_centered(&mut self, row: usize, s: &str, color: ColorCode) {
+        let start = self.dimensions.width.saturating_sub(s.len()) / 2;
+        self.write_at(row, start, s, color);
+    }
+
+    /// Writes the string starting at the given position, without moving the cursor used by the
+    /// flowing output. The string is clipped at the end of the row instead of wrapping.
+    ///
+    /// # Arguments
+    ///
+    /// `row` The row the string is written to, rows outside of the buffer are ignored.
+    /// `col` The column of the first character, columns outside of the buffer are ignored.
+    /// `s` The string being written.
+    /// `color` The color of the written string.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// writer.write_at(0, 70, "12:00:00", ColorCode::new(Color::Black, Color::LightGray));
+    /// ```
+    pub fn write_at(&mut self, row: usize, col: usize, s: &str, color: ColorCode) {
+        if row >= self.dimensions.height {
+            return;
+        }
+
+        for (col, byte) in (col..self.dimensions.width).zip(s.bytes()) {
+            let ascii_character = match byte {
+                0x20..=0x7e => byte,
+                _ => 0xfe,
+            };
+
+            self.write_character(
+                row,
+                col,
+                ScreenCharacter {
+                    ascii_character,
+                    color_code: color,
+                },
+            );
+        }
+    }
+
+    /// Fills the rectangle with the given code page 437 character, without moving the cursor used
+    /// by the flowing output. The parts of the rectangle outside of the screen are clipped.
+    ///
+    /// # Arguments
+    ///
+    /// `top` The row of the top edge of the rectangle.
+    /// `left` The column of the left edge of the rectangle.
+    /// `height` The number of rows filled.
+    /// `width` The number of columns filled.
+    /// `character` The code page 437 character written into every cell.
+    /// `color` The color of the written characters.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// // a blue panel.
+    /// writer.fill_rect(2, 10, 5, 30, b' ', ColorCode::new(Color::White, Color::Blue));
+    /// ```
+    pub fn fill_rect(
+        &mut self,
+        top: usize,
+        left: usize,
+        height: usize,
+        width: usize,
+        character: u8,
+        color: ColorCode,
+    ) {
+        let bottom = top.saturating_add(height).min(self.dimensions.height);
+        let right = left.saturating_add(width).min(self.dimensions.width);
+
+        for row in top..bottom {
+            for col in left..right {
+                self.write_character(
+                    row,
+                    col,
+                    ScreenCharacter {
+                        ascii_character: character,
+                        color_code: color,
+                    },
+                );
+            }
+        }
+    }
+
+    /// Draws the outline of a box with single lined code page 437 glyphs, without moving the
+    /// cursor used by the flowing output or touching the inside of the box. The parts of the box
+    /// outside of the screen are clipped, and boxes smaller than 2x2 are not drawn.
+    ///
+    /// # Arguments
+    ///
+    /// `top` The row of the top edge of the box.
+    /// `left` The column of the left edge of the box.
+    /// `height` The number of rows of the box, including both edges.
+    /// `width` The number of columns of the box, including both edges.
+    /// `color` The color of the outline.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// writer.draw_box(2, 10, 5, 30, ColorCode::new(Color::White, Color::Blue));
+    /// ```
+    pub fn draw_box(
+        &mut self,
+        top: usize,
+        left: usize,
+        height: usize,
+        width: usize,
+        color: ColorCode,
+    ) {
+        if height < 2 || width < 2 {
+            return;
+        }
+
+        // a box starting off the screen has nothing to draw, which also keeps
+        // the edges below from overflowing.
+        if top >= self.dimensions.height || left >= self.dimensions.width {
+            return;
+        }
+
+        let bottom = top.saturating_add(height - 1);
+        let right = left.saturating_add(width - 1);
+
+        self.fill_rect(top, left + 1, 1, width - 2, BOX_HORIZONTAL, color);
+        self.fill_rect(bottom, left + 1, 1, width - 2, BOX_HORIZONTAL, color);
+        self.fill_rect(top + 1, left, height - 2, 1, BOX_VERTICAL, color);
+        self.fill_rect(top + 1, right, height - 2, 1, BOX_VERTICAL, color);
+
+        self.fill_rect(top, left, 1, 1, BOX_TOP_LEFT, color);
+        self.fill_rect(top, right, 1, 1, BOX_TOP_RIGHT, color);
+        self.fill_rect(bottom, left, 1, 1, BOX_BOTTOM_LEFT, color);
+        self.fill_rect(bottom, right, 1, 1, BOX_BOTTOM_RIGHT, color);
+    }
+
+    /// Writes each byte of the input_string into the vga buffer, the input screen buffer values
+    /// must be within the given of 0x20 -> 0x7e.
+    ///
+    /// # Arguments
+    ///
+    /// `input_string` The string that is going to be written to the output.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// writer.write_string("Hello, World");
+    /// ```
+    pub fn write_string(&mut self, input_string: &str) {
+        let mut bytes = input_string.as_bytes();
+
+        while let Some((&byte, rest)) = bytes.split_first() {
+            // the ANSI erase display sequence clears the screen.
+            if let Some(rest) = bytes.strip_prefix(ANSI_CLEAR_SCREEN) {
+                self.clear_screen();
+                bytes = rest;
+                continue;
+            }
+
+            match byte {
+                0x20..=0x7e | BACKSPACE | DELETE | b'\t' => self.write_byte(byte),
+                b'\n' => self.new_line(),
+                _ => self.write_byte(0xfe),
+            }
+
+            bytes = rest;
+        }
+    }
+
+    /// Replaces every character on the screen with a space and moves the cursor back to the
+    /// start of the bottom row, where the next output is written.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// writer.clear_screen();
+    /// ```
+    pub fn clear_screen(&mut self) {
+        for row in 0..self.dimensions.height {
+            self.clear_row(row);
+        }
+
+        self.column_position = 0;
+        self.row_position = self.dimensions.height - 1;
+        self.wrap_pending = false;
+        self.update_cursor();
+    }
+
+    /// Moves the position the next byte is written to, clamping it to the screen. Output then
+    /// continues from there, moving down a row on every new line until the bottom row is reached,
+    /// after which the screen scrolls as usual.
+    ///
+    /// # Arguments
+    ///
+    /// `row` - The row of the position, 0 being the top row.
+    /// `col` - The column of the position, 0 being the leftmost column.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// writer.set_position(5, 10);
+    /// writer.write_string("Score: 42");
+    /// ```
+    pub fn set_position(&mut self, row: usize, col: usize) {
+        self.row_position = row.min(self.dimensions.height - 1);
+        self.column_position = col.min(self.dimensions.width - 1);
+        self.wrap_pending = false;
+        self.update_cursor();
+    }
+
+    /// Returns the row and column the next byte is written to.
+    pub fn position(&self) -> (usize, usize) {
+        (self.row_position, self.column_position)
+    }
+
+    /// Writes the specified byte into the VGA buffer, if the byte is a new line then ensures to
+    /// create a new line, otherwise if the buffer is going to overflow, insert a new line.
+    ///
+    /// # Arguments
+    ///
+    /// `byte` - The byte that will be written into the vga buffer.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// writer::write_byte(b'\n');
+    /// ```
+    pub fn write_byte(&mut self, byte: u8) {
+        match byte {
+            b'\n' => self.new_line(),
+            BACKSPACE | DELETE => self.backspace(),
+            b'\t' => self.tab(),
+            byte => self.write_raw(byte),
+        }
+    }
+
+    /// Writes the byte verbatim as a code page 437 glyph, without interpreting control characters
+    /// such as new lines, so that the full character set (e.g. box drawing characters) can be
+    /// drawn. The output wraps at the end of the row like any other character.
+    ///
+    /// # Arguments
+    ///
+    /// `byte` - The code page 437 character written into the vga buffer.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// // the top left corner of a double lined box.
+    /// writer.write_raw(0xC9);
+    /// ```
+    pub fn write_raw(&mut self, byte: u8) {
+        // If the given column is going to overflow by meeting the max current buffer width
+        // insert a new line before continuing. Otherwise continue as normal.
+        if self.wrap_pending || self.column_position >= self.dimensions.width {
+            self.new_line()
+        }
+
+        // TODO: Missing support for blinking?
+        self.write_character(
+            self.row_position,
+            self.column_position,
+            ScreenCharacter {
+                ascii_character: byte,
+                color_code: self.color_code,
+            },
+        );
+
+        if self.column_position + 1 < self.dimensions.width {
+            self.column_position += 1;
+        } else {
+            self.wrap_pending = true;
+        }
+
+        self.update_cursor();
+    }
+
+    /// Writes each byte verbatim as a code page 437 glyph, see `write_raw`.
+    ///
+    /// # Arguments
+    ///
+    /// `bytes` - The code page 437 characters written into the vga buffer.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// writer.write_raw_bytes(&[0xC9, 0xCD, 0xCD, 0xBB]);
+    /// ```
+    pub fn write_raw_bytes(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.write_raw(byte);
+        }
+    }
+
+    /// Writes spaces up to the next tab stop, at least one. A tab at the end of the row moves to
+    /// the start of the next line, the next tab stop.
+    fn tab(&mut self) {
+        if self.wrap_pending {
+            self.new_line();
+            return;
+        }
+
+        loop {
+            self.write_byte(b' ');
+
+            if self.wrap_pending || self.column_position % TAB_WIDTH == 0 {
+                break;
+            }
+        }
+    }
+
+    /// Removes the last character written on the current row, moving the column back by one. At
+    /// the start of the row nothing happens, the cursor does not move back to the previous line.
+    fn backspace(&mut self) {
+        // after writing the last column the column stays on it, that
+        // character is the one removed.
+        if self.wrap_pending {
+            self.wrap_pending = false;
+        } else if self.column_position > 0 {
+            self.column_position -= 1;
+        } else {
+            return;
+        }
+
+        self.write_character(
+            self.row_position,
+            self.column_position,
+            ScreenCharacter {
+                ascii_character: b' ',
+                color_code: self.color_code,
+            },
+        );
+        self.update_cursor();
+    }
+
+    /// Inserts a new line at the bottom of th VGA buffer by shifting all rows up one and clearing
+    /// the bottom row by inserting all spaces. Finally resetting back to the starting position.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// writer.write_string("Hello, World\n");
+    /// ```
+    fn new_line(&mut self) {
+        if self.row_position < self.dimensions.height - 1 {
+            self.row_position += 1;
+            self.column_position = 0;
+            self.wrap_pending = false;
+            self.update_cursor();
+            return;
+        }
+
+        if self.scrollback.is_some() {
+            let mut top = [BLANK_CHARACTER; TEXT_BUFFER_WIDTH];
+            for (col, character) in top.iter_mut().enumerate() {
+                *character = self.read_character(0, col);
+            }
+
+            if let Some(scrollback) = self.scrollback.as_mut() {
+                scrollback.push(top);
+            }
+        }
+
+        for row in 1..self.dimensions.height {
+            for col in 0..self.dimensions.width {
+                let char = self.read_character(row, col);
+                self.write_character(row - 1, col, char);
+            }
+        }
+
+        self.clear_row(self.dimensions.height - 1);
+        self.column_position = 0;
+        self.wrap_pending = false;
+        self.update_cursor();
+    }
+
+    /// Replaces all characters in the given row with spaces, called after a newline has been
+    /// written into the buffer.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// writer.write_string("Hello, World\n");
+    /// ```
+    fn clear_row(&mut self, row: usize) {
+        let blank = ScreenCharacter {
+            ascii_character: b' ',
+            color_code: self.color_code,
+        };
+
+        for col in 0..self.dimensions.width {
+            self.write_character(row, col, blank);
+        }
+    }
+}
+
+impl fmt::Write for Writer {
+    /// Write a string to the VGA Buffer using the fmt::write implementation to provide formatting.
+    /// This will use continue to write to vga buffer as a standard string output when formatted.
+    ///
+    /// # Arguments
+    ///
+    /// `s` The string input being written to the VGA buffer.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// write!(kernel_writer().lock(), "some numbers: {} {}", 42, 1.23244).unwrap();
+    /// ```
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.write_string(s);
+        Ok(())
+    }
+}
+
+#[macro_export]
+macro_rules! print {
+    ($($arg:tt)*) => ($crate::std::vga_buffer::_print(format_args!($($arg)*)));
+}
+
+#[macro_export]
+macro_rules! println {
+    () => ($crate::print!("\n"));
+    ($($arg:tt)*) => ($crate::print!("{}\n", format_args!($($arg)*)));
+}
+
+/// Prints a line to the screen in the given foreground color, keeping the current background.
+/// The previous color is restored afterwards.
+///
+/// # Example
+///
+/// ```
+/// cprintln!(Color::Red, "warning: {} frames left", frames);
+/// ```
+#[macro_export]
+macro_rules! cprintln {
+    ($color:expr) => ($crate::std::vga_buffer::_cprint($color, format_args!("\n")));
+    ($color:expr, $($arg:tt)*) => (
+        $crate::std::vga_buffer::_cprint($color, format_args!("{}\n", format_args!($($arg)*)))
+    );
+}
+
+#[doc(hidden)]
+pub fn _cprint(foreground: Color, args: core::fmt::Arguments) {
+    use x86_64::instructions::interrupts;
+
+    // the color is changed and restored within the same critical section, so
+    // no other output can be written in the temporary color.
+    interrupts::without_interrupts(|| {
+        crate::std::klog::_append(args);
+        console::with_selected(|console| console.write_colored(foreground, args)).unwrap();
+        mirror_to_serial(args);
+    })
+}
+
+#[doc(hidden)]
+pub fn _print(args: core::fmt::Arguments) {
+    use core::fmt::Write;
+    use x86_64::instructions::interrupts;
+
+    // only  lock the vga writer a time when  no interrupts
+    // are occurring. This is use to stop the  chance of the
+    // spin  lock causing  a dead lock between the interrupt
+    // and the kernal.
+    //
+    // The without_interrupts function takes a closure and
+    // executes it in an interrupt-free environment. We use
+    // it to ensure that no interrupt can occur as long as
+    // the Mutex is locked.
+    interrupts::without_interrupts(|| {
+        crate::std::klog::_append(args);
+        console::with_selected(|console| console.write_fmt(args)).unwrap();
+        mirror_to_serial(args);
+    })
+}
+
+// Set while the screen output is mirrored to the serial port.
+static SERIAL_MIRROR: AtomicBool = AtomicBool::new(false);
+
+// Set while mirrored output is being written to the serial port, so that
+// output printed to the screen from within the serial path is not mirrored
+// back to it.
+static MIRRORING: AtomicBool = AtomicBool::new(false);
+
+/// Enables or disables mirroring the output of `print!`, `println!` and `cprintln!` to the
+/// first serial port, so that it can be scrolled through on the host. Output is only mirrored
+/// while the serial port is not locked, it is dropped rather than waited on.
+///
+/// # Arguments
+///
+/// `enabled` True to mirror the following output.
+///
+/// # Example
+///
+/// ```
+/// vga_buffer::set_serial_mirror(true);
+/// println!("shown on the screen and on the host");
+/// ```
+pub fn set_serial_mirror(enabled: bool) {
+    SERIAL_MIRROR.store(enabled, Ordering::SeqCst);
+}
+
+/// Returns true if the screen output is mirrored to the serial port.
+pub fn serial_mirror() -> bool {
+    SERIAL_MIRROR.load(Ordering::SeqCst)
+}
+
+// Writes the screen output to the serial port as well if mirroring is enabled.
+fn mirror_to_serial(args: core::fmt::Arguments) {
+    if !serial_mirror() || MIRRORING.swap(true, Ordering::SeqCst) {
+        return;
+    }
+
+    crate::std::serial::try_print(args);
+    MIRRORING.store(false, Ordering::SeqCst);
+}
+
+/// Prints to the screen unless the writer is already locked, returning false if nothing was
+/// printed. Used by exception handlers that may have interrupted code holding the writer lock,
+/// where waiting on the lock would dead lock.
+pub fn try_print(args: core::fmt::Arguments) -> bool {
+    use core::fmt::Write;
+    use x86_64::instructions::interrupts;
+
+    interrupts::without_interrupts(|| match kernel_writer().try_lock() {
+        Some(mut writer) => writer.write_fmt(args).is_ok(),
+        None => false,
+    })
+}
+
+/// Changes the color of the following output of the global writer, see `Writer::set_color`.
+pub fn set_color(foreground: Color, background: Color) {
+    use x86_64::instructions::interrupts;
+
+    interrupts::without_interrupts(|| {
+        lock_for_print(kernel_writer()).set_color(foreground, background);
+    })
+}
+
+/// Clears the screen of the global writer, see `Writer::clear_screen`.
+pub fn clear_screen() {
+    use x86_64::instructions::interrupts;
+
+    interrupts::without_interrupts(|| {
+        lock_for_print(kernel_writer()).clear_screen();
+    })
+}
+
+/// Writes the string at the given position of the global writer, see `Writer::write_at`.
+pub fn write_at(row: usize, col: usize, s: &str, color: ColorCode) {
+    use x86_64::instructions::interrupts;
+
+    interrupts::without_interrupts(|| {
+        lock_for_print(kernel_writer()).write_at(row, col, s, color);
+    })
+}
+
+/// Fills a rectangle of the global writer, see `Writer::fill_rect`.
+pub fn fill_rect(
+    top: usize,
+    left: usize,
+    height: usize,
+    width: usize,
+    character: u8,
+    color: ColorCode,
+) {
+    use x86_64::instructions::interrupts;
+
+    interrupts::without_interrupts(|| {
+        lock_for_print(kernel_writer()).fill_rect(top, left, height, width, character, color);
+    })
+}
+
+/// Draws the outline of a box on the global writer, see `Writer::draw_box`.
+pub fn draw_box(top: usize, left: usize, height: usize, width: usize, color: ColorCode) {
+    use x86_64::instructions::interrupts;
+
+    interrupts::without_interrupts(|| {
+        lock_for_print(kernel_writer()).draw_box(top, left, height, width, color);
+    })
+}
+
+/// Returns true if the text is shown anywhere on the screen, see `Writer::screen_contains`.
+pub fn screen_contains(text: &str) -> bool {
+    use x86_64::instructions::interrupts;
+
+    interrupts::without_interrupts(|| lock_for_print(kernel_writer()).screen_contains(text))
+}
+
+/// Hides the hardware cursor of the global writer, see `Writer::hide_cursor`.
+pub fn hide_cursor() {
+    use x86_64::instructions::interrupts;
+
+    interrupts::without_interrupts(|| lock_for_print(kernel_writer()).hide_cursor())
+}
+
+/// Shows the hardware cursor of the global writer, see `Writer::show_cursor`.
+pub fn show_cursor(start: u8, end: u8) {
+    use x86_64::instructions::interrupts;
+
+    interrupts::without_interrupts(|| lock_for_print(kernel_writer()).show_cursor(start, end))
+}
+
+/// Enables or disables batched output on the global writer, see `Writer::set_batched`.
+pub fn set_batched(batched: bool) {
+    use x86_64::instructions::interrupts;
+
+    interrupts::without_interrupts(|| lock_for_print(kernel_writer()).set_batched(batched))
+}
+
+/// Flushes any batched output of the global writer to the screen. This is called on every timer
+/// tick, bounding the refresh rate to the timer frequency, and before exiting QEMU so that no
+/// output is lost.
+///
+/// If the writer is already locked the flush is skipped, the holder of the lock may be the code
+/// that was interrupted (or that panicked) and waiting on it would dead lock. The pending output
+/// will be written by the next flush.
+pub fn flush() {
+    use x86_64::instructions::interrupts;
+
+    interrupts::without_interrupts(|| {
+        if let Some(mut writer) = kernel_writer().try_lock() {
+            writer.flush();
+        }
+    })
+}
+
+// The index and data ports of the CRT controller, the register to access is
+// selected by writing its index to the index port.
+// https://wiki.osdev.org/Text_Mode_Cursor
+const CRTC_INDEX_PORT: u16 = 0x3D4;
+const CRTC_DATA_PORT: u16 = 0x3D5;
+
+const CRTC_MAXIMUM_SCAN_LINE: u8 = 0x09;
+const CRTC_CURSOR_START: u8 = 0x0A;
+const CRTC_CURSOR_END: u8 = 0x0B;
+const CRTC_CURSOR_LOCATION_HIGH: u8 = 0x0E;
+const CRTC_CURSOR_LOCATION_LOW: u8 = 0x0F;
+
+// The scanline bits of the cursor start and end registers, and the bit of
+// the start register that hides the cursor.
+const CURSOR_SCANLINE_MASK: u8 = 0x1F;
+const CURSOR_DISABLE: u8 = 1 << 5;
+
+// The bits of the maximum scan line register holding the number of scanlines
+// of a character minus one.
+const CHARACTER_HEIGHT_MASK: u8 = 0x1F;
+
+// The height of the characters in 80x25 and 80x50 text mode, both modes
+// display 400 scanlines.
+const SCANLINES_80X25: u8 = 16;
+const SCANLINES_80X50: u8 = 8;
+
+/// The shapes the hardware cursor can take, given as the first and last scanline of the 16
+/// scanline character cell it covers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CursorShape {
+    // The bottom two scanlines, e.g. for insert mode.
+    Underline,
+    // The full character cell, e.g. for overwrite mode.
+    Block,
+    // No cursor is drawn.
+    Hidden,
+}
+
+/// The registers of the CRT controller, allowing the cursor programming to be checked against a
+/// fake controller during testing.
+pub trait Crtc {
+    fn read_register(&mut self, index: u8) -> u8;
+    fn write_register(&mut self, index: u8, value: u8);
+}
+
+/// The CRT controller accessed through its I/O ports.
+pub struct PortCrtc {
+    index: PortWriteOnly<u8>,
+    data: Port<u8>,
+}
+
+impl PortCrtc {
+    /// This function is unsafe since the caller must guarantee that a VGA compatible CRT
+    /// controller exists at the standard color ports.
+    pub const unsafe fn new() -> PortCrtc {
+        PortCrtc {
+            index: PortWriteOnly::new(CRTC_INDEX_PORT),
+            data: Port::new(CRTC_DATA_PORT),
+        }
+    }
+}
+
+impl Crtc for PortCrtc {
+    fn read_register(&mut self, index: u8) -> u8 {
+        unsafe {
+            self.index.write(index);
+            self.data.read()
+        }
+    }
+
+    fn write_register(&mut self, index: u8, value: u8) {
+        unsafe {
+            self.index.write(index);
+            self.data.write(value);
+        }
+    }
+}
+
+pub static CRTC: Mutex<PortCrtc> = Mutex::new(unsafe { PortCrtc::new() });
+
+/// Sets the shape of the hardware cursor.
+///
+/// # Arguments
+///
+/// `shape` The new shape of the cursor.
+///
+/// # Example
+///
+/// ```
+/// // overwrite mode uses a block cursor.
+/// vga_buffer::set_cursor_shape(CursorShape::Block);
+/// ```
+pub fn set_cursor_shape(shape: CursorShape) {
+    use x86_64::instructions::interrupts;
+
+    interrupts::without_interrupts(|| set_cursor_shape_with(&mut *CRTC.lock(), shape))
+}
+
+/// Moves the hardware cursor to the given linear position (row * width + column) through the
+/// given controller.
+pub fn set_cursor_position_with(crtc: &mut impl Crtc, position: u16) {
+    crtc.write_register(CRTC_CURSOR_LOCATION_HIGH, (position >> 8) as u8);
+    crtc.write_register(CRTC_CURSOR_LOCATION_LOW, position as u8);
+}
+
+/// Sets the shape of the hardware cursor through the given controller, see `set_cursor_shape`.
+pub fn set_cursor_shape_with(crtc: &mut impl Crtc, shape: CursorShape) {
+    match shape {
+        CursorShape::Underline => show_cursor_with(crtc, 14, 15),
+        CursorShape::Block => show_cursor_with(crtc, 0, 15),
+        CursorShape::Hidden => hide_cursor_with(crtc),
+    }
+}
+
+/// Sets the number of scanlines of each character through the given controller, which decides
+/// the number of rows shown on the screen.
+pub fn set_character_height_with(crtc: &mut impl Crtc, scanlines: u8) {
+    // the upper bits of the register control line doubling and must be preserved.
+    let current = crtc.read_register(CRTC_MAXIMUM_SCAN_LINE);
+    crtc.write_register(
+        CRTC_MAXIMUM_SCAN_LINE,
+        (current & !CHARACTER_HEIGHT_MASK) | (scanlines.wrapping_sub(1) & CHARACTER_HEIGHT_MASK),
+    );
+}
+
+/// Switches the screen to 80x50 text mode by halving the height of the characters to 8
+/// scanlines, loading a font to match with `vga_graphics::load_8x8_font`, and updates every
+/// console to use all 50 rows. The cursor is reset to an underline.
+///
+/// The rows below the 25th lie past the page the bootloader identity maps, so every console is
+/// moved to the `mapped_buffer`. Fails with `GraphicsError::NotMapped` if the offset of the
+/// complete physical memory mapping has not been recorded with
+/// `memory::set_physical_memory_offset`, in which case the text mode is left unchanged.
+///
+/// # Example
+///
+/// ```
+/// vga_buffer::set_80x50_mode()?;
+/// vga_buffer::write_at(40, 0, "row 40", color);
+/// ```
+pub fn set_80x50_mode() -> Result<(), GraphicsError> {
+    let physical_memory_offset =
+        memory::physical_memory_offset().ok_or(GraphicsError::NotMapped)?;
+    let buffer = PhysAddr::new(BUFFER_ADDRESS);
+    let size = core::mem::size_of::<Buffer>();
+    if !unsafe { memory::is_phys_range_mapped(buffer, size, physical_memory_offset) } {
+        return Err(GraphicsError::NotMapped);
+    }
+
+    vga_graphics::load_8x8_font()?;
+    unsafe { crate::std::console::use_mapped_buffer(physical_memory_offset) };
+    switch_text_mode(VgaDimensions::TEXT_80X50, SCANLINES_80X50);
+    Ok(())
+}
+
+/// Switches the screen back to the default 80x25 text mode and font, see `set_80x50_mode`. The
+/// rows below the 25th are no longer shown.
+pub fn set_80x25_mode() {
+    vga_graphics::restore_8x16_font();
+    switch_text_mode(VgaDimensions::TEXT_80X25, SCANLINES_80X25);
+}
+
+// Programs the character height of the text mode and updates the dimensions
+// of every console to match.
+fn switch_text_mode(dimensions: VgaDimensions, scanlines: u8) {
+    use x86_64::instructions::interrupts;
+
+    interrupts::without_interrupts(|| {
+        let mut crtc = CRTC.lock();
+        set_character_height_with(&mut *crtc, scanlines);
+        // an underline cursor covers the bottom two scanlines of the character.
+        show_cursor_with(&mut *crtc, scanlines - 2, scanlines - 1);
+        // the writers move the cursor through the controller.
+        drop(crtc);
+
+        crate::std::console::set_dimensions(dimensions);
+    })
+}
+
+/// Hides the hardware cursor through the given controller, keeping its scanlines.
+pub fn hide_cursor_with(crtc: &mut impl Crtc) {
+    let start = crtc.read_register(CRTC_CURSOR_START);
+    crtc.write_register(CRTC_CURSOR_START, start | CURSOR_DISABLE);
+}
+
+/// Shows the hardware cursor covering the given scanlines through the given controller.
+pub fn show_cursor_with(crtc: &mut impl Crtc, start: u8, end: u8) {
+    // the upper bits of both registers are reserved and must be preserved.
+    let current = crtc.read_register(CRTC_CURSOR_START);
+    crtc.write_register(
+        CRTC_CURSOR_START,
+        (current & !(CURSOR_SCANLINE_MASK | CURSOR_DISABLE)) | (start & CURSOR_SCANLINE_MASK),
+    );
+
+    let current = crtc.read_register(CRTC_CURSOR_END);
+    crtc.write_register(
+        CRTC_CURSOR_END,
+        (current & !CURSOR_SCANLINE_MASK) | (end & CURSOR_SCANLINE_MASK),
+    );
+}
+
+// By introducing the writer as a global static, it begins to ensure that more problems occur. By
+// having a global static means that you cannot easily have mutual exclusion. And need to
+// synchronize. Mutable statics are one way but this is highly discouraged.
+//
+// Instead we are going to be using spin locks to provide safe interior mutability within the
+// static writer.
+// The rows scrolled off the screen by the kernel output, only ever referenced
+// by the global writer.
+static mut SCROLLBACK: Scrollback = Scrollback::new();
+
+static WRITER: Once<TryMutex<Writer>> = Once::new();
+
+/// Returns the writer of the kernel console, which `println!` writes to in VGA text mode.
+pub fn kernel_writer() -> &'static TryMutex<Writer> {
+    WRITER.call_once(|| {
+        TryMutex::new(
+            "WRITER",
+            Writer::new(
+                ColorCode::new(Color::Yellow, Color::Black),
+                unsafe { &mut *(0xb8000 as *mut Buffer) },
+            )
+            .with_scrollback(unsafe { &mut SCROLLBACK }),
+        )
+    })
+}
+
+#[cfg(test)]
+#[macro_use]
+mod test {
+    use crate::println;
+    #[cfg(test)]
+    use crate::std::vga_buffer::*;
+
+    #[test_case]
+    fn test_println_single_line() {
+        println!("test_println_single_line output");
+    }
+
+    #[test_case]
+    fn test_println_many_short() {
+        for _ in 0..200 {
+            println!("test_println_many_short output");
+        }
+    }
+
+    #[test_case]
+    fn test_println_many_long() {
+        for _ in 0..200 {
+            println!(
+                "test_println_many_long output test_println_many_long output \
+            test_println_many_long output test_println_many_long output test_println_many_long \
+            output test_println_many_long output"
+            );
+        }
+    }
+
+    #[test_case]
+    fn test_println_single_line_output() {
+        use core::fmt::Write;
+        use x86_64::instructions::interrupts;
+
+        let s = "some test string that fits on a single line";
+        interrupts::without_interrupts(|| {
+            let mut writer = kernel_writer().lock();
+            writeln!(writer, "\n{}", s).expect("writeln failed");
+            for (i, c) in s.chars().enumerate() {
+                let screen_char = writer.buffer.chars[TEXT_BUFFER_HEIGHT - 2][i].read();
+                assert_eq!(char::from(screen_char.ascii_character), c);
+            }
+        });
+    }
+
+    #[test_case]
+    fn test_println_long_should_overflow() {
+        use core::fmt::Write;
+        use x86_64::instructions::interrupts;
+
+        let input =
+            " Some test string that fits on a single  Some test string that fits on a single ";
+
+        interrupts::without_interrupts(|| {
+            let mut writer = kernel_writer().lock();
+
+            writeln!(writer, "\n{}", input).expect("writeln failed");
+            writeln!(writer, "{}", input).expect("writeln failed");
+            writeln!(writer, "{}", input).expect("writeln failed");
+            writeln!(writer, "").expect("writeln failed");
+
+            for i in 1..4 {
                 for (k, c) in input.chars().enumerate() {
                     // minus i for the given row position and then an additional minus 1 for the new
                     // line that would again shift all components up one row.
@@ -301,6 +1631,598 @@ mod test {
                     assert_eq!(char::from(screen_char.ascii_character), c);
                 }
             }
-        })
+        })
+    }
+
+    #[test_case]
+    fn test_batched_output_reaches_screen_on_flush() {
+        use core::fmt::Write;
+        use x86_64::instructions::interrupts;
+
+        let s = "some batched string that fits on a single line";
+        interrupts::without_interrupts(|| {
+            let mut writer = kernel_writer().lock();
+
+            writer.set_batched(true);
+            writeln!(writer, "\n{}", s).expect("writeln failed");
+
+            // nothing has been flushed yet, the screen must not contain the output.
+            let screen_char = writer.buffer.chars[TEXT_BUFFER_HEIGHT - 2][0].read();
+            assert_ne!(char::from(screen_char.ascii_character), 's');
+
+            writer.flush();
+            for (i, c) in s.chars().enumerate() {
+                let screen_char = writer.buffer.chars[TEXT_BUFFER_HEIGHT - 2][i].read();
+                assert_eq!(char::from(screen_char.ascii_character), c);
+            }
+
+            writer.set_batched(false);
+        });
+    }
+
+    #[test_case]
+    fn test_flush_copies_back_buffer_to_screen() {
+        use core::fmt::Write;
+        use x86_64::instructions::interrupts;
+
+        interrupts::without_interrupts(|| {
+            let mut writer = kernel_writer().lock();
+
+            writer.set_batched(true);
+            for line in 0..5 {
+                writeln!(writer, "batched line {}", line).expect("writeln failed");
+            }
+
+            writer.flush();
+            for row in 0..writer.height() {
+                for col in 0..writer.width() {
+                    assert_eq!(writer.buffer.chars[row][col].read(), writer.back_buffer[row][col]);
+                }
+            }
+
+            writer.set_batched(false);
+        });
+    }
+
+    #[test_case]
+    fn test_scrollback() {
+        use core::fmt::Write;
+        use x86_64::instructions::interrupts;
+
+        // reads the text of the given row as shown on the screen.
+        fn visible_row(writer: &Writer, row: usize) -> [u8; 16] {
+            let mut text = [0u8; 16];
+            for (col, byte) in text.iter_mut().enumerate() {
+                *byte = writer.buffer.chars[row][col].read().ascii_character;
+            }
+            text
+        }
+
+        interrupts::without_interrupts(|| {
+            let mut writer = kernel_writer().lock();
+
+            for line in 0..50 {
+                writeln!(writer, "scroll line {:03}", line).expect("writeln failed");
+            }
+
+            // the last line is on the row above the bottom, so line 26 is at the top
+            // and line 25 was the last to scroll off.
+            assert_eq!(&visible_row(&writer, 0), b"scroll line 026 ");
+
+            writer.scroll_up(10);
+            assert_eq!(writer.scroll_offset(), 10);
+            assert_eq!(&visible_row(&writer, 0), b"scroll line 016 ");
+            assert_eq!(&visible_row(&writer, 10), b"scroll line 026 ");
+
+            writer.scroll_down(5);
+            assert_eq!(&visible_row(&writer, 0), b"scroll line 021 ");
+
+            // new output snaps back to the live screen.
+            writer.write_byte(b'x');
+            assert_eq!(writer.scroll_offset(), 0);
+            assert_eq!(&visible_row(&writer, 0), b"scroll line 026 ");
+            assert_eq!(
+                writer.buffer.chars[TEXT_BUFFER_HEIGHT - 1][0].read().ascii_character,
+                b'x'
+            );
+
+            writer.write_byte(b'\n');
+        });
+    }
+
+    #[test_case]
+    fn bench_throttled_vs_immediate_output() {
+        use crate::serial_println;
+
+        const LINES: usize = 10_000;
+
+        let read_tsc = || unsafe { core::arch::x86_64::_rdtsc() };
+
+        let start = read_tsc();
+        for _ in 0..LINES {
+            println!("bench_throttled_vs_immediate_output line");
+        }
+        let immediate = read_tsc() - start;
+
+        set_batched(true);
+        let start = read_tsc();
+        for _ in 0..LINES {
+            println!("bench_throttled_vs_immediate_output line");
+        }
+        set_batched(false);
+        let throttled = read_tsc() - start;
+
+        serial_println!(
+            "\n  {} lines: immediate {} cycles, throttled {} cycles",
+            LINES,
+            immediate,
+            throttled
+        );
+    }
+
+    /// Asserts that the row above the bottom row starts with the expected text, which is where
+    /// the output of the last println ends up.
+    fn assert_last_line(expected: &str) {
+        use x86_64::instructions::interrupts;
+
+        interrupts::without_interrupts(|| {
+            let writer = kernel_writer().lock();
+            for (i, c) in expected.chars().enumerate() {
+                let screen_char = writer.buffer.chars[TEXT_BUFFER_HEIGHT - 2][i].read();
+                assert_eq!(char::from(screen_char.ascii_character), c);
+            }
+        });
+    }
+
+    #[test_case]
+    fn test_println_floats() {
+        println!("\n{} {} {}", 0.0, -1.5, 1.23244);
+        assert_last_line("0 -1.5 1.23244");
+
+        println!("{} {}", 1e15, -2.5e-3);
+        assert_last_line("1000000000000000 -0.0025");
+
+        println!("{:.2} {:e}", 3.14159, 1234.5);
+        assert_last_line("3.14 1.2345e3");
+    }
+
+    #[test_case]
+    fn test_for_each_cell_uppercases_screen() {
+        use core::fmt::Write;
+        use x86_64::instructions::interrupts;
+
+        interrupts::without_interrupts(|| {
+            let mut writer = kernel_writer().lock();
+            writeln!(writer, "\nsome Mixed case text 123").expect("writeln failed");
+
+            writer.for_each_cell(|_, _, cell| cell.ascii_character.make_ascii_uppercase());
+
+            for (i, c) in "SOME MIXED CASE TEXT 123".chars().enumerate() {
+                let screen_char = writer.buffer.chars[TEXT_BUFFER_HEIGHT - 2][i].read();
+                assert_eq!(char::from(screen_char.ascii_character), c);
+            }
+
+            writer.for_each_cell(|_, _, cell| assert!(!cell.ascii_character.is_ascii_lowercase()));
+        });
+    }
+
+    #[test_case]
+    fn test_write_string_fuzz() {
+        use x86_64::instructions::interrupts;
+
+        // The seed of the generator, changing it produces a different but
+        // equally reproducible sequence of input.
+        const SEED: u64 = 0x5EED_F022;
+        const ITERATIONS: usize = 4096;
+
+        // a linear congruential generator using the constants from Knuth's MMIX,
+        // the top byte of the state is used since the low bits have short periods.
+        let mut state = SEED;
+        let mut next_byte = || {
+            state = state
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            (state >> 56) as u8
+        };
+
+        interrupts::without_interrupts(|| {
+            let mut writer = kernel_writer().lock();
+
+            for _ in 0..ITERATIONS {
+                // any byte as a (possibly multi byte) character through write_string.
+                let mut encoded = [0u8; 4];
+                writer.write_string(char::from(next_byte()).encode_utf8(&mut encoded));
+                assert!(writer.column_position < TEXT_BUFFER_WIDTH);
+
+                // and as raw binary data through write_byte.
+                writer.write_byte(next_byte());
+                assert!(writer.column_position < TEXT_BUFFER_WIDTH);
+            }
+
+            writer.write_byte(b'\n');
+        });
+    }
+
+    #[test_case]
+    fn test_write_at() {
+        use x86_64::instructions::interrupts;
+
+        let color = ColorCode::new(Color::Black, Color::LightGray);
+
+        interrupts::without_interrupts(|| {
+            let column = kernel_writer().lock().column_position;
+
+            // the last character does not fit and is clipped instead of wrapping,
+            // leaving the flowing output where it was.
+            write_at(0, 78, "OK!", color);
+
+            let writer = kernel_writer().lock();
+            let first = writer.buffer.chars[0][78].read();
+            let second = writer.buffer.chars[0][79].read();
+            assert_eq!((first.ascii_character, second.ascii_character), (b'O', b'K'));
+            assert_eq!((first.color_code, second.color_code), (color, color));
+
+            assert_eq!(writer.column_position, column);
+        });
+    }
+
+    #[test_case]
+    fn test_write_centered() {
+        use x86_64::instructions::interrupts;
+
+        let color = ColorCode::new(Color::White, Color::Blue);
+
+        interrupts::without_interrupts(|| {
+            let mut writer = kernel_writer().lock();
+            let column = writer.column_position;
+
+            writer.write_centered(0, "OS", color);
+            let screen_char = writer.buffer.chars[0][39].read();
+            assert_eq!(char::from(screen_char.ascii_character), 'O');
+            assert_eq!(screen_char.color_code, color);
+            assert_eq!(char::from(writer.buffer.chars[0][40].read().ascii_character), 'S');
+
+            // the flowing output must not have moved.
+            assert_eq!(writer.column_position, column);
+
+            // a string wider than the row starts at the first column and is clipped.
+            let wide = "0123456789012345678901234567890123456789\
+                        0123456789012345678901234567890123456789ABCDEFGHIJ";
+            writer.write_centered(1, wide, color);
+            assert_eq!(char::from(writer.buffer.chars[1][0].read().ascii_character), '0');
+            assert_eq!(char::from(writer.buffer.chars[1][79].read().ascii_character), '9');
+        });
+    }
+
+    // A fake controller holding the values of the cursor registers, with the
+    // reserved upper bits set to check that they are preserved.
+    struct FakeCrtc {
+        start: u8,
+        end: u8,
+        max_scan_line: u8,
+    }
+
+    impl Crtc for FakeCrtc {
+        fn read_register(&mut self, index: u8) -> u8 {
+            match index {
+                CRTC_CURSOR_START => self.start,
+                CRTC_CURSOR_END => self.end,
+                CRTC_MAXIMUM_SCAN_LINE => self.max_scan_line,
+                _ => 0,
+            }
+        }
+
+        fn write_register(&mut self, index: u8, value: u8) {
+            match index {
+                CRTC_CURSOR_START => self.start = value,
+                CRTC_CURSOR_END => self.end = value,
+                CRTC_MAXIMUM_SCAN_LINE => self.max_scan_line = value,
+                _ => panic!("unexpected CRTC register {:#x}", index),
+            }
+        }
+    }
+
+    #[test_case]
+    fn test_set_cursor_shape() {
+        let mut crtc = FakeCrtc {
+            start: 0xC0,
+            end: 0xE0,
+            max_scan_line: 0,
+        };
+
+        set_cursor_shape_with(&mut crtc, CursorShape::Block);
+        assert_eq!((crtc.start, crtc.end), (0xC0, 0xEF));
+
+        set_cursor_shape_with(&mut crtc, CursorShape::Underline);
+        assert_eq!((crtc.start, crtc.end), (0xCE, 0xEF));
+
+        // hiding keeps the scanlines, showing the cursor again clears the bit.
+        set_cursor_shape_with(&mut crtc, CursorShape::Hidden);
+        assert_eq!((crtc.start, crtc.end), (0xCE | CURSOR_DISABLE, 0xEF));
+
+        set_cursor_shape_with(&mut crtc, CursorShape::Block);
+        assert_eq!((crtc.start, crtc.end), (0xC0, 0xEF));
+    }
+
+    #[test_case]
+    fn test_set_character_height() {
+        let mut crtc = FakeCrtc {
+            start: 0,
+            end: 0,
+            max_scan_line: 0x4F,
+        };
+
+        set_character_height_with(&mut crtc, SCANLINES_80X50);
+        assert_eq!(crtc.max_scan_line, 0x47);
+
+        set_character_height_with(&mut crtc, SCANLINES_80X25);
+        assert_eq!(crtc.max_scan_line, 0x4F);
+    }
+
+    #[test_case]
+    fn test_80x50_mode() {
+        use x86_64::instructions::interrupts;
+
+        assert_eq!(set_80x50_mode(), Ok(()));
+
+        interrupts::without_interrupts(|| {
+            let mut writer = kernel_writer().lock();
+            assert_eq!(writer.height(), 50);
+
+            // row 40 is clamped to the bottom row in 80x25 mode.
+            writer.set_position(40, 0);
+            writer.write_byte(b'X');
+            assert_eq!(writer.buffer.chars[40][0].read().ascii_character, b'X');
+            assert_eq!(writer.position(), (40, 1));
+        });
+
+        set_80x25_mode();
+
+        interrupts::without_interrupts(|| {
+            let mut writer = kernel_writer().lock();
+            assert_eq!(writer.height(), TEXT_BUFFER_HEIGHT);
+            assert_eq!(writer.position().0, TEXT_BUFFER_HEIGHT - 1);
+
+            writer.set_position(TEXT_BUFFER_HEIGHT - 1, 0);
+        });
+    }
+
+    #[test_case]
+    fn test_hide_and_show_cursor() {
+        use x86_64::instructions::interrupts;
+
+        interrupts::without_interrupts(|| {
+            let mut writer = kernel_writer().lock();
+
+            writer.hide_cursor();
+            let start = CRTC.lock().read_register(CRTC_CURSOR_START);
+            assert_ne!(start & CURSOR_DISABLE, 0);
+
+            writer.show_cursor(14, 15);
+            let mut crtc = CRTC.lock();
+            let start = crtc.read_register(CRTC_CURSOR_START);
+            let end = crtc.read_register(CRTC_CURSOR_END);
+            assert_eq!(start & CURSOR_DISABLE, 0);
+            assert_eq!(start & CURSOR_SCANLINE_MASK, 14);
+            assert_eq!(end & CURSOR_SCANLINE_MASK, 15);
+        });
+    }
+
+    #[test_case]
+    fn test_cursor_follows_output() {
+        use x86_64::instructions::interrupts;
+
+        interrupts::without_interrupts(|| {
+            let mut writer = kernel_writer().lock();
+            writer.write_string("\nabc");
+            assert_eq!(writer.column_position, 3);
+
+            let mut crtc = CRTC.lock();
+            let high = crtc.read_register(CRTC_CURSOR_LOCATION_HIGH) as usize;
+            let low = crtc.read_register(CRTC_CURSOR_LOCATION_LOW) as usize;
+
+            let expected = (TEXT_BUFFER_HEIGHT - 1) * TEXT_BUFFER_WIDTH + writer.column_position;
+            assert_eq!((high << 8) | low, expected);
+            assert_eq!(low, expected & 0xFF);
+        });
+    }
+
+    #[test_case]
+    fn test_clear_screen() {
+        use x86_64::instructions::interrupts;
+
+        fn assert_blank(writer: &Writer) {
+            for &(row, col) in &[(0, 0), (12, 40), (24, 0), (24, 3), (24, 79)] {
+                let screen_char = writer.buffer.chars[row][col].read();
+                assert_eq!(screen_char.ascii_character, b' ');
+            }
+        }
+
+        interrupts::without_interrupts(|| {
+            let mut writer = kernel_writer().lock();
+
+            writer.write_string("some text to clear");
+            writer.clear_screen();
+            assert_blank(&writer);
+            assert_eq!(writer.column_position, 0);
+
+            // the escape sequence clears the screen in the middle of a string.
+            writer.write_string("before\x1b[2Jab");
+            assert_eq!(writer.buffer.chars[24][0].read().ascii_character, b'a');
+            assert_eq!(writer.buffer.chars[24][1].read().ascii_character, b'b');
+            writer.clear_screen();
+            assert_blank(&writer);
+        });
+    }
+
+    #[test_case]
+    fn test_set_position() {
+        use x86_64::instructions::interrupts;
+
+        interrupts::without_interrupts(|| {
+            let mut writer = kernel_writer().lock();
+
+            writer.set_position(5, 10);
+            writer.write_byte(b'X');
+            assert_eq!(writer.buffer.chars[5][10].read().ascii_character, b'X');
+            assert_eq!(writer.position(), (5, 11));
+
+            // a new line moves down a row without scrolling.
+            writer.write_string("\nY");
+            assert_eq!(writer.buffer.chars[6][0].read().ascii_character, b'Y');
+            assert_eq!(writer.buffer.chars[5][10].read().ascii_character, b'X');
+
+            // coordinates outside the screen are clamped to the last row and column.
+            writer.set_position(100, 200);
+            assert_eq!(writer.position(), (TEXT_BUFFER_HEIGHT - 1, TEXT_BUFFER_WIDTH - 1));
+
+            writer.set_position(TEXT_BUFFER_HEIGHT - 1, 0);
+        });
+    }
+
+    #[test_case]
+    fn test_write_raw_bytes() {
+        use x86_64::instructions::interrupts;
+
+        interrupts::without_interrupts(|| {
+            let mut writer = kernel_writer().lock();
+
+            // box corners and control characters are written unchanged.
+            let bytes = [0xC9, 0xBB, 0xC8, 0xBC, b'\n', 0x08];
+            writer.write_string("\n");
+            writer.write_raw_bytes(&bytes);
+            for (col, &expected) in bytes.iter().enumerate() {
+                assert_eq!(writer.buffer.chars[24][col].read().ascii_character, expected);
+            }
+
+            // write_string still replaces them.
+            writer.write_string("\n\u{2554}");
+            assert_eq!(writer.buffer.chars[24][0].read().ascii_character, 0xfe);
+        });
+    }
+
+    #[test_case]
+    fn test_draw_box() {
+        use x86_64::instructions::interrupts;
+
+        interrupts::without_interrupts(|| {
+            let mut writer = kernel_writer().lock();
+            let color = ColorCode::new(Color::White, Color::Blue);
+            let glyph = |writer: &Writer, row: usize, col: usize| {
+                writer.buffer.chars[row][col].read().ascii_character
+            };
+
+            writer.fill_rect(5, 10, 3, 4, b'.', color);
+            writer.draw_box(5, 10, 3, 4, color);
+
+            assert_eq!(glyph(&writer, 5, 10), 0xDA);
+            assert_eq!(glyph(&writer, 5, 13), 0xBF);
+            assert_eq!(glyph(&writer, 7, 10), 0xC0);
+            assert_eq!(glyph(&writer, 7, 13), 0xD9);
+            assert_eq!(glyph(&writer, 5, 11), 0xC4);
+            assert_eq!(glyph(&writer, 6, 13), 0xB3);
+            // the inside of the box is kept.
+            assert_eq!(glyph(&writer, 6, 11), b'.');
+
+            // a box crossing the edge of the screen is clipped.
+            let (row, col) = (TEXT_BUFFER_HEIGHT - 2, TEXT_BUFFER_WIDTH - 2);
+            writer.draw_box(row, col, 4, 4, color);
+            assert_eq!(glyph(&writer, row, col), 0xDA);
+            assert_eq!(glyph(&writer, row, col + 1), 0xC4);
+            assert_eq!(glyph(&writer, row + 1, col), 0xB3);
+
+            // a box starting at the end of the address space is not drawn.
+            writer.draw_box(usize::MAX, usize::MAX, 4, 4, color);
+        });
+    }
+
+    #[test_case]
+    fn test_backspace() {
+        use x86_64::instructions::interrupts;
+
+        interrupts::without_interrupts(|| {
+            let mut writer = kernel_writer().lock();
+
+            writer.write_string("\nabc\x08d");
+            for (col, &expected) in b"abd ".iter().enumerate() {
+                assert_eq!(writer.buffer.chars[24][col].read().ascii_character, expected);
+            }
+            assert_eq!(writer.column_position, 3);
+
+            // at the start of the row a backspace is ignored.
+            writer.write_string("\n\x08\x7fx");
+            assert_eq!(writer.buffer.chars[24][0].read().ascii_character, b'x');
+            assert_eq!(writer.buffer.chars[23][0].read().ascii_character, b'a');
+        });
+    }
+
+    #[test_case]
+    fn test_tab_expansion() {
+        use x86_64::instructions::interrupts;
+
+        interrupts::without_interrupts(|| {
+            let mut writer = kernel_writer().lock();
+
+            writer.write_string("\na\tb");
+            assert_eq!(writer.buffer.chars[24][0].read().ascii_character, b'a');
+            assert_eq!(writer.buffer.chars[24][7].read().ascii_character, b' ');
+            assert_eq!(writer.buffer.chars[24][8].read().ascii_character, b'b');
+
+            // a tab at a tab stop still advances a full tab.
+            writer.write_string("\n\tc");
+            assert_eq!(writer.buffer.chars[24][8].read().ascii_character, b'c');
+
+            // a tab in the last tab stop of the row wraps to the next line.
+            writer.write_string("\n");
+            for _ in 0..75 {
+                writer.write_byte(b'x');
+            }
+            writer.write_string("\t\td");
+            assert_eq!(writer.buffer.chars[24][0].read().ascii_character, b'd');
+        });
+    }
+
+    #[test_case]
+    fn test_set_color() {
+        use x86_64::instructions::interrupts;
+
+        interrupts::without_interrupts(|| {
+            let mut writer = kernel_writer().lock();
+            let previous = writer.color_code;
+
+            writer.set_color(Color::Red, Color::Black);
+            writer.write_string("\nr");
+
+            let expected = ColorCode::new(Color::Red, Color::Black);
+            assert_eq!(writer.color_code, expected);
+            assert_eq!(writer.buffer.chars[24][0].read().color_code, expected);
+
+            writer.color_code = previous;
+        });
+    }
+
+    #[test_case]
+    fn test_cprintln_restores_color() {
+        use x86_64::instructions::interrupts;
+
+        // nothing else may write to the screen between printing and checking.
+        interrupts::without_interrupts(|| {
+            let previous = kernel_writer().lock().color_code;
+
+            // the line starts on a fresh row, and ends by moving to the next.
+            crate::print!("\n");
+            cprintln!(Color::Red, "test_cprintln_restores_color {}", 42);
+
+            let writer = kernel_writer().lock();
+            assert_eq!(writer.color_code, previous);
+
+            // the line was written in red on the previous background.
+            for (col, &expected) in b"test_cprintln_restores_color 42".iter().enumerate() {
+                let screen_char = writer.buffer.chars[23][col].read();
+                assert_eq!(screen_char.ascii_character, expected);
+                assert_eq!(screen_char.color_code.0 & 0x0F, Color::Red as u8);
+                assert_eq!(screen_char.color_code.0 & 0xF0, previous.0 & 0xF0);
+            }
+        });
     }
 }
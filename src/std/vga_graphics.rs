@@ -0,0 +1,448 @@
+use core::sync::atomic::{AtomicBool, Ordering};
+use spin::Mutex;
+use x86_64::instructions::port::Port;
+use x86_64::PhysAddr;
+
+use crate::std::{framebuffer, memory};
+
+/// The width of the screen in mode 13h, in pixels.
+pub const WIDTH: usize = 320;
+/// The height of the screen in mode 13h, in pixels.
+pub const HEIGHT: usize = 200;
+
+// The framebuffer of mode 13h, one byte per pixel selecting a palette color.
+const FRAMEBUFFER: u64 = 0xA0000;
+
+// The text buffer of mode 3, which is also where the font is accessed
+// through while in text mode.
+const TEXT_BUFFER: u64 = 0xB8000;
+// Enough cells for 80x50 text mode, see `vga_buffer::set_80x50_mode`.
+const TEXT_CELLS: usize = 80 * 50;
+
+// The font of mode 3 is stored in plane 2, 32 bytes for each of the 256
+// characters. Drawing pixels overwrites it, so it is saved and restored.
+const GLYPH_SIZE: usize = 32;
+const FONT_SIZE: usize = 256 * GLYPH_SIZE;
+const FONT_PLANE: u8 = 2;
+
+// The characters drawn with the 8x8 font of `framebuffer`, the other glyphs
+// of the 8 scanline font are squeezed from the 16 scanline font.
+const PRINTABLE_FIRST: usize = 0x20;
+const PRINTABLE_LAST: usize = 0x7E;
+
+// The VGA register ports.
+// https://wiki.osdev.org/VGA_Hardware
+const MISC_WRITE_PORT: u16 = 0x3C2;
+const SEQUENCER_INDEX_PORT: u16 = 0x3C4;
+const CRTC_INDEX_PORT: u16 = 0x3D4;
+const CRTC_DATA_PORT: u16 = 0x3D5;
+const GRAPHICS_INDEX_PORT: u16 = 0x3CE;
+const ATTRIBUTE_PORT: u16 = 0x3C0;
+const INPUT_STATUS_PORT: u16 = 0x3DA;
+
+// The registers used to give the CPU access to a single plane.
+const SEQUENCER_MAP_MASK: u8 = 0x02;
+const SEQUENCER_MEMORY_MODE: u8 = 0x04;
+const GRAPHICS_READ_MAP: u8 = 0x04;
+const GRAPHICS_MODE: u8 = 0x05;
+const GRAPHICS_MISC: u8 = 0x06;
+
+// The register values of both modes, in the order they are written by
+// `write_registers`: miscellaneous output, 5 sequencer, 25 CRT controller,
+// 9 graphics controller and 21 attribute controller registers.
+// https://files.osdev.org/mirrors/geezer/osd/graphics/modes.c
+const MODE_13H: [u8; 61] = [
+    0x63, //
+    0x03, 0x01, 0x0F, 0x00, 0x0E, //
+    0x5F, 0x4F, 0x50, 0x82, 0x54, 0x80, 0xBF, 0x1F, 0x00, 0x41, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+    0x9C, 0x0E, 0x8F, 0x28, 0x40, 0x96, 0xB9, 0xA3, 0xFF, //
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x40, 0x05, 0x0F, 0xFF, //
+    0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09, 0x0A, 0x0B, 0x0C, 0x0D, 0x0E, 0x0F,
+    0x41, 0x00, 0x0F, 0x00, 0x00,
+];
+
+const MODE_3: [u8; 61] = [
+    0x67, //
+    0x03, 0x00, 0x03, 0x00, 0x02, //
+    0x5F, 0x4F, 0x50, 0x82, 0x55, 0x81, 0xBF, 0x1F, 0x00, 0x4F, 0x0D, 0x0E, 0x00, 0x00, 0x00, 0x50,
+    0x9C, 0x0E, 0x8F, 0x28, 0x1F, 0x96, 0xB9, 0xA3, 0xFF, //
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x10, 0x0E, 0x00, 0xFF, //
+    0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x14, 0x07, 0x38, 0x39, 0x3A, 0x3B, 0x3C, 0x3D, 0x3E, 0x3F,
+    0x0C, 0x00, 0x0F, 0x08, 0x00,
+];
+
+/// The errors that can occur when switching to mode 13h or changing the font of the text mode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GraphicsError {
+    // The VGA memory is not reachable through the complete physical memory
+    // mapping, or the offset of the mapping has not been recorded.
+    NotMapped,
+}
+
+// Set while the screen is in mode 13h.
+static GRAPHICS_MODE_ENABLED: AtomicBool = AtomicBool::new(false);
+
+// The font and the text on the screen, saved when switching to mode 13h.
+struct SavedText {
+    font: [u8; FONT_SIZE],
+    cells: [u16; TEXT_CELLS],
+}
+
+static SAVED: Mutex<SavedText> = Mutex::new(SavedText {
+    font: [0; FONT_SIZE],
+    cells: [0; TEXT_CELLS],
+});
+
+// The 16 scanline font of 80x25 text mode, saved while `load_8x8_font` has
+// replaced it.
+static SAVED_8X16_FONT: Mutex<Option<[u8; FONT_SIZE]>> = Mutex::new(None);
+
+/// Switches the screen to VGA mode 13h, 320x200 pixels with 256 colors. The text on the screen
+/// and the font are saved and restored by `set_text_mode`. Text written while in mode 13h is not
+/// shown.
+///
+/// The VGA memory is accessed through the complete physical memory mapping, since the bootloader
+/// only identity maps the page of the text buffer. Fails with `GraphicsError::NotMapped` if the
+/// offset of the mapping has not been recorded with `memory::set_physical_memory_offset`.
+pub fn set_graphics_mode() -> Result<(), GraphicsError> {
+    use x86_64::instructions::interrupts;
+
+    interrupts::without_interrupts(|| {
+        if GRAPHICS_MODE_ENABLED.load(Ordering::SeqCst) {
+            return Ok(());
+        }
+
+        // checked once here, so that drawing only has to look the memory up.
+        if !is_mapped(FRAMEBUFFER, WIDTH * HEIGHT) || !is_mapped(TEXT_BUFFER, FONT_SIZE) {
+            return Err(GraphicsError::NotMapped);
+        }
+
+        let mut saved = SAVED.lock();
+        let text = vga_memory(TEXT_BUFFER, TEXT_CELLS * 2).ok_or(GraphicsError::NotMapped)?;
+        for (i, cell) in saved.cells.iter_mut().enumerate() {
+            *cell = unsafe { core::ptr::read_volatile((text.as_ptr() as *const u16).add(i)) };
+        }
+        unsafe { access_font(|font| saved.font.copy_from_slice(font)) };
+
+        unsafe { write_registers(&MODE_13H) };
+        GRAPHICS_MODE_ENABLED.store(true, Ordering::SeqCst);
+        Ok(())
+    })
+}
+
+/// Switches the screen back to 80x25 text mode, restoring the font and the text that was on the
+/// screen before `set_graphics_mode`. Called by the panic handler so that panics can be read.
+pub fn set_text_mode() {
+    use x86_64::instructions::interrupts;
+
+    interrupts::without_interrupts(|| {
+        if !GRAPHICS_MODE_ENABLED.load(Ordering::SeqCst) {
+            return;
+        }
+
+        unsafe { write_registers(&MODE_3) };
+
+        // the saved text is only locked while switching to mode 13h, if that
+        // panicked the screen is left with a broken font rather than hanging.
+        if let (Some(saved), Some(text)) =
+            (SAVED.try_lock(), vga_memory(TEXT_BUFFER, TEXT_CELLS * 2))
+        {
+            unsafe { access_font(|font| font.copy_from_slice(&saved.font)) };
+            for (i, cell) in saved.cells.iter().enumerate() {
+                unsafe { core::ptr::write_volatile((text.as_mut_ptr() as *mut u16).add(i), *cell) };
+            }
+        }
+
+        GRAPHICS_MODE_ENABLED.store(false, Ordering::SeqCst);
+    })
+}
+
+/// Returns true if the screen is in mode 13h.
+pub fn is_graphics_mode() -> bool {
+    GRAPHICS_MODE_ENABLED.load(Ordering::SeqCst)
+}
+
+/// Replaces the font of the text mode with glyphs 8 scanlines high, as used by 80x50 text mode.
+/// The printable ASCII characters are drawn with the 8x8 font of `framebuffer`, the other
+/// characters are squeezed from the loaded 16 scanline glyphs by merging each pair of rows. The
+/// replaced font is restored by `restore_8x16_font`.
+///
+/// Fails with `GraphicsError::NotMapped` if the font can't be reached through the complete
+/// physical memory mapping, see `set_graphics_mode`.
+pub fn load_8x8_font() -> Result<(), GraphicsError> {
+    use x86_64::instructions::interrupts;
+
+    interrupts::without_interrupts(|| {
+        if !is_mapped(TEXT_BUFFER, FONT_SIZE) {
+            return Err(GraphicsError::NotMapped);
+        }
+
+        let mut saved = SAVED_8X16_FONT.lock();
+        unsafe {
+            access_font(|font| {
+                // loading twice must not squeeze the already squeezed glyphs.
+                let tall = saved.get_or_insert_with(|| {
+                    let mut tall = [0; FONT_SIZE];
+                    tall.copy_from_slice(font);
+                    tall
+                });
+
+                for (character, glyph) in font.chunks_mut(GLYPH_SIZE).enumerate() {
+                    let tall = &tall[character * GLYPH_SIZE..][..GLYPH_SIZE];
+                    for (row, line) in glyph.iter_mut().take(8).enumerate() {
+                        *line = squeezed_row(character, tall, row);
+                    }
+                }
+            })
+        };
+
+        Ok(())
+    })
+}
+
+/// Restores the 16 scanline font replaced by `load_8x8_font`, does nothing if it was not
+/// replaced.
+pub fn restore_8x16_font() {
+    use x86_64::instructions::interrupts;
+
+    interrupts::without_interrupts(|| {
+        if let Some(tall) = SAVED_8X16_FONT.lock().take() {
+            unsafe { access_font(|font| font.copy_from_slice(&tall)) };
+        }
+    })
+}
+
+// Returns a row of the character in the 8 scanline font. The VGA font has the
+// leftmost pixel in the most significant bit, the reverse of `framebuffer`.
+fn squeezed_row(character: usize, tall: &[u8], row: usize) -> u8 {
+    if (PRINTABLE_FIRST..=PRINTABLE_LAST).contains(&character) {
+        framebuffer::glyph(character as u8 as char)[row].reverse_bits()
+    } else {
+        tall[2 * row] | tall[2 * row + 1]
+    }
+}
+
+/// Sets the pixel at the given position to the color, does nothing outside of the screen or when
+/// not in mode 13h.
+///
+/// # Arguments
+///
+/// `x` The column of the pixel, from the left.
+/// `y` The row of the pixel, from the top.
+/// `color` The index of the color in the 256 color palette.
+///
+/// # Example
+///
+/// ```
+/// vga_graphics::set_graphics_mode();
+/// vga_graphics::put_pixel(160, 100, 0x0F);
+/// ```
+pub fn put_pixel(x: usize, y: usize, color: u8) {
+    if x >= WIDTH || y >= HEIGHT || !is_graphics_mode() {
+        return;
+    }
+
+    if let Some(framebuffer) = vga_memory(FRAMEBUFFER, WIDTH * HEIGHT) {
+        unsafe { core::ptr::write_volatile(&mut framebuffer[y * WIDTH + x], color) };
+    }
+}
+
+/// Returns the color of the pixel at the given position, or `None` outside of the screen or when
+/// not in mode 13h.
+pub fn get_pixel(x: usize, y: usize) -> Option<u8> {
+    if x >= WIDTH || y >= HEIGHT || !is_graphics_mode() {
+        return None;
+    }
+
+    let framebuffer = vga_memory(FRAMEBUFFER, WIDTH * HEIGHT)?;
+    Some(unsafe { core::ptr::read_volatile(&framebuffer[y * WIDTH + x]) })
+}
+
+/// Fills the whole screen with the color, does nothing when not in mode 13h.
+pub fn clear(color: u8) {
+    if !is_graphics_mode() {
+        return;
+    }
+
+    if let Some(framebuffer) = vga_memory(FRAMEBUFFER, WIDTH * HEIGHT) {
+        for pixel in framebuffer.iter_mut() {
+            unsafe { core::ptr::write_volatile(pixel, color) };
+        }
+    }
+}
+
+// Returns `len` bytes of VGA memory at the physical address through the
+// complete physical memory mapping, or `None` if its offset is not known. The
+// range must have been checked with `is_mapped`.
+fn vga_memory(phys: u64, len: usize) -> Option<&'static mut [u8]> {
+    let offset = memory::physical_memory_offset()?;
+    Some(unsafe { memory::phys_slice_mut(PhysAddr::new(phys), len, offset) })
+}
+
+// Returns true if every page of the VGA memory range is reachable through the
+// complete physical memory mapping.
+fn is_mapped(phys: u64, len: usize) -> bool {
+    match memory::physical_memory_offset() {
+        Some(offset) => unsafe { memory::is_phys_range_mapped(PhysAddr::new(phys), len, offset) },
+        None => false,
+    }
+}
+
+/// Programs every register of a mode, in the order of the tables above.
+///
+/// This function is unsafe since the registers must hold a valid mode.
+unsafe fn write_registers(registers: &[u8; 61]) {
+    let mut misc: Port<u8> = Port::new(MISC_WRITE_PORT);
+    let mut crtc_index: Port<u8> = Port::new(CRTC_INDEX_PORT);
+    let mut crtc_data: Port<u8> = Port::new(CRTC_DATA_PORT);
+    let mut attribute: Port<u8> = Port::new(ATTRIBUTE_PORT);
+    let mut input_status: Port<u8> = Port::new(INPUT_STATUS_PORT);
+
+    let (misc_value, rest) = registers.split_at(1);
+    let (sequencer, rest) = rest.split_at(5);
+    let (crtc, rest) = rest.split_at(25);
+    let (graphics, attributes) = rest.split_at(9);
+
+    misc.write(misc_value[0]);
+
+    for (index, &value) in sequencer.iter().enumerate() {
+        write_indexed(SEQUENCER_INDEX_PORT, index as u8, value);
+    }
+
+    // the first 8 CRT controller registers are write protected by bit 7 of
+    // register 0x11, and bit 7 of register 0x03 must stay set.
+    crtc_index.write(0x03);
+    let value = crtc_data.read();
+    crtc_data.write(value | 0x80);
+    crtc_index.write(0x11);
+    let value = crtc_data.read();
+    crtc_data.write(value & !0x80);
+
+    for (index, &value) in crtc.iter().enumerate() {
+        let value = match index {
+            0x03 => value | 0x80,
+            0x11 => value & !0x80,
+            _ => value,
+        };
+
+        write_indexed(CRTC_INDEX_PORT, index as u8, value);
+    }
+
+    for (index, &value) in graphics.iter().enumerate() {
+        write_indexed(GRAPHICS_INDEX_PORT, index as u8, value);
+    }
+
+    // reading the input status resets the attribute controller to expect an
+    // index, after which the index and value are written to the same port.
+    for (index, &value) in attributes.iter().enumerate() {
+        input_status.read();
+        attribute.write(index as u8);
+        attribute.write(value);
+    }
+
+    // enable the display again now that the palette has been written.
+    input_status.read();
+    attribute.write(0x20);
+}
+
+/// Runs the closure with the font plane mapped at the text buffer, which must be in text mode.
+/// Odd/even addressing is disabled while the closure runs so that the plane is accessed as
+/// plain bytes, the registers are restored afterwards.
+///
+/// This function is unsafe since it must only be called in text mode, with interrupts disabled and
+/// the font checked to be mapped with `is_mapped`.
+unsafe fn access_font(f: impl FnOnce(&mut [u8])) {
+    let font = match vga_memory(TEXT_BUFFER, FONT_SIZE) {
+        Some(font) => font,
+        None => return,
+    };
+
+    let map_mask = read_indexed(SEQUENCER_INDEX_PORT, SEQUENCER_MAP_MASK);
+    let memory_mode = read_indexed(SEQUENCER_INDEX_PORT, SEQUENCER_MEMORY_MODE);
+    let read_map = read_indexed(GRAPHICS_INDEX_PORT, GRAPHICS_READ_MAP);
+    let mode = read_indexed(GRAPHICS_INDEX_PORT, GRAPHICS_MODE);
+    let misc = read_indexed(GRAPHICS_INDEX_PORT, GRAPHICS_MISC);
+
+    write_indexed(
+        SEQUENCER_INDEX_PORT,
+        SEQUENCER_MEMORY_MODE,
+        memory_mode | 0x04,
+    );
+    write_indexed(GRAPHICS_INDEX_PORT, GRAPHICS_MODE, mode & !0x10);
+    write_indexed(GRAPHICS_INDEX_PORT, GRAPHICS_MISC, misc & !0x02);
+    write_indexed(SEQUENCER_INDEX_PORT, SEQUENCER_MAP_MASK, 1 << FONT_PLANE);
+    write_indexed(GRAPHICS_INDEX_PORT, GRAPHICS_READ_MAP, FONT_PLANE);
+
+    f(font);
+
+    write_indexed(SEQUENCER_INDEX_PORT, SEQUENCER_MAP_MASK, map_mask);
+    write_indexed(SEQUENCER_INDEX_PORT, SEQUENCER_MEMORY_MODE, memory_mode);
+    write_indexed(GRAPHICS_INDEX_PORT, GRAPHICS_READ_MAP, read_map);
+    write_indexed(GRAPHICS_INDEX_PORT, GRAPHICS_MODE, mode);
+    write_indexed(GRAPHICS_INDEX_PORT, GRAPHICS_MISC, misc);
+}
+
+// The data port of the sequencer, CRT controller and graphics controller is
+// always the port after the index port.
+unsafe fn write_indexed(port: u16, index: u8, value: u8) {
+    let mut index_port: Port<u8> = Port::new(port);
+    let mut data_port: Port<u8> = Port::new(port + 1);
+
+    index_port.write(index);
+    data_port.write(value);
+}
+
+unsafe fn read_indexed(port: u16, index: u8) -> u8 {
+    let mut index_port: Port<u8> = Port::new(port);
+    let mut data_port: Port<u8> = Port::new(port + 1);
+
+    index_port.write(index);
+    data_port.read()
+}
+
+// Tests
+
+#[test_case]
+fn test_put_pixel_reads_back() {
+    assert_eq!(set_graphics_mode(), Ok(()));
+    assert!(is_graphics_mode());
+
+    clear(0x00);
+    put_pixel(10, 20, 0x2A);
+    let framebuffer = vga_memory(FRAMEBUFFER, WIDTH * HEIGHT).unwrap();
+    let color = unsafe { core::ptr::read_volatile(&framebuffer[20 * WIDTH + 10]) };
+    let neighbour = get_pixel(11, 20);
+
+    set_text_mode();
+    assert!(!is_graphics_mode());
+
+    assert_eq!(color, 0x2A);
+    assert_eq!(neighbour, Some(0x00));
+}
+
+#[test_case]
+fn test_load_8x8_font() {
+    use x86_64::instructions::interrupts;
+
+    // reads the first rows of a glyph straight from the font plane.
+    fn rows(character: usize) -> [u8; 8] {
+        let mut rows = [0; 8];
+        interrupts::without_interrupts(|| unsafe {
+            access_font(|font| rows.copy_from_slice(&font[character * GLYPH_SIZE..][..8]))
+        });
+        rows
+    }
+
+    let tall = rows(b'A' as usize);
+
+    assert_eq!(load_8x8_font(), Ok(()));
+    let squeezed = rows(b'A' as usize);
+    assert_eq!(load_8x8_font(), Ok(()));
+    let reloaded = rows(b'A' as usize);
+    restore_8x16_font();
+
+    // the top row of 'A' in the 8x8 font is 0x0C, with the leftmost pixel in bit 0.
+    assert_eq!(squeezed[0], 0x30);
+    assert_eq!(reloaded, squeezed);
+    assert_eq!(rows(b'A' as usize), tall);
+}
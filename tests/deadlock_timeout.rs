@@ -0,0 +1,40 @@
+#![no_std]
+#![no_main]
+
+use core::fmt::Write;
+use core::panic::PanicInfo;
+use operating_system::std::sync::TryMutex;
+use operating_system::{exit_qemu, serial_print, serial_println, QemuExitCode, TestOutput};
+
+static LOCK: TryMutex<()> = TryMutex::new("LOCK", ());
+
+// Locking a mutex that is already held by the same code would spin forever,
+// the bounded lock must panic and name the mutex instead.
+#[no_mangle]
+pub extern "C" fn _start() -> ! {
+    serial_print!("deadlock_timeout::relock...");
+
+    let _guard = LOCK.lock();
+    let _again = LOCK.lock_with_timeout(1_000_000);
+
+    serial_println!("[test did not panic]");
+    exit_qemu(QemuExitCode::Failed);
+    loop {}
+}
+
+#[panic_handler]
+fn panic(info: &PanicInfo) -> ! {
+    let mut message: TestOutput<128> = TestOutput::new();
+    let _ = write!(message, "{}", info);
+
+    if message.as_str().contains("potential deadlock on LOCK") {
+        serial_println!("[ok]");
+        exit_qemu(QemuExitCode::Success);
+    } else {
+        serial_println!("[failed]\n");
+        serial_println!("Error: {}\n", info);
+        exit_qemu(QemuExitCode::Failed);
+    }
+
+    loop {}
+}
@@ -0,0 +1,64 @@
+#![no_std]
+#![no_main]
+#![feature(custom_test_frameworks)]
+#![test_runner(operating_system::test_runner)]
+#![reexport_test_harness_main = "test_main"]
+
+use bootloader::{entry_point, BootInfo};
+use core::panic::PanicInfo;
+use operating_system::std::allocator::{HEAP_GROWTH_SIZE, HEAP_GROWTH_START};
+use operating_system::std::memory::{self, BootInfoFrameAllocator};
+use x86_64::VirtAddr;
+
+entry_point!(main);
+
+// noinspection RsUnresolvedReference
+fn main(boot_info: &'static BootInfo) -> ! {
+    operating_system::init();
+
+    let physical_memory_offset = VirtAddr::new(boot_info.physical_memory_offset);
+    memory::set_physical_memory_offset(physical_memory_offset);
+    let mapper = unsafe { memory::init(physical_memory_offset) };
+    let frame_allocator = unsafe { BootInfoFrameAllocator::init(&boot_info.memory_map) };
+    memory::init_demand_paging(mapper, frame_allocator);
+
+    test_main();
+
+    loop {}
+}
+
+#[test_case]
+fn test_touching_growth_range_maps_page() {
+    let offset = memory::physical_memory_offset().unwrap();
+    let addr = VirtAddr::new((HEAP_GROWTH_START + 0x10) as u64);
+    assert_eq!(unsafe { memory::translate_addr(addr, offset) }, None);
+
+    let mapped = memory::demand_mapped_pages();
+    let ptr: *mut u64 = addr.as_mut_ptr();
+
+    // the first read faults, the page is mapped and the read retried.
+    assert_eq!(unsafe { ptr.read_volatile() }, 0);
+    unsafe { ptr.write_volatile(0xdead_beef) };
+    assert_eq!(unsafe { ptr.read_volatile() }, 0xdead_beef);
+
+    assert_eq!(memory::demand_mapped_pages(), mapped + 1);
+    assert!(unsafe { memory::translate_addr(addr, offset) }.is_some());
+}
+
+#[test_case]
+fn test_each_page_mapped_once() {
+    let last_page = HEAP_GROWTH_START + HEAP_GROWTH_SIZE - 4096;
+    let mapped = memory::demand_mapped_pages();
+
+    for i in 0..4 {
+        let ptr = (last_page + i * 8) as *mut u64;
+        unsafe { ptr.write_volatile(i as u64) };
+    }
+
+    assert_eq!(memory::demand_mapped_pages(), mapped + 1);
+}
+
+#[panic_handler]
+fn panic(info: &PanicInfo) -> ! {
+    operating_system::test_panic_handler(info)
+}
@@ -0,0 +1,32 @@
+#![no_std]
+#![no_main]
+
+use core::panic::PanicInfo;
+use operating_system::std::interrupts::{self, DoubleFaultAction};
+use operating_system::serial_print;
+
+// Unlike `stack_overflow`, this test does not install its own IDT. Instead the
+// crate's double fault handler is configured to report success and exit QEMU.
+#[no_mangle]
+pub extern "C" fn _start() -> ! {
+    serial_print!("double_fault_action::stack_overflow...");
+
+    operating_system::std::gdt::init();
+    interrupts::init_idt();
+    interrupts::set_double_fault_action(DoubleFaultAction::ExitQemuSuccess);
+
+    stack_overflow();
+
+    panic!("Execution continued after stack overflow")
+}
+
+#[allow(unconditional_recursion)]
+fn stack_overflow() {
+    stack_overflow(); // for each recursion, the return address is pushed.
+    volatile::Volatile::new(0).read(); // prevent tail recursion optimizations.
+}
+
+#[panic_handler]
+fn panic(info: &PanicInfo) -> ! {
+    operating_system::test_panic_handler(info)
+}
@@ -0,0 +1,118 @@
+#![no_std]
+#![no_main]
+#![feature(custom_test_frameworks)]
+#![test_runner(operating_system::test_runner)]
+#![reexport_test_harness_main = "test_main"]
+
+extern crate alloc;
+
+use alloc::rc::Rc;
+use bootloader::{entry_point, BootInfo};
+use core::cell::Cell;
+use core::future::Future;
+use core::panic::PanicInfo;
+use core::pin::Pin;
+use core::task::{Context, Poll};
+use operating_system::std::allocator;
+use operating_system::std::memory::{self, BootInfoFrameAllocator};
+use operating_system::std::task::executor::Executor;
+use operating_system::std::task::simple_executor::SimpleExecutor;
+use operating_system::std::task::{timer, Task};
+use x86_64::VirtAddr;
+
+entry_point!(main);
+
+// noinspection RsUnresolvedReference
+fn main(boot_info: &'static BootInfo) -> ! {
+    operating_system::init();
+
+    let physical_memory_offset = VirtAddr::new(boot_info.physical_memory_offset);
+    let mut mapper = unsafe { memory::init(physical_memory_offset) };
+    let mut frame_allocator = unsafe { BootInfoFrameAllocator::init(&boot_info.memory_map) };
+    allocator::init_heap(&mut mapper, &mut frame_allocator).expect("heap initialization failed");
+
+    test_main();
+
+    loop {}
+}
+
+#[test_case]
+fn test_spawned_task_completes() {
+    let counter = Rc::new(Cell::new(0));
+    let task_counter = counter.clone();
+
+    let mut executor = Executor::new();
+    executor.spawn(Task::new(async move {
+        task_counter.set(task_counter.get() + 1);
+    }));
+
+    executor.run_ready_tasks();
+
+    assert_eq!(counter.get(), 1);
+    assert_eq!(executor.task_count(), 0);
+}
+
+#[test_case]
+fn test_woken_task_is_polled_again() {
+    let counter = Rc::new(Cell::new(0));
+    let task_counter = counter.clone();
+
+    let mut executor = Executor::new();
+    executor.spawn(Task::new(async move {
+        for _ in 0..3 {
+            timer::sleep(10).await;
+            task_counter.set(task_counter.get() + 1);
+        }
+    }));
+
+    // the timer interrupt wakes the task once each sleep has passed.
+    while executor.task_count() > 0 {
+        executor.run_ready_tasks();
+        x86_64::instructions::hlt();
+    }
+
+    assert_eq!(counter.get(), 3);
+}
+
+// A future that is pending on the first poll, waking itself so that it is
+// polled again.
+struct YieldNow {
+    yielded: bool,
+}
+
+impl Future for YieldNow {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<()> {
+        if self.yielded {
+            return Poll::Ready(());
+        }
+
+        self.yielded = true;
+        cx.waker().wake_by_ref();
+        Poll::Pending
+    }
+}
+
+#[test_case]
+fn test_simple_executor_runs_yielding_tasks() {
+    let counter = Rc::new(Cell::new(0));
+
+    let mut executor = SimpleExecutor::new();
+    for _ in 0..2 {
+        let task_counter = counter.clone();
+        executor.spawn(Task::new(async move {
+            YieldNow { yielded: false }.await;
+            task_counter.set(task_counter.get() + 1);
+        }));
+    }
+
+    executor.run();
+
+    assert_eq!(counter.get(), 2);
+}
+
+#[panic_handler]
+fn panic(info: &PanicInfo) -> ! {
+    operating_system::test_panic_handler(info)
+}
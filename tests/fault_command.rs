@@ -0,0 +1,36 @@
+#![no_std]
+#![no_main]
+#![feature(custom_test_frameworks)]
+#![test_runner(operating_system::test_runner)]
+#![reexport_test_harness_main = "test_main"]
+
+use core::panic::PanicInfo;
+use operating_system::std::{interrupts, shell};
+
+#[no_mangle] // don't mangle the name of this function
+// noinspection RsUnresolvedReference
+pub extern "C" fn _start() -> ! {
+    operating_system::init();
+    test_main();
+
+    loop {}
+}
+
+#[test_case]
+fn test_fault_bp_returns_to_shell() {
+    let before = interrupts::breakpoint_count();
+
+    assert_eq!(shell::execute("fault bp"), Ok(()));
+    assert_eq!(interrupts::breakpoint_count(), before + 1);
+}
+
+#[test_case]
+fn test_fault_rejects_unknown_type() {
+    assert_eq!(shell::execute("fault xx"), Err(shell::ShellError::InvalidArgument));
+    assert_eq!(shell::execute("fault"), Err(shell::ShellError::MissingArgument));
+}
+
+#[panic_handler]
+fn panic(info: &PanicInfo) -> ! {
+    operating_system::test_panic_handler(info)
+}
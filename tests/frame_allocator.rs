@@ -0,0 +1,42 @@
+#![no_std]
+#![no_main]
+#![feature(custom_test_frameworks)]
+#![test_runner(operating_system::test_runner)]
+#![reexport_test_harness_main = "test_main"]
+
+use bootloader::bootinfo::MemoryMap;
+use bootloader::{entry_point, BootInfo};
+use core::panic::PanicInfo;
+use operating_system::std::memory::BootInfoFrameAllocator;
+use operating_system::std::once::Once;
+use x86_64::structures::paging::FrameAllocator;
+
+// The memory map of the boot info, handed over to the tests.
+static MEMORY_MAP: Once<&'static MemoryMap> = Once::new();
+
+entry_point!(main);
+
+// noinspection RsUnresolvedReference
+fn main(boot_info: &'static BootInfo) -> ! {
+    MEMORY_MAP.call_once(|| &boot_info.memory_map);
+    test_main();
+
+    loop {}
+}
+
+#[test_case]
+fn test_allocate_distinct_frames() {
+    let memory_map = *MEMORY_MAP.r#try().expect("memory map not set");
+    let mut frame_allocator = unsafe { BootInfoFrameAllocator::init(memory_map) };
+
+    let first = frame_allocator.allocate_frame().expect("no usable frame");
+    let second = frame_allocator.allocate_frame().expect("no second usable frame");
+
+    assert_ne!(first.start_address(), second.start_address());
+    assert_eq!(first.start_address().as_u64() % 4096, 0);
+}
+
+#[panic_handler]
+fn panic(info: &PanicInfo) -> ! {
+    operating_system::test_panic_handler(info)
+}
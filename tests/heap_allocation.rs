@@ -0,0 +1,92 @@
+#![no_std]
+#![no_main]
+#![feature(custom_test_frameworks)]
+#![test_runner(operating_system::test_runner)]
+#![reexport_test_harness_main = "test_main"]
+
+extern crate alloc;
+
+use alloc::{boxed::Box, vec::Vec};
+use bootloader::{entry_point, BootInfo};
+use core::panic::PanicInfo;
+use operating_system::std::allocator::{self, HEAP_SIZE};
+use operating_system::std::memory::{self, BootInfoFrameAllocator};
+use x86_64::VirtAddr;
+
+entry_point!(main);
+
+// noinspection RsUnresolvedReference
+fn main(boot_info: &'static BootInfo) -> ! {
+    operating_system::init();
+
+    let physical_memory_offset = VirtAddr::new(boot_info.physical_memory_offset);
+    let mut mapper = unsafe { memory::init(physical_memory_offset) };
+    let mut frame_allocator = unsafe { BootInfoFrameAllocator::init(&boot_info.memory_map) };
+    allocator::init_heap(&mut mapper, &mut frame_allocator).expect("heap initialization failed");
+
+    test_main();
+
+    loop {}
+}
+
+#[test_case]
+fn test_simple_allocation() {
+    let heap_value_1 = Box::new(41);
+    let heap_value_2 = Box::new(13);
+
+    assert_eq!(*heap_value_1, 41);
+    assert_eq!(*heap_value_2, 13);
+    assert_ne!(&*heap_value_1 as *const i32, &*heap_value_2 as *const i32);
+}
+
+#[test_case]
+fn test_allocation_is_traced() {
+    use operating_system::std::trace;
+
+    // the volatile read keeps the unused allocation from being optimized away.
+    let value = Box::new([0u8; 24]);
+    assert_eq!(unsafe { core::ptr::read_volatile(&value[0]) }, 0);
+
+    assert!(trace::TRACE
+        .events()
+        .any(|event| event.kind == trace::ALLOCATION && event.data == 24));
+    drop(value);
+}
+
+#[test_case]
+fn test_large_vec() {
+    let n = 1000;
+    let mut vec = Vec::new();
+    for i in 0..n {
+        vec.push(i);
+    }
+
+    assert_eq!(vec.iter().sum::<u64>(), (n - 1) * n / 2);
+}
+
+#[test_case]
+fn test_many_boxes() {
+    // every box is freed before the next allocation, more boxes than fit
+    // into the heap at once only succeed if the memory is reused.
+    for i in 0..HEAP_SIZE {
+        let x = Box::new(i);
+        assert_eq!(*x, i);
+    }
+}
+
+#[test_case]
+fn test_live_allocations_do_not_overlap() {
+    let boxes: Vec<Box<[u8; 64]>> = (0..32u8).map(|i| Box::new([i; 64])).collect();
+
+    // writing a distinct pattern into every allocation leaves the others intact.
+    for (i, values) in boxes.iter().enumerate() {
+        assert!(values.iter().all(|&value| value == i as u8));
+    }
+
+    assert!(allocator::used() >= 32 * 64);
+}
+
+#[panic_handler]
+fn panic(info: &PanicInfo) -> ! {
+    operating_system::test_panic_handler(info)
+}
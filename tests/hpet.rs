@@ -0,0 +1,46 @@
+#![no_std]
+#![no_main]
+#![feature(custom_test_frameworks)]
+#![test_runner(operating_system::test_runner)]
+#![reexport_test_harness_main = "test_main"]
+
+use bootloader::{entry_point, BootInfo};
+use core::panic::PanicInfo;
+use operating_system::std::{hpet, memory};
+use x86_64::{PhysAddr, VirtAddr};
+
+entry_point!(main);
+
+// noinspection RsUnresolvedReference
+fn main(boot_info: &'static BootInfo) -> ! {
+    let physical_memory_offset = VirtAddr::new(boot_info.physical_memory_offset);
+    let mut mapper = unsafe { memory::init(physical_memory_offset) };
+    let mut frame_allocator =
+        unsafe { memory::BootInfoFrameAllocator::init(&boot_info.memory_map) };
+
+    hpet::init(
+        PhysAddr::new(hpet::DEFAULT_HPET_ADDRESS),
+        &mut mapper,
+        &mut frame_allocator,
+    )
+    .expect("HPET init failed");
+
+    test_main();
+
+    loop {}
+}
+
+#[test_case]
+fn test_hpet_counter_increases() {
+    assert!(hpet::is_present());
+
+    let first = hpet::now_ns();
+    let second = hpet::now_ns();
+
+    assert!(second > first);
+}
+
+#[panic_handler]
+fn panic(info: &PanicInfo) -> ! {
+    operating_system::test_panic_handler(info)
+}
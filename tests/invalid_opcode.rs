@@ -0,0 +1,36 @@
+#![no_std]
+#![no_main]
+#![feature(asm)]
+
+use core::panic::PanicInfo;
+use operating_system::std::interrupts::{self, Exception};
+use operating_system::{exit_qemu, serial_print, serial_println, QemuExitCode};
+
+// The test does not install its own IDT. The kernel's handler halts forever,
+// so the exception hook checks that it was entered instead and exits QEMU.
+#[no_mangle]
+pub extern "C" fn _start() -> ! {
+    serial_print!("invalid_opcode::ud2...");
+
+    operating_system::std::gdt::init();
+    interrupts::init_idt();
+    interrupts::set_exception_hook(Some(exception_hook));
+
+    unsafe { asm!("ud2") };
+
+    panic!("Execution continued after ud2")
+}
+
+fn exception_hook(exception: Exception) {
+    if exception != Exception::InvalidOpcode {
+        panic!("ud2 raised {:?} instead of an invalid opcode", exception);
+    }
+
+    serial_println!("[ok]");
+    exit_qemu(QemuExitCode::Success);
+}
+
+#[panic_handler]
+fn panic(info: &PanicInfo) -> ! {
+    operating_system::test_panic_handler(info)
+}
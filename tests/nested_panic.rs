@@ -0,0 +1,47 @@
+#![no_std]
+#![no_main]
+
+use core::panic::PanicInfo;
+use operating_system::std::panic;
+use operating_system::std::vga_buffer::{kernel_writer, screen_contains};
+use operating_system::{exit_qemu, serial_print, serial_println, QemuExitCode};
+
+const FIRST_MESSAGE: &str = "panic while the writer is locked";
+const NESTED_MESSAGE: &str = "panic while reporting";
+
+#[no_mangle]
+pub extern "C" fn _start() -> ! {
+    serial_print!("nested_panic::nested_panic...");
+
+    // the guard is never dropped, as if the panic happened inside a `println!`.
+    core::mem::forget(kernel_writer().lock());
+    panic!("panic while the writer is locked");
+}
+
+#[panic_handler]
+fn panic(info: &PanicInfo) -> ! {
+    if panic::depth() > 0 {
+        // the nested panic must only be reported on the serial port.
+        panic::report(info);
+
+        if screen_contains(NESTED_MESSAGE) {
+            serial_println!("[nested panic written to the screen]");
+            exit_qemu(QemuExitCode::Failed);
+        }
+
+        serial_println!("[ok]");
+        exit_qemu(QemuExitCode::Success);
+        loop {}
+    }
+
+    // without the recovery in the panic path this would wait forever on the
+    // writer lock still held by `_start`.
+    panic::report(info);
+
+    if !screen_contains(FIRST_MESSAGE) {
+        serial_println!("[panic message missing from the screen]");
+        exit_qemu(QemuExitCode::Failed);
+    }
+
+    panic!("panic while reporting");
+}
@@ -0,0 +1,42 @@
+#![no_std]
+#![no_main]
+#![feature(asm)]
+
+use core::panic::PanicInfo;
+use operating_system::std::interrupts::{self, nmi_status, Exception, NmiStatus};
+use operating_system::{exit_qemu, serial_print, serial_println, QemuExitCode};
+
+// The test does not install its own IDT. The kernel's handler halts forever,
+// so the exception hook checks that it was entered instead and exits QEMU.
+#[no_mangle]
+pub extern "C" fn _start() -> ! {
+    serial_print!("nmi::int_2...");
+
+    operating_system::std::gdt::init();
+    interrupts::init_idt();
+    interrupts::set_exception_hook(Some(exception_hook));
+
+    unsafe { asm!("int 2") };
+
+    panic!("Execution continued after int 2")
+}
+
+// A software NMI sets none of the hardware error bits.
+fn exception_hook(exception: Exception) {
+    if exception != Exception::NonMaskableInterrupt {
+        panic!("int 2 raised {:?} instead of an NMI", exception);
+    }
+
+    let status = nmi_status();
+    if status != NmiStatus::default() {
+        panic!("unexpected NMI status: {}", status);
+    }
+
+    serial_println!("[ok]");
+    exit_qemu(QemuExitCode::Success);
+}
+
+#[panic_handler]
+fn panic(info: &PanicInfo) -> ! {
+    operating_system::test_panic_handler(info)
+}
@@ -0,0 +1,39 @@
+#![no_std]
+#![no_main]
+#![feature(asm)]
+
+use core::panic::PanicInfo;
+use operating_system::std::interrupts::{self, Exception};
+use operating_system::{exit_qemu, serial_print, serial_println, QemuExitCode};
+
+// Like `double_fault_action`, this test does not install its own IDT. The
+// kernel's handlers halt forever, so the exception hook reports which of
+// them was entered instead and exits QEMU.
+#[no_mangle]
+pub extern "C" fn _start() -> ! {
+    serial_print!("overflow::int_4...");
+
+    operating_system::std::gdt::init();
+    interrupts::init_idt();
+    interrupts::set_exception_hook(Some(exception_hook));
+
+    // `into` and `bound` do not exist in 64-bit mode, the exception is
+    // raised in software instead.
+    unsafe { asm!("int 4") };
+
+    panic!("Execution continued after int 4")
+}
+
+fn exception_hook(exception: Exception) {
+    if exception != Exception::Overflow {
+        panic!("int 4 raised {:?} instead of an overflow", exception);
+    }
+
+    serial_println!("[ok]");
+    exit_qemu(QemuExitCode::Success);
+}
+
+#[panic_handler]
+fn panic(info: &PanicInfo) -> ! {
+    operating_system::test_panic_handler(info)
+}
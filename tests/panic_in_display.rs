@@ -0,0 +1,39 @@
+#![no_std]
+#![no_main]
+
+use core::fmt;
+use core::panic::PanicInfo;
+use operating_system::{exit_qemu, println, serial_print, serial_println, QemuExitCode};
+
+// A type whose formatting panics part way through a `println!`, leaving the
+// VGA writer locked when the panic handler runs.
+struct PanickingDisplay;
+
+impl fmt::Display for PanickingDisplay {
+    fn fmt(&self, _f: &mut fmt::Formatter) -> fmt::Result {
+        panic!("Display implementation panicked");
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn _start() -> ! {
+    serial_print!("panic_in_display::panic_in_display...");
+    println!("{}", PanickingDisplay);
+
+    serial_println!("[test did not panic]");
+    exit_qemu(QemuExitCode::Failed);
+    loop {}
+}
+
+#[panic_handler]
+fn panic(info: &PanicInfo) -> ! {
+    operating_system::std::panic::begin();
+
+    // without the recovery in the panic path this would wait forever on the
+    // writer lock still held by the panicking `println!`.
+    println!("{}", info);
+
+    serial_println!("[ok]");
+    exit_qemu(QemuExitCode::Success);
+    loop {}
+}
@@ -0,0 +1,49 @@
+#![no_std]
+#![no_main]
+#![feature(custom_test_frameworks)]
+#![test_runner(operating_system::test_runner)]
+#![reexport_test_harness_main = "test_main"]
+
+use bootloader::{entry_point, BootInfo};
+use core::panic::PanicInfo;
+use core::sync::atomic::{AtomicU64, Ordering};
+use operating_system::std::memory;
+use x86_64::{PhysAddr, VirtAddr};
+
+// The offset of the complete physical memory mapping, handed over from the
+// boot info to the tests.
+static PHYSICAL_MEMORY_OFFSET: AtomicU64 = AtomicU64::new(0);
+
+entry_point!(main);
+
+// noinspection RsUnresolvedReference
+fn main(boot_info: &'static BootInfo) -> ! {
+    PHYSICAL_MEMORY_OFFSET.store(boot_info.physical_memory_offset, Ordering::SeqCst);
+    test_main();
+
+    loop {}
+}
+
+#[test_case]
+fn test_phys_slice_reads_vga_buffer() {
+    use core::fmt::Write;
+    use operating_system::std::vga_buffer::kernel_writer;
+    use x86_64::instructions::interrupts;
+
+    let offset = VirtAddr::new(PHYSICAL_MEMORY_OFFSET.load(Ordering::SeqCst));
+
+    interrupts::without_interrupts(|| {
+        let mut writer = kernel_writer().lock();
+        writeln!(writer, "\nZ").expect("writeln failed");
+
+        // the character is written to the first column of the second to last row,
+        // each cell being an ascii byte followed by a color byte.
+        let vga = unsafe { memory::phys_slice(PhysAddr::new(0xb8000), 80 * 25 * 2, offset) };
+        assert_eq!(vga[(23 * 80) * 2], b'Z');
+    });
+}
+
+#[panic_handler]
+fn panic(info: &PanicInfo) -> ! {
+    operating_system::test_panic_handler(info)
+}
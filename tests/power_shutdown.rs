@@ -0,0 +1,20 @@
+#![no_std]
+#![no_main]
+
+use core::panic::PanicInfo;
+use operating_system::{serial_print, serial_println};
+
+// Shutting down exits QEMU through the isa-debug-exit device with the success
+// code, reaching the end of `_start` means the exit never happened.
+#[no_mangle]
+pub extern "C" fn _start() -> ! {
+    serial_print!("power_shutdown::shutdown...");
+    serial_println!("[ok]");
+
+    operating_system::std::power::shutdown();
+}
+
+#[panic_handler]
+fn panic(info: &PanicInfo) -> ! {
+    operating_system::test_panic_handler(info)
+}
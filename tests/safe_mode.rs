@@ -0,0 +1,53 @@
+#![no_std]
+#![no_main]
+#![feature(custom_test_frameworks)]
+#![test_runner(operating_system::test_runner)]
+#![reexport_test_harness_main = "test_main"]
+
+use bootloader::{entry_point, BootInfo};
+use core::panic::PanicInfo;
+use operating_system::println;
+use operating_system::std::boot::{self, BootMode, Subsystem};
+use operating_system::std::vga_buffer::screen_contains;
+
+entry_point!(main);
+
+// noinspection RsUnresolvedReference
+fn main(boot_info: &'static BootInfo) -> ! {
+    operating_system::init_with_mode(BootMode::Safe);
+    // the same memory setup as the kernel, which offers the heap to safe mode.
+    operating_system::init_memory(boot_info);
+    test_main();
+
+    loop {}
+}
+
+#[test_case]
+fn test_safe_mode_skips_optional_subsystems() {
+    assert_eq!(boot::mode(), BootMode::Safe);
+
+    assert!(!boot::was_attempted(Subsystem::Heap));
+    assert!(boot::was_skipped(Subsystem::Heap));
+    assert!(!boot::was_attempted(Subsystem::Disk));
+    assert!(boot::was_skipped(Subsystem::Disk));
+    assert!(screen_contains("safe mode: skipped Heap initialization"));
+}
+
+#[test_case]
+fn test_safe_mode_can_print() {
+    println!("test_safe_mode_can_print output");
+    assert!(screen_contains("test_safe_mode_can_print output"));
+}
+
+#[test_case]
+fn test_safe_mode_prompt_status() {
+    let mut out = operating_system::TestOutput::<128>::new();
+
+    boot::prompt_command_to(&mut out, "status").unwrap();
+    assert_eq!(out.as_str(), "mode: Safe\nskipped: Heap Disk Mouse\n");
+}
+
+#[panic_handler]
+fn panic(info: &PanicInfo) -> ! {
+    operating_system::test_panic_handler(info)
+}
@@ -4,6 +4,12 @@
 use core::panic::PanicInfo;
 use operating_system::{exit_qemu, serial_print, serial_println, QemuExitCode};
 
+// Tests that are expected to panic cannot run under the custom test runner,
+// which treats every panic as a failure and stops at the first one. Each such
+// test is a binary of its own with `harness = false` in Cargo.toml: the panic
+// handler reports success, and reaching the end of the test without panicking
+// reports failure. Only a single test can be run per binary, since nothing
+// runs after the panic handler.
 #[no_mangle]
 pub extern "C" fn _start() -> ! {
     should_fail();
@@ -0,0 +1,29 @@
+#![no_std]
+#![no_main]
+
+use core::panic::PanicInfo;
+use operating_system::{serial_print, serial_println, Outcome};
+
+// The shutdown sequence writes its report to serial and then exits QEMU with
+// the success code, reaching the end of `_start` means the exit never happened.
+#[no_mangle]
+pub extern "C" fn _start() -> ! {
+    serial_print!("shutdown_sequence::success...");
+
+    operating_system::set_shutdown_hook(Some(check_report));
+    operating_system::shutdown_sequence(Outcome::Success);
+}
+
+// Run once the report has been written to serial, right before the exit.
+fn check_report(report: &str) {
+    assert!(report.starts_with("shutdown: Success\n"));
+    assert!(report.contains("uptime: "));
+    assert!(report.contains("timer interrupts: "));
+
+    serial_println!("[ok]");
+}
+
+#[panic_handler]
+fn panic(info: &PanicInfo) -> ! {
+    operating_system::test_panic_handler(info)
+}
@@ -3,13 +3,15 @@
 #![feature(abi_x86_interrupt)]
 
 use core::panic::PanicInfo;
+use operating_system::std::once::Once;
 use operating_system::{exit_qemu, serial_print, serial_println, QemuExitCode};
 use x86_64::structures::idt::InterruptStackFrame;
 use x86_64::structures::idt::InterruptDescriptorTable;
-use lazy_static::lazy_static;
 
-lazy_static! {
-    static ref TEST_IDT: InterruptDescriptorTable = {
+static TEST_IDT: Once<InterruptDescriptorTable> = Once::new();
+
+fn test_idt() -> &'static InterruptDescriptorTable {
+    TEST_IDT.call_once(|| {
         let mut idt = InterruptDescriptorTable::new();
         unsafe {
             idt.double_fault
@@ -18,7 +20,7 @@ lazy_static! {
         }
 
         idt
-    };
+    })
 }
 
 #[no_mangle]
@@ -27,7 +29,7 @@ pub extern "C" fn _start() -> ! {
 
 
     operating_system::std::gdt::init();
-    TEST_IDT.load();
+    test_idt().load();
 
     stack_overflow();
 
@@ -0,0 +1,74 @@
+#![no_std]
+#![no_main]
+
+use core::panic::PanicInfo;
+use operating_system::std::interrupts::{self, Exception, SelectorTable};
+use operating_system::std::once::Once;
+use operating_system::{exit_qemu, serial_print, serial_println, QemuExitCode};
+use x86_64::structures::gdt::{
+    Descriptor, DescriptorFlags, GlobalDescriptorTable, SegmentSelector,
+};
+
+// The index in the test GDT of the data segment that is not present.
+const NOT_PRESENT_INDEX: u16 = 2;
+
+static TEST_GDT: Once<(GlobalDescriptorTable, SegmentSelector, SegmentSelector)> = Once::new();
+
+fn test_gdt() -> &'static (GlobalDescriptorTable, SegmentSelector, SegmentSelector) {
+    TEST_GDT.call_once(|| {
+        let mut gdt = GlobalDescriptorTable::new();
+
+        let code = gdt.add_entry(Descriptor::kernel_code_segment());
+        // a writable data segment without the present bit.
+        let flags = DescriptorFlags::USER_SEGMENT | DescriptorFlags::WRITABLE;
+        let not_present = gdt.add_entry(Descriptor::UserSegment(flags.bits()));
+
+        (gdt, code, not_present)
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn _start() -> ! {
+    use x86_64::instructions::segmentation::{load_ss, set_cs};
+
+    serial_print!("stack_segment_fault::load_ss...");
+
+    let gdt = test_gdt();
+    gdt.0.load();
+    unsafe { set_cs(gdt.1) };
+
+    // the kernel's IDT is built after switching to the test GDT, so that its
+    // entries use the code segment of the test GDT.
+    interrupts::init_idt();
+    interrupts::set_exception_hook(Some(exception_hook));
+
+    unsafe { load_ss(gdt.2) };
+
+    panic!("Execution continued after loading a segment that is not present")
+}
+
+// The kernel's handler halts forever after printing the decoded error code,
+// so the exception hook checks that it names the loaded selector and exits
+// QEMU.
+fn exception_hook(exception: Exception) {
+    let selector = match exception {
+        Exception::StackSegmentFault(selector) => selector,
+        _ => panic!(
+            "load_ss raised {:?} instead of a stack segment fault",
+            exception
+        ),
+    };
+    serial_print!("{}...", selector);
+
+    assert_eq!(selector.table, SelectorTable::Gdt);
+    assert_eq!(selector.index, NOT_PRESENT_INDEX);
+    assert!(!selector.external);
+
+    serial_println!("[ok]");
+    exit_qemu(QemuExitCode::Success);
+}
+
+#[panic_handler]
+fn panic(info: &PanicInfo) -> ! {
+    operating_system::test_panic_handler(info)
+}
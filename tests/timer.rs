@@ -0,0 +1,58 @@
+#![no_std]
+#![no_main]
+
+use core::panic::PanicInfo;
+use operating_system::std::{interrupts, rtc};
+use operating_system::{exit_qemu, serial_print, serial_println, QemuExitCode};
+
+// The number of timer ticks waited for, 50ms at the default frequency.
+const WAIT_TICKS: u64 = 5;
+
+// The number of time stamp counter cycles after which the timer is given up
+// on, a few seconds on any modern CPU.
+const TIMEOUT_CYCLES: u64 = 8_000_000_000;
+
+#[no_mangle]
+pub extern "C" fn _start() -> ! {
+    serial_print!("timer::timer_interrupt_delivered...");
+
+    // installs the IDT, programs the PIT and PICs and enables interrupts.
+    operating_system::init();
+    assert!(x86_64::instructions::interrupts::are_enabled());
+
+    // the RTC keeps waking the halt loop up, so that a dead timer fails once
+    // the timeout has passed rather than halting forever.
+    rtc::enable_periodic_interrupt(rtc::MAX_RATE);
+
+    let start = interrupts::ticks();
+    let deadline = read_tsc() + TIMEOUT_CYCLES;
+
+    while interrupts::ticks() - start < WAIT_TICKS {
+        if read_tsc() >= deadline {
+            rtc::disable_periodic_interrupt();
+            serial_println!(
+                "[failed]\n\nError: {} of {} timer ticks before the timeout\n",
+                interrupts::ticks() - start,
+                WAIT_TICKS
+            );
+            exit_qemu(QemuExitCode::Failed);
+            loop {}
+        }
+
+        x86_64::instructions::hlt();
+    }
+
+    rtc::disable_periodic_interrupt();
+    serial_println!("[ok]");
+    exit_qemu(QemuExitCode::Success);
+    loop {}
+}
+
+fn read_tsc() -> u64 {
+    unsafe { core::arch::x86_64::_rdtsc() }
+}
+
+#[panic_handler]
+fn panic(info: &PanicInfo) -> ! {
+    operating_system::test_panic_handler(info)
+}